@@ -0,0 +1,673 @@
+//! Checked, saturating and overflowing arithmetic on dates, timestamps and
+//! intervals.
+//!
+//! Every operation comes in three forms, after the integer methods of the
+//! same names:
+//!
+//! - `checked_*` returns None if the result overflows, or for dates and
+//!   timestamps if it fails `IS_VALID_DATE`/`IS_VALID_TIMESTAMP`.
+//! - `saturating_*` clamps such a result to `MIN_DATE`/`MAX_DATE` or
+//!   `MIN_TIMESTAMP`/`MAX_TIMESTAMP`. Each field of an interval clamps to
+//!   the bounds of its type on its own, and a finite interval never clamps
+//!   to an infinity, only to the largest finite interval of its sign.
+//! - `overflowing_*` returns the saturating result and whether it was
+//!   clamped.
+//!
+//! Times and times with zone stay within 00:00:00 and 24:00:00. Where
+//! PostgreSQL wraps a time around midnight, these overflow instead.
+//!
+//! Infinite dates and timestamps are returned unchanged, as by
+//! `timestamp_pl_interval`. Infinite intervals follow PostgreSQL: an
+//! infinity plus a finite interval or the same infinity is that infinity,
+//! and a finite date or timestamp plus an infinite interval is that
+//! infinity. The sum of opposite infinities and an infinity times zero are
+//! undefined; they have no checked result, intervals saturate to a zero
+//! interval, much as NaN converts to the integer 0, and timestamps are left
+//! unchanged. Division by zero is undefined in the same way.
+//!
+//! The difference of two dates or of two timestamps always fits, so those
+//! have only checked forms, which fail for infinite input.
+
+use super::{date_is_nobegin, date_is_noend, date_not_finite, interval_is_nobegin,
+            interval_is_noend, timestamp_not_finite, timestamp_pl_interval, Date, Interval,
+            Time, TimeTz, Timestamp, DAYS_PER_MONTH, DT_NOBEGIN, DT_NOEND, INTERVAL_NOBEGIN,
+            INTERVAL_NOEND, MAX_DATE, MAX_TIMESTAMP, MIN_DATE, MIN_TIMESTAMP, USECS_PER_DAY};
+
+fn checked<T>(result: (T, bool)) -> Option<T> {
+  match result {
+    (v, false) => Some(v),
+    (_, true) => None
+  }
+}
+
+/// Clamp v to min..=max, and say whether it had to be.
+fn clamp(v: i128, min: i128, max: i128) -> (i128, bool) {
+  if v < min {
+    (min, true)
+  } else if v > max {
+    (max, true)
+  } else {
+    (v, false)
+  }
+}
+
+// ---------------------------------------------------------------------------
+// Dates
+// ---------------------------------------------------------------------------
+
+fn date_pl(date: Date, days: i128) -> (Date, bool) {
+  if date_not_finite(date) {
+    return (date, false);
+  }
+
+  let (result, overflow) = clamp(date as i128 + days, MIN_DATE as i128, MAX_DATE as i128);
+  (result as Date, overflow)
+}
+
+/// date_pli()
+/// Add days to a date. Return None if the result is out of range.
+pub fn checked_date_pli(date: Date, days: i32) -> Option<Date> {
+  checked(date_pl(date, days as i128))
+}
+
+pub fn saturating_date_pli(date: Date, days: i32) -> Date {
+  date_pl(date, days as i128).0
+}
+
+pub fn overflowing_date_pli(date: Date, days: i32) -> (Date, bool) {
+  date_pl(date, days as i128)
+}
+
+/// date_mii()
+/// Subtract days from a date. Return None if the result is out of range.
+pub fn checked_date_mii(date: Date, days: i32) -> Option<Date> {
+  checked(date_pl(date, -(days as i128)))
+}
+
+pub fn saturating_date_mii(date: Date, days: i32) -> Date {
+  date_pl(date, -(days as i128)).0
+}
+
+pub fn overflowing_date_mii(date: Date, days: i32) -> (Date, bool) {
+  date_pl(date, -(days as i128))
+}
+
+/// date_mi()
+/// The number of days from b to a. Return None if either is infinite.
+pub fn checked_date_mi(a: Date, b: Date) -> Option<i32> {
+  if date_not_finite(a) || date_not_finite(b) {
+    return None;
+  }
+  a.checked_sub(b)
+}
+
+// ---------------------------------------------------------------------------
+// Timestamps
+// ---------------------------------------------------------------------------
+
+/// t + month months + day days + time microseconds, computed as
+/// timestamp_pl_interval() does but without intermediate overflow.
+fn timestamp_pl(t: Timestamp, month: i128, day: i128, time: i128) -> (Timestamp, bool) {
+  if timestamp_not_finite(t) {
+    return (t, false);
+  }
+
+  let up = if month > 0 { MAX_TIMESTAMP } else { MIN_TIMESTAMP };
+  let mut t = t;
+  if month != 0 {
+    // the months are added first, so if they overflow their sign decides
+    if month < ::std::i32::MIN as i128 || month > ::std::i32::MAX as i128 {
+      return (up, true);
+    }
+    t = match timestamp_pl_interval(t, &Interval::new(month as i32, 0, 0)) {
+      Some(t) => t,
+      None => return (up, true)
+    };
+  }
+
+  let result = t as i128 + day * USECS_PER_DAY as i128 + time;
+  let (result, overflow) = clamp(result, MIN_TIMESTAMP as i128, MAX_TIMESTAMP as i128);
+  (result as Timestamp, overflow)
+}
+
+/// t + sign * span
+fn timestamp_pl_span(t: Timestamp, span: &Interval, sign: i128) -> (Timestamp, bool) {
+  let inf = sign * infinity(span);
+  if inf != 0 {
+    let result = if inf > 0 { DT_NOEND } else { DT_NOBEGIN };
+    // opposite infinities leave t as it is
+    return if timestamp_not_finite(t) && t != result { (t, true) } else { (result, false) };
+  }
+
+  timestamp_pl(t, sign * span.month as i128, sign * span.day as i128, sign * span.time as i128)
+}
+
+/// timestamp_pl_interval()
+/// Add an interval to a timestamp. Return None if the result is out of
+/// range, or for opposite infinities.
+pub fn checked_timestamp_pl_interval(t: Timestamp, span: &Interval) -> Option<Timestamp> {
+  checked(timestamp_pl_span(t, span, 1))
+}
+
+pub fn saturating_timestamp_pl_interval(t: Timestamp, span: &Interval) -> Timestamp {
+  timestamp_pl_span(t, span, 1).0
+}
+
+pub fn overflowing_timestamp_pl_interval(t: Timestamp, span: &Interval) -> (Timestamp, bool) {
+  timestamp_pl_span(t, span, 1)
+}
+
+/// timestamp_mi_interval()
+/// Subtract an interval from a timestamp. Return None if the result is out
+/// of range, or for equal infinities.
+pub fn checked_timestamp_mi_interval(t: Timestamp, span: &Interval) -> Option<Timestamp> {
+  checked(timestamp_pl_span(t, span, -1))
+}
+
+pub fn saturating_timestamp_mi_interval(t: Timestamp, span: &Interval) -> Timestamp {
+  timestamp_pl_span(t, span, -1).0
+}
+
+pub fn overflowing_timestamp_mi_interval(t: Timestamp, span: &Interval) -> (Timestamp, bool) {
+  timestamp_pl_span(t, span, -1)
+}
+
+/// date + sign * span, from midnight of the date. A date past the range of
+/// timestamps overflows, as in date2timestamp().
+fn date_pl_span(date: Date, span: &Interval, sign: i128) -> (Timestamp, bool) {
+  let t = if date_is_nobegin(date) {
+    DT_NOBEGIN
+  } else if date_is_noend(date) {
+    DT_NOEND
+  } else {
+    let t = date as i128 * USECS_PER_DAY as i128;
+    match clamp(t, MIN_TIMESTAMP as i128, MAX_TIMESTAMP as i128) {
+      (t, false) => t as Timestamp,
+      (t, true) => return (t as Timestamp, true)
+    }
+  };
+
+  timestamp_pl_span(t, span, sign)
+}
+
+/// date_pl_interval()
+/// Add an interval to a date, giving a timestamp. Return None if the
+/// result is out of range, or for opposite infinities.
+pub fn checked_date_pl_interval(date: Date, span: &Interval) -> Option<Timestamp> {
+  checked(date_pl_span(date, span, 1))
+}
+
+pub fn saturating_date_pl_interval(date: Date, span: &Interval) -> Timestamp {
+  date_pl_span(date, span, 1).0
+}
+
+pub fn overflowing_date_pl_interval(date: Date, span: &Interval) -> (Timestamp, bool) {
+  date_pl_span(date, span, 1)
+}
+
+/// date_mi_interval()
+/// Subtract an interval from a date, giving a timestamp. Return None if the
+/// result is out of range, or for equal infinities.
+pub fn checked_date_mi_interval(date: Date, span: &Interval) -> Option<Timestamp> {
+  checked(date_pl_span(date, span, -1))
+}
+
+pub fn saturating_date_mi_interval(date: Date, span: &Interval) -> Timestamp {
+  date_pl_span(date, span, -1).0
+}
+
+pub fn overflowing_date_mi_interval(date: Date, span: &Interval) -> (Timestamp, bool) {
+  date_pl_span(date, span, -1)
+}
+
+/// timestamp_mi()
+/// The interval from b to a, in days and time as after justify_hours().
+/// Return None if either is infinite.
+pub fn checked_timestamp_mi(a: Timestamp, b: Timestamp) -> Option<Interval> {
+  if timestamp_not_finite(a) || timestamp_not_finite(b) {
+    return None;
+  }
+
+  let diff = a as i128 - b as i128;
+  let day = diff / USECS_PER_DAY as i128;
+  Some(Interval::new(0, day as i32, (diff - day * USECS_PER_DAY as i128) as i64))
+}
+
+// ---------------------------------------------------------------------------
+// Times
+// ---------------------------------------------------------------------------
+
+/// time + sign * span. Only the time of the interval counts, as in
+/// time_pl_interval().
+fn time_pl_span(time: Time, span: &Interval, sign: i128) -> (Time, bool) {
+  let inf = sign * infinity(span);
+  if inf != 0 {
+    return (if inf > 0 { USECS_PER_DAY } else { 0 }, true);
+  }
+
+  let (result, overflow) = clamp(time as i128 + sign * span.time as i128,
+                                 0, USECS_PER_DAY as i128);
+  (result as Time, overflow)
+}
+
+/// time_pl_interval()
+/// Add the time of an interval to a time. Return None if the result is
+/// outside the day, or for an infinite interval.
+pub fn checked_time_pl_interval(time: Time, span: &Interval) -> Option<Time> {
+  checked(time_pl_span(time, span, 1))
+}
+
+pub fn saturating_time_pl_interval(time: Time, span: &Interval) -> Time {
+  time_pl_span(time, span, 1).0
+}
+
+pub fn overflowing_time_pl_interval(time: Time, span: &Interval) -> (Time, bool) {
+  time_pl_span(time, span, 1)
+}
+
+/// time_mi_interval()
+/// Subtract the time of an interval from a time. Return None if the result
+/// is outside the day, or for an infinite interval.
+pub fn checked_time_mi_interval(time: Time, span: &Interval) -> Option<Time> {
+  checked(time_pl_span(time, span, -1))
+}
+
+pub fn saturating_time_mi_interval(time: Time, span: &Interval) -> Time {
+  time_pl_span(time, span, -1).0
+}
+
+pub fn overflowing_time_mi_interval(time: Time, span: &Interval) -> (Time, bool) {
+  time_pl_span(time, span, -1)
+}
+
+fn timetz_pl_span(time: &TimeTz, span: &Interval, sign: i128) -> (TimeTz, bool) {
+  let (result, overflow) = time_pl_span(time.time, span, sign);
+  (TimeTz::new(result, time.zone), overflow)
+}
+
+/// timetz_pl_interval()
+/// Add the time of an interval to a time with zone, keeping the zone.
+/// Return None if the result is outside the day, or for an infinite
+/// interval.
+pub fn checked_timetz_pl_interval(time: &TimeTz, span: &Interval) -> Option<TimeTz> {
+  checked(timetz_pl_span(time, span, 1))
+}
+
+pub fn saturating_timetz_pl_interval(time: &TimeTz, span: &Interval) -> TimeTz {
+  timetz_pl_span(time, span, 1).0
+}
+
+pub fn overflowing_timetz_pl_interval(time: &TimeTz, span: &Interval) -> (TimeTz, bool) {
+  timetz_pl_span(time, span, 1)
+}
+
+/// timetz_mi_interval()
+/// Subtract the time of an interval from a time with zone, keeping the
+/// zone. Return None if the result is outside the day, or for an infinite
+/// interval.
+pub fn checked_timetz_mi_interval(time: &TimeTz, span: &Interval) -> Option<TimeTz> {
+  checked(timetz_pl_span(time, span, -1))
+}
+
+pub fn saturating_timetz_mi_interval(time: &TimeTz, span: &Interval) -> TimeTz {
+  timetz_pl_span(time, span, -1).0
+}
+
+pub fn overflowing_timetz_mi_interval(time: &TimeTz, span: &Interval) -> (TimeTz, bool) {
+  timetz_pl_span(time, span, -1)
+}
+
+// ---------------------------------------------------------------------------
+// Intervals
+// ---------------------------------------------------------------------------
+
+/// 1 for infinity, -1 for -infinity and 0 for a finite interval.
+fn infinity(span: &Interval) -> i128 {
+  if interval_is_noend(span) {
+    1
+  } else if interval_is_nobegin(span) {
+    -1
+  } else {
+    0
+  }
+}
+
+fn infinite(sign: i128) -> Interval {
+  if sign > 0 { INTERVAL_NOEND } else { INTERVAL_NOBEGIN }
+}
+
+/// An interval of exact fields, each clamped to its type.
+fn clamp_interval(month: i128, day: i128, time: i128) -> (Interval, bool) {
+  let (month, m) = clamp(month, ::std::i32::MIN as i128, ::std::i32::MAX as i128);
+  let (day, d) = clamp(day, ::std::i32::MIN as i128, ::std::i32::MAX as i128);
+  let (time, t) = clamp(time, ::std::i64::MIN as i128, ::std::i64::MAX as i128);
+  let span = Interval::new(month as i32, day as i32, time as i64);
+
+  // a finite result must not look like an infinity, so it stops one
+  // microsecond short of one
+  if interval_is_noend(&span) {
+    return (Interval::new(span.month, span.day, span.time - 1), true);
+  }
+  if interval_is_nobegin(&span) {
+    return (Interval::new(span.month, span.day, span.time + 1), true);
+  }
+  (span, m || d || t)
+}
+
+/// a + sign * b
+fn interval_pl(a: &Interval, b: &Interval, sign: i128) -> (Interval, bool) {
+  let (inf_a, inf_b) = (infinity(a), sign * infinity(b));
+  if inf_a != 0 && inf_b != 0 && inf_a != inf_b {
+    return (Interval::new(0, 0, 0), true);
+  }
+  if inf_a != 0 || inf_b != 0 {
+    return (infinite(inf_a + inf_b), false);
+  }
+
+  clamp_interval(a.month as i128 + sign * b.month as i128,
+                 a.day as i128 + sign * b.day as i128,
+                 a.time as i128 + sign * b.time as i128)
+}
+
+fn interval_mul(span: &Interval, factor: i32) -> (Interval, bool) {
+  let factor = factor as i128;
+  let inf = infinity(span);
+  if inf != 0 {
+    return if factor == 0 { (Interval::new(0, 0, 0), true) } else { (infinite(inf * factor), false) };
+  }
+
+  clamp_interval(span.month as i128 * factor, span.day as i128 * factor,
+                 span.time as i128 * factor)
+}
+
+/// n / d rounded to the nearest, halves to even, as rint() does.
+fn div_rint(n: i128, d: i128) -> i128 {
+  let q = n / d;
+  let r2 = 2 * (n % d).abs();
+  if r2 > d.abs() || (r2 == d.abs() && q % 2 != 0) {
+    if (n < 0) == (d < 0) { q + 1 } else { q - 1 }
+  } else {
+    q
+  }
+}
+
+fn interval_div(span: &Interval, divisor: i32) -> (Interval, bool) {
+  let divisor = divisor as i128;
+  if divisor == 0 {
+    return (Interval::new(0, 0, 0), true);
+  }
+  let inf = infinity(span);
+  if inf != 0 {
+    return (infinite(inf * divisor), false);
+  }
+
+  // what is left of the months cascades into days, at 30 days a month,
+  // and what is left of those into the time
+  let month = span.month as i128 / divisor;
+  let days = span.day as i128 + span.month as i128 % divisor * DAYS_PER_MONTH as i128;
+  let day = days / divisor;
+  let time = span.time as i128 + days % divisor * USECS_PER_DAY as i128;
+  clamp_interval(month, day, div_rint(time, divisor))
+}
+
+/// interval_pl()
+/// Add two intervals field by field. Return None on overflow.
+pub fn checked_interval_pl(a: &Interval, b: &Interval) -> Option<Interval> {
+  checked(interval_pl(a, b, 1))
+}
+
+pub fn saturating_interval_pl(a: &Interval, b: &Interval) -> Interval {
+  interval_pl(a, b, 1).0
+}
+
+pub fn overflowing_interval_pl(a: &Interval, b: &Interval) -> (Interval, bool) {
+  interval_pl(a, b, 1)
+}
+
+/// interval_mi()
+/// Subtract two intervals field by field. Return None on overflow.
+pub fn checked_interval_mi(a: &Interval, b: &Interval) -> Option<Interval> {
+  checked(interval_pl(a, b, -1))
+}
+
+pub fn saturating_interval_mi(a: &Interval, b: &Interval) -> Interval {
+  interval_pl(a, b, -1).0
+}
+
+pub fn overflowing_interval_mi(a: &Interval, b: &Interval) -> (Interval, bool) {
+  interval_pl(a, b, -1)
+}
+
+/// interval_mul()
+/// Multiply each field of an interval by a whole factor. Unlike
+/// PostgreSQL's, this takes no fractions, so nothing cascades into the
+/// smaller fields. Return None on overflow.
+pub fn checked_interval_mul(span: &Interval, factor: i32) -> Option<Interval> {
+  checked(interval_mul(span, factor))
+}
+
+pub fn saturating_interval_mul(span: &Interval, factor: i32) -> Interval {
+  interval_mul(span, factor).0
+}
+
+pub fn overflowing_interval_mul(span: &Interval, factor: i32) -> (Interval, bool) {
+  interval_mul(span, factor)
+}
+
+/// interval_div()
+/// Divide an interval by a whole divisor. The remainder of the months
+/// carries into the days and that of the days into the time, as in
+/// PostgreSQL, and the time is rounded to the microsecond. Return None on
+/// overflow or division by zero.
+pub fn checked_interval_div(span: &Interval, divisor: i32) -> Option<Interval> {
+  checked(interval_div(span, divisor))
+}
+
+pub fn saturating_interval_div(span: &Interval, divisor: i32) -> Interval {
+  interval_div(span, divisor).0
+}
+
+pub fn overflowing_interval_div(span: &Interval, divisor: i32) -> (Interval, bool) {
+  interval_div(span, divisor)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::{date_in, timestamp_in, DATEVAL_NOBEGIN, DATEVAL_NOEND, USECS_PER_HOUR};
+
+  fn date(s: &str) -> Date {
+    date_in(s).ok().unwrap()
+  }
+
+  fn ts(s: &str) -> Timestamp {
+    timestamp_in(s).ok().unwrap()
+  }
+
+  fn fields(span: &Interval) -> (i32, i32, i64) {
+    (span.month(), span.day(), span.time())
+  }
+
+  #[test]
+  fn test_date() {
+    assert_eq!(Some(date("2016-03-01")), checked_date_pli(date("2016-02-28"), 2));
+    assert_eq!(Some(date("2016-02-28")), checked_date_mii(date("2016-03-01"), 2));
+    assert_eq!(Some(MAX_DATE), checked_date_pli(MAX_DATE - 1, 1));
+    assert_eq!(None, checked_date_pli(MAX_DATE, 1));
+    assert_eq!(None, checked_date_mii(MIN_DATE, 1));
+    assert_eq!(None, checked_date_mii(0, ::std::i32::MIN));
+    assert_eq!(Some(DATEVAL_NOEND), checked_date_pli(DATEVAL_NOEND, -1));
+
+    assert_eq!(MAX_DATE, saturating_date_pli(0, ::std::i32::MAX));
+    assert_eq!(MIN_DATE, saturating_date_mii(0, ::std::i32::MAX));
+    assert_eq!(MAX_DATE, saturating_date_mii(0, ::std::i32::MIN));
+    assert_eq!(DATEVAL_NOBEGIN, saturating_date_pli(DATEVAL_NOBEGIN, 1));
+    assert_eq!((MAX_DATE, true), overflowing_date_pli(MAX_DATE, 1));
+    assert_eq!((MAX_DATE, false), overflowing_date_pli(MAX_DATE, 0));
+    assert_eq!((MIN_DATE, true), overflowing_date_pli(MIN_DATE - 1, 0));
+
+    assert_eq!(Some(2), checked_date_mi(date("2016-03-01"), date("2016-02-28")));
+    assert_eq!(Some(MAX_DATE - MIN_DATE), checked_date_mi(MAX_DATE, MIN_DATE));
+    assert_eq!(None, checked_date_mi(DATEVAL_NOEND, 0));
+  }
+
+  #[test]
+  fn test_timestamp() {
+    let t = ts("2016-01-31 10:00:00");
+    let span = Interval::new(1, 1, USECS_PER_HOUR);
+    assert_eq!(Some(ts("2016-03-01 11:00:00")), checked_timestamp_pl_interval(t, &span));
+    assert_eq!(Some(ts("2015-12-30 09:00:00")), checked_timestamp_mi_interval(t, &span));
+    assert_eq!(Some(DT_NOEND), checked_timestamp_pl_interval(DT_NOEND, &span));
+
+    assert_eq!(Some(MAX_TIMESTAMP), checked_timestamp_pl_interval(MAX_TIMESTAMP - 1,
+                                                                  &Interval::new(0, 0, 1)));
+    assert_eq!(None, checked_timestamp_pl_interval(MAX_TIMESTAMP, &Interval::new(0, 0, 1)));
+    assert_eq!(None, checked_timestamp_mi_interval(MIN_TIMESTAMP, &Interval::new(0, 0, 1)));
+    assert_eq!(None, checked_timestamp_pl_interval(t, &Interval::new(0, ::std::i32::MAX, 0)));
+
+    // the extreme fields cannot be negated as they are, but fit in the result
+    let span = Interval::new(0, 0, ::std::i64::MIN);
+    let t = ts("1000-01-01");
+    let expected = (t as i128 - ::std::i64::MIN as i128) as Timestamp;
+    assert_eq!(Some(expected), checked_timestamp_mi_interval(t, &span));
+
+    // the month step overflows even though the days would bring it back
+    let span = Interval::new(::std::i32::MAX, ::std::i32::MIN, 0);
+    assert_eq!((MAX_TIMESTAMP, true), overflowing_timestamp_pl_interval(t, &span));
+    assert_eq!((MIN_TIMESTAMP, true), overflowing_timestamp_mi_interval(t, &span));
+
+    assert_eq!(MIN_TIMESTAMP, saturating_timestamp_pl_interval(MIN_TIMESTAMP - 1,
+                                                               &Interval::new(0, 0, 0)));
+
+    // infinite intervals give infinite timestamps
+    assert_eq!(Some(DT_NOEND), checked_timestamp_pl_interval(t, &INTERVAL_NOEND));
+    assert_eq!(Some(DT_NOBEGIN), checked_timestamp_pl_interval(t, &INTERVAL_NOBEGIN));
+    assert_eq!(Some(DT_NOEND), checked_timestamp_mi_interval(t, &INTERVAL_NOBEGIN));
+    assert_eq!(Some(DT_NOEND), checked_timestamp_pl_interval(DT_NOEND, &INTERVAL_NOEND));
+    assert_eq!(None, checked_timestamp_pl_interval(DT_NOEND, &INTERVAL_NOBEGIN));
+    assert_eq!(None, checked_timestamp_mi_interval(DT_NOBEGIN, &INTERVAL_NOBEGIN));
+    assert_eq!(DT_NOBEGIN, saturating_timestamp_mi_interval(DT_NOBEGIN, &INTERVAL_NOBEGIN));
+    assert_eq!((DT_NOEND, false), overflowing_timestamp_pl_interval(MAX_TIMESTAMP, &INTERVAL_NOEND));
+    assert_eq!(timestamp_pl_interval(t, &INTERVAL_NOBEGIN),
+               checked_timestamp_pl_interval(t, &INTERVAL_NOBEGIN));
+    assert_eq!((t, false), overflowing_timestamp_pl_interval(t, &Interval::new(0, 0, 0)));
+
+    let a = ts("2016-03-01 11:00:00");
+    let b = ts("2016-01-31 10:00:00.5");
+    assert_eq!((0, 30, 3599500000), fields(&checked_timestamp_mi(a, b).unwrap()));
+    assert_eq!((0, -30, -3599500000), fields(&checked_timestamp_mi(b, a).unwrap()));
+    let span = checked_timestamp_mi(MAX_TIMESTAMP, MIN_TIMESTAMP).unwrap();
+    assert_eq!(Some(MAX_TIMESTAMP), checked_timestamp_pl_interval(MIN_TIMESTAMP, &span));
+    assert!(checked_timestamp_mi(DT_NOEND, a).is_none());
+  }
+
+  #[test]
+  fn test_date_interval() {
+    let d = date("2016-01-31");
+    let span = Interval::new(1, 1, USECS_PER_HOUR);
+    assert_eq!(Some(ts("2016-03-01 01:00:00")), checked_date_pl_interval(d, &span));
+    assert_eq!(Some(ts("2015-12-29 23:00:00")), checked_date_mi_interval(d, &span));
+    assert_eq!(Some(DT_NOEND), checked_date_pl_interval(DATEVAL_NOEND, &span));
+    assert_eq!(Some(DT_NOBEGIN), checked_date_mi_interval(d, &INTERVAL_NOEND));
+    assert_eq!(None, checked_date_pl_interval(DATEVAL_NOBEGIN, &INTERVAL_NOEND));
+
+    // dates reach further than timestamps
+    assert_eq!(None, checked_date_pl_interval(MAX_DATE, &Interval::new(0, 0, 0)));
+    assert_eq!((MAX_TIMESTAMP, true), overflowing_date_mi_interval(MAX_DATE, &span));
+    assert_eq!(None, checked_date_pl_interval(date("294276-12-31"), &Interval::new(0, 1, 0)));
+    assert_eq!(MAX_TIMESTAMP,
+               saturating_date_pl_interval(date("294276-12-31"), &Interval::new(0, 1, 0)));
+    assert_eq!(MIN_TIMESTAMP, saturating_date_mi_interval(MIN_DATE, &Interval::new(0, 0, 1)));
+  }
+
+  #[test]
+  fn test_time_interval() {
+    let t = 10 * USECS_PER_HOUR;
+    let span = Interval::new(1, 1, 2 * USECS_PER_HOUR);
+    assert_eq!(Some(12 * USECS_PER_HOUR), checked_time_pl_interval(t, &span));
+    assert_eq!(Some(8 * USECS_PER_HOUR), checked_time_mi_interval(t, &span));
+    assert_eq!(Some(USECS_PER_DAY), checked_time_pl_interval(t, &Interval::new(0, 0, 14 * USECS_PER_HOUR)));
+    assert_eq!(None, checked_time_pl_interval(t, &Interval::new(0, 0, 15 * USECS_PER_HOUR)));
+    assert_eq!(None, checked_time_mi_interval(t, &Interval::new(0, 0, ::std::i64::MIN)));
+    assert_eq!(None, checked_time_pl_interval(t, &INTERVAL_NOEND));
+
+    assert_eq!(USECS_PER_DAY, saturating_time_pl_interval(t, &Interval::new(0, 0, ::std::i64::MAX)));
+    assert_eq!(0, saturating_time_mi_interval(t, &Interval::new(0, 0, 11 * USECS_PER_HOUR)));
+    assert_eq!((0, true), overflowing_time_pl_interval(t, &INTERVAL_NOBEGIN));
+    assert_eq!((t, false), overflowing_time_pl_interval(t, &Interval::new(0, 0, 0)));
+
+    let tz = TimeTz::new(t, -9 * 3600);
+    let r = checked_timetz_pl_interval(&tz, &span).unwrap();
+    assert_eq!((12 * USECS_PER_HOUR, -9 * 3600), (r.time(), r.zone()));
+    let r = checked_timetz_mi_interval(&tz, &span).unwrap();
+    assert_eq!((8 * USECS_PER_HOUR, -9 * 3600), (r.time(), r.zone()));
+    assert!(checked_timetz_mi_interval(&tz, &Interval::new(0, 0, t + 1)).is_none());
+    let (r, overflow) = overflowing_timetz_pl_interval(&tz, &INTERVAL_NOEND);
+    assert_eq!((USECS_PER_DAY, -9 * 3600, true), (r.time(), r.zone(), overflow));
+    assert_eq!(0, saturating_timetz_mi_interval(&tz, &INTERVAL_NOEND).time());
+  }
+
+  #[test]
+  fn test_interval() {
+    let a = Interval::new(1, 2, 3);
+    let b = Interval::new(10, 20, 30);
+    assert_eq!((11, 22, 33), fields(&checked_interval_pl(&a, &b).unwrap()));
+    assert_eq!((-9, -18, -27), fields(&checked_interval_mi(&a, &b).unwrap()));
+    assert_eq!((-3, -6, -9), fields(&checked_interval_mul(&a, -3).unwrap()));
+    assert_eq!((0, 0, 0), fields(&checked_interval_mul(&a, 0).unwrap()));
+
+    let max = Interval::new(::std::i32::MAX, 0, 0);
+    assert!(checked_interval_pl(&max, &a).is_none());
+    assert!(checked_interval_mul(&max, 2).is_none());
+    assert!(checked_interval_mi(&Interval::new(0, 0, 0), &Interval::new(0, ::std::i32::MIN, 0))
+            .is_none());
+    assert_eq!((::std::i32::MAX, 2, 3), fields(&saturating_interval_pl(&max, &a)));
+    assert_eq!((::std::i32::MIN, 0, 0), fields(&saturating_interval_mul(&max, -2)));
+    let (span, overflow) = overflowing_interval_pl(&max, &a);
+    assert_eq!((::std::i32::MAX, 2, 3), fields(&span));
+    assert!(overflow);
+    assert!(!overflowing_interval_pl(&a, &b).1);
+
+    // a finite result may not take the form of an infinity
+    let almost = Interval::new(::std::i32::MAX, ::std::i32::MAX, ::std::i64::MAX - 1);
+    assert!(checked_interval_pl(&almost, &Interval::new(0, 0, 1)).is_none());
+    assert_eq!(fields(&almost), fields(&saturating_interval_pl(&almost, &Interval::new(0, 0, 1))));
+    assert_eq!(fields(&almost), fields(&saturating_interval_mul(&almost, 2)));
+    let (span, overflow) = overflowing_interval_mi(&Interval::new(::std::i32::MIN, ::std::i32::MIN, -2),
+                                                   &Interval::new(0, 0, ::std::i64::MAX));
+    assert_eq!((::std::i32::MIN, ::std::i32::MIN, ::std::i64::MIN + 1), fields(&span));
+    assert!(overflow);
+
+    assert!(interval_is_noend(&checked_interval_pl(&INTERVAL_NOEND, &a).unwrap()));
+    assert!(interval_is_noend(&checked_interval_pl(&INTERVAL_NOEND, &INTERVAL_NOEND).unwrap()));
+    assert!(interval_is_nobegin(&checked_interval_mi(&a, &INTERVAL_NOEND).unwrap()));
+    assert!(interval_is_nobegin(&checked_interval_mul(&INTERVAL_NOEND, -2).unwrap()));
+    assert!(checked_interval_pl(&INTERVAL_NOEND, &INTERVAL_NOBEGIN).is_none());
+    assert!(checked_interval_mi(&INTERVAL_NOEND, &INTERVAL_NOEND).is_none());
+    assert!(checked_interval_mul(&INTERVAL_NOBEGIN, 0).is_none());
+    assert_eq!((0, 0, 0), fields(&saturating_interval_mi(&INTERVAL_NOEND, &INTERVAL_NOEND)));
+  }
+
+  #[test]
+  fn test_interval_div() {
+    assert_eq!((0, 15, 0), fields(&checked_interval_div(&Interval::new(1, 0, 0), 2).unwrap()));
+    assert_eq!((0, 10, 0), fields(&checked_interval_div(&Interval::new(1, -10, 0), 2).unwrap()));
+    assert_eq!((1, 1, 12 * USECS_PER_HOUR),
+               fields(&checked_interval_div(&Interval::new(2, 3, 0), 2).unwrap()));
+    assert_eq!((0, 4, 24685714286),
+               fields(&checked_interval_div(&Interval::new(1, 0, 0), 7).unwrap()));
+    assert_eq!((0, 0, -2), fields(&checked_interval_div(&Interval::new(0, 0, -5), 2).unwrap()));
+    assert_eq!((0, 0, 2), fields(&checked_interval_div(&Interval::new(0, 0, 7), 3).unwrap()));
+    assert_eq!((-1, -2, -3), fields(&checked_interval_div(&Interval::new(1, 2, 3), -1).unwrap()));
+
+    assert!(checked_interval_div(&Interval::new(1, 2, 3), 0).is_none());
+    assert_eq!((0, 0, 0), fields(&saturating_interval_div(&Interval::new(1, 2, 3), 0)));
+    let min = Interval::new(::std::i32::MIN, 0, 0);
+    assert!(checked_interval_div(&min, -1).is_none());
+    assert_eq!((::std::i32::MAX, 0, 0), fields(&saturating_interval_div(&min, -1)));
+
+    assert!(interval_is_noend(&checked_interval_div(&INTERVAL_NOEND, 3).unwrap()));
+    assert!(interval_is_nobegin(&checked_interval_div(&INTERVAL_NOEND, -3).unwrap()));
+    assert!(checked_interval_div(&INTERVAL_NOBEGIN, 0).is_none());
+  }
+}
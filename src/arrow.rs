@@ -0,0 +1,397 @@
+//! Conversions between Julian columns and Apache Arrow arrays.
+//!
+//! Arrow counts dates and timestamps from the Unix epoch (1970-01-01), while
+//! Julian counts them from 2000-01-01 (POSTGRES_EPOCH_JDATE), so values are
+//! shifted by a constant offset. When the value buffer is owned and not shared,
+//! the shift happens in place and no new buffer is allocated. Otherwise the
+//! values are copied once while shifting.
+//!
+//! Infinite dates, timestamps and intervals map to the extreme values of the
+//! Arrow type in both directions. A finite value that would land outside the
+//! target range fails with a `RangeError` naming the first offending row; on
+//! import, that range is `IS_VALID_DATE` or `IS_VALID_TIMESTAMP`. Values
+//! under a null slot are never checked.
+//!
+//! Julian's `Timestamp` and `TimestampTz` share a representation, so both use
+//! `to_timestamp`/`from_timestamp`. Pass a time zone (e.g. "+00:00") when
+//! exporting a `TimestampTz` column.
+
+use std::cmp;
+use std::sync::Arc;
+
+use arrow_array::{Array, Date32Array, Date64Array, IntervalMonthDayNanoArray, PrimitiveArray};
+use arrow_array::types::{ArrowPrimitiveType, ArrowTimestampType};
+use arrow_buffer::{IntervalMonthDayNano, NullBuffer, ScalarBuffer};
+use arrow_schema::TimeUnit;
+
+use kernels::RangeError;
+use super::{Date, Interval, Timestamp, DATEVAL_NOBEGIN, DATEVAL_NOEND, DT_NOBEGIN, DT_NOEND,
+            INTERVAL_NOBEGIN, INTERVAL_NOEND, MAX_DATE, MIN_DATE, POSTGRES_EPOCH_JDATE,
+            UNIX_EPOCH_JDATE, USECS_PER_DAY};
+use super::{date_is_nobegin, date_is_noend, date_not_finite, interval_is_nobegin,
+            interval_is_noend, timestamp_not_finite, IS_VALID_DATE, IS_VALID_TIMESTAMP};
+
+/// days from 1970-01-01 to 2000-01-01
+const EPOCH_DIFF_DAYS  :i32 = POSTGRES_EPOCH_JDATE - UNIX_EPOCH_JDATE;
+const EPOCH_DIFF_USECS :i64 = EPOCH_DIFF_DAYS as i64 * USECS_PER_DAY;
+const MSECS_PER_DAY    :i64 = 86400000;
+
+/// Take the values out of an array, reusing its buffer if it isn't shared.
+fn into_values<T: ArrowPrimitiveType>(array: PrimitiveArray<T>)
+    -> (Vec<T::Native>, Option<NullBuffer>) {
+  let (_, values, nulls) = array.into_parts();
+  let values = match values.into_inner().into_vec::<T::Native>() {
+    Ok(v) => v,
+    Err(buffer) => ScalarBuffer::<T::Native>::from(buffer).to_vec()
+  };
+  (values, nulls)
+}
+
+/// Apply `f` to every value in place. `f` returns the new value and
+/// whether the conversion overflowed.
+fn map_in_place<N, F>(values: &mut [N], nulls: Option<&NullBuffer>, f: F)
+    -> Result<(), RangeError> where N: Copy, F: Fn(N) -> (N, bool) {
+  let len = values.len();
+  let mut bad = len;
+
+  match nulls {
+    None => {
+      for (i, v) in values.iter_mut().enumerate() {
+        let (r, overflow) = f(*v);
+        *v = r;
+        bad = if overflow { cmp::min(bad, i) } else { bad };
+      }
+    }
+    Some(nulls) => {
+      for (i, (v, valid)) in values.iter_mut().zip(nulls.iter()).enumerate() {
+        let (r, overflow) = f(*v);
+        *v = r;
+        bad = if overflow & valid { cmp::min(bad, i) } else { bad };
+      }
+    }
+  }
+
+  if bad < len { Err(RangeError { row: bad }) } else { Ok(()) }
+}
+
+fn map_values<N, M, F>(values: &[N], nulls: Option<&NullBuffer>, f: F)
+    -> Result<Vec<M>, RangeError> where N: Copy, M: Copy + Default, F: Fn(N) -> (M, bool) {
+  let mut out = vec![M::default(); values.len()];
+  let mut bad = values.len();
+
+  for (i, (o, &v)) in out.iter_mut().zip(values).enumerate() {
+    let (r, overflow) = f(v);
+    *o = r;
+    let valid = nulls.map_or(true, |n| n.is_valid(i));
+    bad = if overflow & valid { cmp::min(bad, i) } else { bad };
+  }
+
+  if bad < values.len() { Err(RangeError { row: bad }) } else { Ok(out) }
+}
+
+/// Floor division, so that instants before 1970 round towards the past.
+#[inline(always)]
+fn div_floor(a: i64, b: i64) -> i64 {
+  let q = a / b;
+  if (a % b) < 0 { q - 1 } else { q }
+}
+
+// ---------------------------------------------------------------------------
+// Date32 / Date64
+// ---------------------------------------------------------------------------
+
+#[inline(always)]
+fn date_to_unix(d: Date) -> (i32, bool) {
+  if date_not_finite(d) {
+    return (d, false);
+  }
+  let (r, overflow) = d.overflowing_add(EPOCH_DIFF_DAYS);
+  (r, overflow | date_not_finite(r))
+}
+
+#[inline(always)]
+fn unix_to_date(d: i32) -> (Date, bool) {
+  if date_not_finite(d) {
+    return (d, false);
+  }
+  let (r, overflow) = d.overflowing_sub(EPOCH_DIFF_DAYS);
+  (r, overflow | !IS_VALID_DATE(r))
+}
+
+/// Date -> Arrow Date32 (days since 1970-01-01)
+pub fn to_date32(dates: Vec<Date>, nulls: Option<NullBuffer>) -> Result<Date32Array, RangeError> {
+  let mut dates = dates;
+  map_in_place(&mut dates, nulls.as_ref(), date_to_unix)?;
+  Ok(Date32Array::new(ScalarBuffer::from(dates), nulls))
+}
+
+/// Arrow Date32 -> Date
+pub fn from_date32(array: Date32Array) -> Result<(Vec<Date>, Option<NullBuffer>), RangeError> {
+  let (mut dates, nulls) = into_values(array);
+  map_in_place(&mut dates, nulls.as_ref(), unix_to_date)?;
+  Ok((dates, nulls))
+}
+
+/// Date -> Arrow Date64 (milliseconds since 1970-01-01, whole days)
+pub fn to_date64(dates: &[Date], nulls: Option<NullBuffer>) -> Result<Date64Array, RangeError> {
+  let values = map_values(dates, nulls.as_ref(), |d| {
+    if date_is_nobegin(d) {
+      (::std::i64::MIN, false)
+    } else if date_is_noend(d) {
+      (::std::i64::MAX, false)
+    } else {
+      ((d as i64 + EPOCH_DIFF_DAYS as i64) * MSECS_PER_DAY, false)
+    }
+  })?;
+  Ok(Date64Array::new(ScalarBuffer::from(values), nulls))
+}
+
+/// Arrow Date64 -> Date. Milliseconds within a day are truncated.
+pub fn from_date64(array: &Date64Array) -> Result<(Vec<Date>, Option<NullBuffer>), RangeError> {
+  let nulls = array.nulls().cloned();
+  let dates = map_values(array.values(), nulls.as_ref(), |ms: i64| {
+    if ms == ::std::i64::MIN {
+      (DATEVAL_NOBEGIN, false)
+    } else if ms == ::std::i64::MAX {
+      (DATEVAL_NOEND, false)
+    } else {
+      let d = div_floor(ms, MSECS_PER_DAY) - EPOCH_DIFF_DAYS as i64;
+      let overflow = d < MIN_DATE as i64 || d > MAX_DATE as i64;
+      (d as Date, overflow)
+    }
+  })?;
+  Ok((dates, nulls))
+}
+
+// ---------------------------------------------------------------------------
+// Timestamp(unit, tz)
+// ---------------------------------------------------------------------------
+
+/// Timestamp (microseconds since 2000-01-01) -> value in the given unit
+/// since 1970-01-01. Units coarser than microseconds are floored.
+#[inline(always)]
+fn timestamp_to_unix(ts: Timestamp, unit: &TimeUnit) -> (i64, bool) {
+  if timestamp_not_finite(ts) {
+    return (ts, false);
+  }
+
+  let (us, overflow) = ts.overflowing_add(EPOCH_DIFF_USECS);
+  let (r, overflow2) = match *unit {
+    TimeUnit::Second => (div_floor(us, 1000000), false),
+    TimeUnit::Millisecond => (div_floor(us, 1000), false),
+    TimeUnit::Microsecond => (us, false),
+    TimeUnit::Nanosecond => us.overflowing_mul(1000)
+  };
+  (r, overflow | overflow2 | timestamp_not_finite(r))
+}
+
+/// Value in the given unit since 1970-01-01 -> Timestamp. Nanoseconds are
+/// floored to microseconds.
+#[inline(always)]
+fn unix_to_timestamp(v: i64, unit: &TimeUnit) -> (Timestamp, bool) {
+  if v == DT_NOBEGIN || v == DT_NOEND {
+    return (v, false);
+  }
+
+  // shift to 2000-01-01 in the given unit first, so the last seconds
+  // before END_TIMESTAMP don't overflow on the way to microseconds
+  let scale = match *unit {
+    TimeUnit::Second => 1000000,
+    TimeUnit::Millisecond => 1000,
+    TimeUnit::Microsecond | TimeUnit::Nanosecond => 1
+  };
+  let v = match *unit {
+    TimeUnit::Nanosecond => div_floor(v, 1000),
+    _ => v
+  };
+  let (shifted, overflow) = v.overflowing_sub(EPOCH_DIFF_USECS / scale);
+  let (r, overflow2) = shifted.overflowing_mul(scale);
+  (r, overflow | overflow2 | !IS_VALID_TIMESTAMP(r))
+}
+
+/// Timestamp -> Arrow Timestamp(T::UNIT, tz)
+pub fn to_timestamp<T>(timestamps: Vec<Timestamp>, tz: Option<Arc<str>>,
+    nulls: Option<NullBuffer>) -> Result<PrimitiveArray<T>, RangeError>
+    where T: ArrowTimestampType {
+  let unit = T::UNIT;
+  let mut values = timestamps;
+  map_in_place(&mut values, nulls.as_ref(), |ts| timestamp_to_unix(ts, &unit))?;
+  Ok(PrimitiveArray::<T>::new(ScalarBuffer::from(values), nulls).with_timezone_opt(tz))
+}
+
+/// Arrow Timestamp(T::UNIT, tz) -> Timestamp. The time zone is dropped:
+/// zoned arrays hold UTC instants, which is what a `TimestampTz` stores.
+pub fn from_timestamp<T>(array: PrimitiveArray<T>)
+    -> Result<(Vec<Timestamp>, Option<NullBuffer>), RangeError> where T: ArrowTimestampType {
+  let unit = T::UNIT;
+  let (mut values, nulls) = into_values(array);
+  map_in_place(&mut values, nulls.as_ref(), |v| unix_to_timestamp(v, &unit))?;
+  Ok((values, nulls))
+}
+
+// ---------------------------------------------------------------------------
+// Interval
+// ---------------------------------------------------------------------------
+
+/// Interval -> Arrow Interval(MonthDayNano). INTERVAL_NOBEGIN and
+/// INTERVAL_NOEND have every field at its least or greatest value.
+pub fn to_interval(intervals: &[Interval], nulls: Option<NullBuffer>)
+    -> Result<IntervalMonthDayNanoArray, RangeError> {
+  let values = map_values(intervals, nulls.as_ref(), |span: Interval| {
+    if interval_is_nobegin(&span) {
+      return (IntervalMonthDayNano::MIN, false);
+    }
+    if interval_is_noend(&span) {
+      return (IntervalMonthDayNano::MAX, false);
+    }
+    let (nanos, overflow) = span.time.overflowing_mul(1000);
+    (IntervalMonthDayNano::new(span.month, span.day, nanos), overflow)
+  })?;
+  Ok(IntervalMonthDayNanoArray::new(ScalarBuffer::from(values), nulls))
+}
+
+/// Arrow Interval(MonthDayNano) -> Interval. Nanoseconds are truncated
+/// towards zero.
+pub fn from_interval(array: &IntervalMonthDayNanoArray) -> (Vec<Interval>, Option<NullBuffer>) {
+  let intervals = array.values().iter().map(|v| {
+    if *v == IntervalMonthDayNano::MIN {
+      INTERVAL_NOBEGIN
+    } else if *v == IntervalMonthDayNano::MAX {
+      INTERVAL_NOEND
+    } else {
+      Interval::new(v.months, v.days, v.nanoseconds / 1000)
+    }
+  }).collect();
+  (intervals, array.nulls().cloned())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use arrow_array::types::{TimestampMicrosecondType, TimestampNanosecondType,
+                           TimestampSecondType};
+  use super::super::{date2j, MAX_TIMESTAMP, MIN_TIMESTAMP};
+
+  fn date(y: i32, m: i32, d: i32) -> Date {
+    date2j(y, m, d) - POSTGRES_EPOCH_JDATE
+  }
+
+  #[test]
+  fn test_date32() {
+    let dates = vec![date(1970, 1, 1), date(2016, 11, 11), DATEVAL_NOEND, date(1969, 12, 31)];
+    let array = to_date32(dates.clone(), None).unwrap();
+    assert_eq!(&[0, 17116, ::std::i32::MAX, -1], array.values().as_ref());
+
+    let (back, nulls) = from_date32(array).unwrap();
+    assert_eq!(dates, back);
+    assert!(nulls.is_none());
+  }
+
+  #[test]
+  fn test_date32_overflow() {
+    let nulls = NullBuffer::from(vec![true, false]);
+    let array = Date32Array::new(ScalarBuffer::from(vec![0, ::std::i32::MIN + 1]), None);
+    assert_eq!(RangeError { row: 1 }, from_date32(array).err().unwrap());
+
+    let array = Date32Array::new(ScalarBuffer::from(vec![0, ::std::i32::MIN + 1]), Some(nulls));
+    assert!(from_date32(array).is_ok());
+  }
+
+  #[test]
+  fn test_date64() {
+    let dates = vec![date(1970, 1, 2), date(1969, 12, 31)];
+    let array = to_date64(&dates, None).unwrap();
+    assert_eq!(&[MSECS_PER_DAY, -MSECS_PER_DAY], array.values().as_ref());
+    assert_eq!(dates, from_date64(&array).unwrap().0);
+  }
+
+  #[test]
+  fn test_timestamp() {
+    let ts = vec![0, -1, DT_NOBEGIN];
+
+    let micros = to_timestamp::<TimestampMicrosecondType>(ts.clone(), None, None).unwrap();
+    assert_eq!(&[946684800000000, 946684799999999, ::std::i64::MIN], micros.values().as_ref());
+    assert_eq!(ts, from_timestamp(micros).unwrap().0);
+
+    let secs = to_timestamp::<TimestampSecondType>(ts.clone(), Some("+00:00".into()), None)
+      .unwrap();
+    assert_eq!(&[946684800, 946684799, ::std::i64::MIN], secs.values().as_ref());
+    assert_eq!(Some("+00:00"), secs.timezone());
+
+    let nanos = to_timestamp::<TimestampNanosecondType>(ts.clone(), None, None).unwrap();
+    assert_eq!(ts, from_timestamp(nanos).unwrap().0);
+
+    assert_eq!(RangeError { row: 0 },
+      to_timestamp::<TimestampNanosecondType>(vec![DT_NOEND - 1], None, None).err().unwrap());
+  }
+
+  #[test]
+  fn test_interval() {
+    let spans = [Interval::new(1, 2, 3), Interval::new(-1, 0, -1000000)];
+    let array = to_interval(&spans, None).unwrap();
+    assert_eq!(IntervalMonthDayNano::new(1, 2, 3000), array.value(0));
+
+    let (back, _) = from_interval(&array);
+    assert_eq!((1, 2, 3), (back[0].month(), back[0].day(), back[0].time()));
+    assert_eq!((-1, 0, -1000000), (back[1].month(), back[1].day(), back[1].time()));
+
+    // the infinities take the extreme values
+    let spans = [INTERVAL_NOBEGIN, INTERVAL_NOEND, Interval::new(0, 0, 1)];
+    let array = to_interval(&spans, None).unwrap();
+    assert_eq!(IntervalMonthDayNano::new(::std::i32::MIN, ::std::i32::MIN, ::std::i64::MIN),
+               array.value(0));
+    assert_eq!(IntervalMonthDayNano::new(::std::i32::MAX, ::std::i32::MAX, ::std::i64::MAX),
+               array.value(1));
+    let (back, _) = from_interval(&array);
+    assert!(interval_is_nobegin(&back[0]));
+    assert!(interval_is_noend(&back[1]));
+    assert_eq!(1, back[2].time());
+
+    // a finite interval still has to fit in nanoseconds
+    let spans = [Interval::new(0, 0, ::std::i64::MAX), Interval::new(0, 0, ::std::i64::MIN)];
+    assert_eq!(RangeError { row: 0 }, to_interval(&spans, None).err().unwrap());
+    assert_eq!(RangeError { row: 0 }, to_interval(&spans[1..], None).err().unwrap());
+  }
+
+  #[test]
+  fn test_import_range() {
+    // the first and last valid dates, and one day past each
+    let unix = |d: Date| d + EPOCH_DIFF_DAYS;
+    let array = Date32Array::new(ScalarBuffer::from(vec![unix(MIN_DATE), unix(MAX_DATE)]), None);
+    assert_eq!(vec![MIN_DATE, MAX_DATE], from_date32(array).unwrap().0);
+    let array = Date32Array::new(ScalarBuffer::from(vec![0, unix(MIN_DATE) - 1]), None);
+    assert_eq!(RangeError { row: 1 }, from_date32(array).err().unwrap());
+    let array = Date32Array::new(ScalarBuffer::from(vec![unix(MAX_DATE) + 1]), None);
+    assert_eq!(RangeError { row: 0 }, from_date32(array).err().unwrap());
+
+    let ms = |d: Date| (d as i64 + EPOCH_DIFF_DAYS as i64) * MSECS_PER_DAY;
+    let array = Date64Array::new(ScalarBuffer::from(vec![ms(MIN_DATE), ms(MAX_DATE) + 1]), None);
+    assert_eq!(vec![MIN_DATE, MAX_DATE], from_date64(&array).unwrap().0);
+    let array = Date64Array::new(ScalarBuffer::from(vec![0, ms(MIN_DATE) - 1]), None);
+    assert_eq!(RangeError { row: 1 }, from_date64(&array).err().unwrap());
+    let array = Date64Array::new(ScalarBuffer::from(vec![ms(MAX_DATE + 1)]), None);
+    assert_eq!(RangeError { row: 0 }, from_date64(&array).err().unwrap());
+
+    // timestamps that fit an i64 but not the timestamp range
+    let micros = |t: Timestamp| t + EPOCH_DIFF_USECS;
+    let values = vec![micros(MIN_TIMESTAMP), DT_NOEND];
+    let array = PrimitiveArray::<TimestampMicrosecondType>::new(ScalarBuffer::from(values), None);
+    assert_eq!(vec![MIN_TIMESTAMP, DT_NOEND], from_timestamp(array).unwrap().0);
+    for &bad in [micros(MIN_TIMESTAMP) - 1, DT_NOBEGIN + 1].iter() {
+      let array = PrimitiveArray::<TimestampMicrosecondType>::new(ScalarBuffer::from(vec![0, bad]),
+                                                                  None);
+      assert_eq!(RangeError { row: 1 }, from_timestamp(array).err().unwrap());
+    }
+    // the last second
+    let secs = MAX_TIMESTAMP / 1000000 + EPOCH_DIFF_USECS / 1000000;
+    let array = PrimitiveArray::<TimestampSecondType>::new(ScalarBuffer::from(vec![secs]), None);
+    assert_eq!(vec![MAX_TIMESTAMP - 999999], from_timestamp(array).unwrap().0);
+    let array = PrimitiveArray::<TimestampSecondType>::new(ScalarBuffer::from(vec![0, secs + 1]),
+                                                           None);
+    assert_eq!(RangeError { row: 1 }, from_timestamp(array).err().unwrap());
+    let nulls = NullBuffer::from(vec![true, false]);
+    let array = PrimitiveArray::<TimestampSecondType>::new(ScalarBuffer::from(vec![0, 1 << 50]),
+                                                           Some(nulls));
+    assert!(from_timestamp(array).is_ok());
+  }
+}
@@ -0,0 +1,1025 @@
+//! Calendars other than the proleptic Gregorian one.
+//!
+//! Dates and timestamps always count days in the proleptic Gregorian
+//! calendar, as PostgreSQL does. A `Calendar` only changes how a day is
+//! named on input and output: each one maps its year, month and day to the
+//! Julian day number of `date2j()` and back, and every conversion goes
+//! through that day number.
+//!
+//! - `Gregorian` is the calendar of the rest of the crate.
+//! - `Julian` is the proleptic Julian calendar, with a leap year every
+//!   fourth year.
+//! - `Historical` follows the Julian calendar up to a changeover day and
+//!   the Gregorian one from then on. The days between the last Julian date
+//!   and the first Gregorian one were skipped, so they are rejected on
+//!   input and never printed. The changeover is the Gregorian reform of
+//!   1582-10-15 by default, and `BRITISH_REFORM_JDATE` gives Britain and
+//!   its colonies, which went from 1752-09-02 to 1752-09-14.
+//! - `Hebrew` is the arithmetic Hebrew calendar. Months are numbered from
+//!   Tishrei, so a leap year has Adar I as month 6 and Adar II as month 7.
+//! - `Islamic` is the tabular Islamic calendar, with the civil epoch of
+//!   1 Muharram 1 AH on Friday 622-07-16 (Julian) and 11 leap years in
+//!   each 30, in years 2, 5, 7, 10, 13, 16, 18, 21, 24, 26 and 29.
+//! - `Persian` is the solar Hijri calendar by the 33-year arithmetic rule,
+//!   which agrees with the astronomical calendar of Iran from 1178 to 1633
+//!   AP (1799 to 2256).
+//! - `Coptic` and `Ethiopian` have twelve months of 30 days and a
+//!   thirteenth of five, or six in a leap year.
+//!
+//! Japanese era dates and ISO 8601 week dates are not years, months and
+//! days of their own and have converters below instead.
+//!
+//! Years are numbered as elsewhere: 1 BC, or the year before the epoch of
+//! the calendar, is year 0.
+
+use super::{checked_date2j, date2isoweek, date2isoyear, date2j, date_is_nobegin, date_is_noend,
+            date_out_cal, decode_date_text, decode_timestamp, div_floor, isleap, isoweek2j,
+            j2date, j2day, julian_day, timestamp_out_cal, Date, DateStyle, DateTimeParseError,
+            Timestamp, DAY_TAB, IS_VALID_DATE, JULIAN_MAXYEAR, JULIAN_MINYEAR, MONTHS,
+            POSTGRES_EPOCH_JDATE};
+
+/// The first day of the Gregorian calendar, 1582-10-15.
+pub const GREGORIAN_REFORM_JDATE :i32 = 2299161; // == date2j(1582, 10, 15)
+/// The first day of the Gregorian calendar in Britain, 1752-09-14.
+pub const BRITISH_REFORM_JDATE   :i32 = 2361222; // == date2j(1752, 9, 14)
+
+// The first days of the calendars, as Julian days
+const HEBREW_EPOCH_JDATE    :i32 = 347998;  // 3761-10-07 BC (Julian)
+const ISLAMIC_EPOCH_JDATE   :i32 = 1948440; // 622-07-16 (Julian)
+const PERSIAN_EPOCH_JDATE   :i32 = 1948320; // 622-03-18 (Julian), by the 33-year rule
+const COPTIC_EPOCH_JDATE    :i32 = 1825030; // 284-08-29 (Julian)
+const ETHIOPIAN_EPOCH_JDATE :i32 = 1724221; // 8-08-29 (Julian)
+
+/// A way of naming days by year, month and day.
+pub trait Calendar {
+  /// The Julian day of a date, or None if it does not fit in an i32. As
+  /// with date2j(), days past the end of the month run on into the next.
+  fn checked_date2j(&self, y: i32, m: i32, d: i32) -> Option<i32>;
+
+  /// The Julian day of a date. Panics if it does not fit in an i32.
+  fn date2j(&self, y: i32, m: i32, d: i32) -> i32 {
+    match self.checked_date2j(y, m, d) {
+      Some(julian) => julian,
+      None => panic!("Julian day out of range: {}-{}-{}", y, m, d)
+    }
+  }
+
+  /// The date of a Julian day, as (year, month, day).
+  fn j2date(&self, julian_day: i32) -> (i32, u32, u32);
+
+  /// Whether the date exists in this calendar.
+  fn is_valid(&self, y: i32, m: i32, d: i32) -> bool {
+    match self.checked_date2j(y, m, d) {
+      Some(julian) => m >= 1 && d >= 1 && self.j2date(julian) == (y, m as u32, d as u32),
+      None => false
+    }
+  }
+
+  /// The number of months in year y.
+  fn months_in_year(&self, _y: i32) -> i32 {
+    12
+  }
+
+  /// The name of month m of year y, as printed in the Postgres style. The
+  /// Gregorian names are abbreviated, as in PostgreSQL.
+  fn month_name(&self, _y: i32, m: i32) -> &'static str {
+    MONTHS[(m - 1) as usize]
+  }
+
+  /// The label that follows a year before 1 in date_long(), counting back
+  /// from year 0 as 1, e.g. "BC". None if such years are written signed.
+  fn before_era(&self) -> Option<&'static str> {
+    None
+  }
+}
+
+/// Whether d is a day of month m in a year that is leap or not.
+fn is_valid_day(leap: bool, m: i32, d: i32) -> bool {
+  m >= 1 && m <= 12 && d >= 1 && d <= DAY_TAB[leap as usize][(m - 1) as usize]
+}
+
+/// The proleptic Gregorian calendar.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Gregorian;
+
+impl Calendar for Gregorian {
+  fn checked_date2j(&self, y: i32, m: i32, d: i32) -> Option<i32> {
+    checked_date2j(y, m, d)
+  }
+
+  fn j2date(&self, julian_day: i32) -> (i32, u32, u32) {
+    j2date(julian_day)
+  }
+
+  fn is_valid(&self, y: i32, m: i32, d: i32) -> bool {
+    is_valid_day(isleap(y), m, d)
+  }
+
+  fn before_era(&self) -> Option<&'static str> {
+    Some("BC")
+  }
+}
+
+/// The proleptic Julian calendar.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Julian;
+
+impl Calendar for Julian {
+  /// Same as date2j(), less the century rule for leap days.
+  fn checked_date2j(&self, y: i32, m: i32, d: i32) -> Option<i32> {
+    let (mut y, mut m) = (y as i64, m as i64);
+    if m > 2 {
+      m += 1;
+      y += 4800;
+    } else {
+      m += 13;
+      y += 4799;
+    }
+
+    let mut julian: i64 = y * 365 - 32205;
+    julian += div_floor(y, 4);
+    julian += 7834 * m / 256 + d as i64;
+
+    julian_day(julian)
+  }
+
+  fn j2date(&self, julian_day: i32) -> (i32, u32, u32) {
+    // after E. G. Richards, with floor division for days before -4712
+    let e: i64 = 4 * (julian_day as i64 + 1401) + 3;
+    let h: i64 = 5 * ((e - div_floor(e, 1461) * 1461) / 4) + 2;
+    let day = (h % 153) / 5 + 1;
+    let month = (h / 153 + 2) % 12 + 1;
+    let year = div_floor(e, 1461) - 4716 + (14 - month) / 12;
+
+    (year as i32, month as u32, day as u32)
+  }
+
+  fn is_valid(&self, y: i32, m: i32, d: i32) -> bool {
+    is_valid_day(y % 4 == 0, m, d)
+  }
+
+  fn before_era(&self) -> Option<&'static str> {
+    Some("BC")
+  }
+}
+
+/// The Julian calendar up to a changeover day, and the Gregorian calendar
+/// from that day on.
+#[derive(Clone, Copy, Debug)]
+pub struct Historical {
+  changeover: i32,
+  /// the Gregorian date of the changeover
+  first: (i32, i32, i32)
+}
+
+impl Historical {
+  /// A calendar that turns Gregorian on the Julian day changeover. The
+  /// day should be late enough that the Gregorian calendar is ahead of the
+  /// Julian one, as it is from the year 200 on.
+  pub fn new(changeover: i32) -> Historical {
+    let (y, m, d) = j2date(changeover);
+    Historical {
+      changeover: changeover,
+      first: (y, m as i32, d as i32)
+    }
+  }
+
+  /// The Julian day of the first Gregorian date.
+  pub fn changeover(&self) -> i32 {
+    self.changeover
+  }
+
+  fn is_gregorian(&self, y: i32, m: i32, d: i32) -> bool {
+    (y, m, d) >= self.first
+  }
+}
+
+impl Default for Historical {
+  fn default() -> Historical {
+    Historical::new(GREGORIAN_REFORM_JDATE)
+  }
+}
+
+impl Calendar for Historical {
+  fn checked_date2j(&self, y: i32, m: i32, d: i32) -> Option<i32> {
+    if self.is_gregorian(y, m, d) {
+      Gregorian.checked_date2j(y, m, d)
+    } else {
+      Julian.checked_date2j(y, m, d)
+    }
+  }
+
+  fn j2date(&self, julian_day: i32) -> (i32, u32, u32) {
+    if julian_day >= self.changeover {
+      Gregorian.j2date(julian_day)
+    } else {
+      Julian.j2date(julian_day)
+    }
+  }
+
+  fn before_era(&self) -> Option<&'static str> {
+    Some("BC")
+  }
+
+  /// The Julian dates from the changeover on were skipped.
+  fn is_valid(&self, y: i32, m: i32, d: i32) -> bool {
+    if self.is_gregorian(y, m, d) {
+      Gregorian.is_valid(y, m, d)
+    } else {
+      Julian.is_valid(y, m, d) && Julian.date2j(y, m, d) < self.changeover
+    }
+  }
+}
+
+/// a mod b, for b > 0, from 0 to b - 1.
+fn rem_floor(a: i64, b: i64) -> i64 {
+  a - div_floor(a, b) * b
+}
+
+const HEBREW_MONTHS: [&'static str;13] = [
+  "Tishrei", "Cheshvan", "Kislev", "Tevet", "Shevat", "Adar I", "Adar II",
+  "Nisan", "Iyar", "Sivan", "Tammuz", "Av", "Elul"
+];
+
+/// The arithmetic Hebrew calendar.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Hebrew;
+
+impl Hebrew {
+  fn is_leap(y: i64) -> bool {
+    rem_floor(7 * y + 1, 19) < 7
+  }
+
+  /// Days from the epoch to the molad of Tishrei of year y, put off a day
+  /// if it falls on a Sunday, Wednesday or Friday.
+  fn elapsed_days(y: i64) -> i64 {
+    let months = div_floor(235 * y - 234, 19);
+    let parts = 12084 + 13753 * months;
+    let days = 29 * months + div_floor(parts, 25920);
+    if rem_floor(3 * (days + 1), 7) < 3 { days + 1 } else { days }
+  }
+
+  /// The Julian day of 1 Tishrei of year y, after the postponements that
+  /// keep the lengths of years within 353 to 355 or 383 to 385 days.
+  fn new_year(y: i64) -> i64 {
+    let (prev, this, next) = (Hebrew::elapsed_days(y - 1), Hebrew::elapsed_days(y),
+                              Hebrew::elapsed_days(y + 1));
+    let delay = if next - this == 356 {
+      2
+    } else if this - prev == 382 {
+      1
+    } else {
+      0
+    };
+    HEBREW_EPOCH_JDATE as i64 + this + delay
+  }
+
+  /// The index in HEBREW_MONTHS of month m, in a leap year or not.
+  fn month_index(leap: bool, m: i32) -> usize {
+    if leap || m < 6 { (m - 1) as usize } else { m as usize }
+  }
+
+  /// Days in month m of a year of year_days days.
+  fn month_days(leap: bool, year_days: i64, m: i32) -> i64 {
+    match Hebrew::month_index(leap, m) {
+      // Cheshvan is long in a year of 355 or 385 days, and Kislev short in
+      // one of 353 or 383
+      1 if year_days % 10 == 5 => 30,
+      2 if year_days % 10 == 3 => 29,
+      // Adar I has 30 days, and Adar or Adar II 29
+      1 | 3 | 6 | 8 | 10 | 12 => 29,
+      _ => 30
+    }
+  }
+}
+
+impl Calendar for Hebrew {
+  fn checked_date2j(&self, y: i32, m: i32, d: i32) -> Option<i32> {
+    let y = y as i64;
+    let new_year = Hebrew::new_year(y);
+    let year_days = Hebrew::new_year(y + 1) - new_year;
+    let leap = Hebrew::is_leap(y);
+
+    let mut julian = new_year + d as i64 - 1;
+    for k in 1..::std::cmp::min(m, 14) {
+      julian += Hebrew::month_days(leap, year_days, k);
+    }
+    julian_day(julian)
+  }
+
+  fn j2date(&self, julian_day: i32) -> (i32, u32, u32) {
+    let julian = julian_day as i64;
+
+    // a mean year is 235 months of 765433 parts over 19, and the estimate
+    // is off by at most one
+    let mut y = div_floor((julian - HEBREW_EPOCH_JDATE as i64) * 492480, 179876755) + 1;
+    while Hebrew::new_year(y) > julian {
+      y -= 1;
+    }
+    while Hebrew::new_year(y + 1) <= julian {
+      y += 1;
+    }
+
+    let new_year = Hebrew::new_year(y);
+    let year_days = Hebrew::new_year(y + 1) - new_year;
+    let leap = Hebrew::is_leap(y);
+    let mut day = julian - new_year;
+    let mut m = 1;
+    while day >= Hebrew::month_days(leap, year_days, m) {
+      day -= Hebrew::month_days(leap, year_days, m);
+      m += 1;
+    }
+
+    (y as i32, m as u32, day as u32 + 1)
+  }
+
+  fn is_valid(&self, y: i32, m: i32, d: i32) -> bool {
+    let y = y as i64;
+    let year_days = Hebrew::new_year(y + 1) - Hebrew::new_year(y);
+    m >= 1 && m <= self.months_in_year(y as i32) && d >= 1 &&
+      d as i64 <= Hebrew::month_days(Hebrew::is_leap(y), year_days, m)
+  }
+
+  fn months_in_year(&self, y: i32) -> i32 {
+    if Hebrew::is_leap(y as i64) { 13 } else { 12 }
+  }
+
+  fn month_name(&self, y: i32, m: i32) -> &'static str {
+    let leap = Hebrew::is_leap(y as i64);
+    if !leap && m == 6 {
+      "Adar"
+    } else {
+      HEBREW_MONTHS[Hebrew::month_index(leap, m)]
+    }
+  }
+}
+
+const ISLAMIC_MONTHS: [&'static str;12] = [
+  "Muharram", "Safar", "Rabi' al-Awwal", "Rabi' al-Thani", "Jumada al-Ula",
+  "Jumada al-Akhirah", "Rajab", "Sha'ban", "Ramadan", "Shawwal", "Dhu al-Qa'dah",
+  "Dhu al-Hijjah"
+];
+
+/// The tabular Islamic calendar.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Islamic;
+
+impl Islamic {
+  /// Months alternate 30 and 29 days, and a leap year adds a day to the
+  /// last.
+  fn julian(y: i64, m: i64, d: i64) -> i64 {
+    ISLAMIC_EPOCH_JDATE as i64 - 1 + (y - 1) * 354 + div_floor(3 + 11 * y, 30) +
+      29 * (m - 1) + div_floor(m, 2) + d
+  }
+}
+
+impl Calendar for Islamic {
+  fn checked_date2j(&self, y: i32, m: i32, d: i32) -> Option<i32> {
+    julian_day(Islamic::julian(y as i64, m as i64, d as i64))
+  }
+
+  fn j2date(&self, julian_day: i32) -> (i32, u32, u32) {
+    let julian = julian_day as i64;
+    let y = div_floor(30 * (julian - ISLAMIC_EPOCH_JDATE as i64) + 10646, 10631);
+    let day = julian - Islamic::julian(y, 1, 1);
+    // month k from 0 starts on day ceil(29.5 * k)
+    let m = ::std::cmp::min(2 * day / 59, 11) + 1;
+    let d = julian - Islamic::julian(y, m, 1) + 1;
+
+    (y as i32, m as u32, d as u32)
+  }
+
+  fn month_name(&self, _y: i32, m: i32) -> &'static str {
+    ISLAMIC_MONTHS[(m - 1) as usize]
+  }
+
+  /// Before the Hijra.
+  fn before_era(&self) -> Option<&'static str> {
+    Some("BH")
+  }
+}
+
+const PERSIAN_MONTHS: [&'static str;12] = [
+  "Farvardin", "Ordibehesht", "Khordad", "Tir", "Mordad", "Shahrivar",
+  "Mehr", "Aban", "Azar", "Dey", "Bahman", "Esfand"
+];
+
+/// The solar Hijri calendar of Iran and Afghanistan.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Persian;
+
+impl Persian {
+  /// Six months of 31 days, five of 30, and Esfand of 29, or 30 in the
+  /// eight leap years of every 33.
+  fn julian(y: i64, m: i64, d: i64) -> i64 {
+    let days = if m <= 7 { 31 * (m - 1) } else { 30 * (m - 1) + 6 };
+    PERSIAN_EPOCH_JDATE as i64 - 1 + (y - 1) * 365 + div_floor(8 * y + 21, 33) + days + d
+  }
+}
+
+impl Calendar for Persian {
+  fn checked_date2j(&self, y: i32, m: i32, d: i32) -> Option<i32> {
+    julian_day(Persian::julian(y as i64, m as i64, d as i64))
+  }
+
+  fn j2date(&self, julian_day: i32) -> (i32, u32, u32) {
+    let julian = julian_day as i64;
+
+    // 33 years have 12053 days
+    let mut y = div_floor(33 * (julian - PERSIAN_EPOCH_JDATE as i64), 12053) + 1;
+    while Persian::julian(y, 1, 1) > julian {
+      y -= 1;
+    }
+    while Persian::julian(y + 1, 1, 1) <= julian {
+      y += 1;
+    }
+
+    let day = julian - Persian::julian(y, 1, 1);
+    let (m, d) = if day < 186 {
+      (day / 31 + 1, day % 31 + 1)
+    } else {
+      ((day - 186) / 30 + 7, (day - 186) % 30 + 1)
+    };
+
+    (y as i32, m as u32, d as u32)
+  }
+
+  fn month_name(&self, _y: i32, m: i32) -> &'static str {
+    PERSIAN_MONTHS[(m - 1) as usize]
+  }
+}
+
+/// Julian day of a date in the calendar of Coptic or Ethiopian form that
+/// starts on the Julian day epoch.
+fn coptic_julian(epoch: i32, y: i64, m: i64, d: i64) -> i64 {
+  epoch as i64 - 1 + (y - 1) * 365 + div_floor(y, 4) + 30 * (m - 1) + d
+}
+
+/// The date of a Julian day in the calendar of Coptic or Ethiopian form
+/// that starts on the Julian day epoch.
+fn coptic_date(epoch: i32, julian_day: i32) -> (i32, u32, u32) {
+  let julian = julian_day as i64;
+  let y = div_floor(4 * (julian - epoch as i64) + 1463, 1461);
+  let m = (julian - coptic_julian(epoch, y, 1, 1)) / 30 + 1;
+  let d = julian - coptic_julian(epoch, y, m, 1) + 1;
+
+  (y as i32, m as u32, d as u32)
+}
+
+const COPTIC_MONTHS: [&'static str;13] = [
+  "Thout", "Paopi", "Hathor", "Koiak", "Tobi", "Meshir", "Paremhat",
+  "Parmouti", "Pashons", "Paoni", "Epip", "Mesori", "Pi Kogi Enavot"
+];
+
+/// The Coptic calendar, counting years from 284 (Anno Martyrum).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Coptic;
+
+impl Calendar for Coptic {
+  fn checked_date2j(&self, y: i32, m: i32, d: i32) -> Option<i32> {
+    julian_day(coptic_julian(COPTIC_EPOCH_JDATE, y as i64, m as i64, d as i64))
+  }
+
+  fn j2date(&self, julian_day: i32) -> (i32, u32, u32) {
+    coptic_date(COPTIC_EPOCH_JDATE, julian_day)
+  }
+
+  fn months_in_year(&self, _y: i32) -> i32 {
+    13
+  }
+
+  fn month_name(&self, _y: i32, m: i32) -> &'static str {
+    COPTIC_MONTHS[(m - 1) as usize]
+  }
+}
+
+const ETHIOPIAN_MONTHS: [&'static str;13] = [
+  "Meskerem", "Tikimt", "Hidar", "Tahsas", "Tir", "Yekatit", "Megabit",
+  "Miyazya", "Ginbot", "Sene", "Hamle", "Nehase", "Pagume"
+];
+
+/// The Ethiopian calendar, counting years from 8 (Amete Mihret).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Ethiopian;
+
+impl Calendar for Ethiopian {
+  fn checked_date2j(&self, y: i32, m: i32, d: i32) -> Option<i32> {
+    julian_day(coptic_julian(ETHIOPIAN_EPOCH_JDATE, y as i64, m as i64, d as i64))
+  }
+
+  fn j2date(&self, julian_day: i32) -> (i32, u32, u32) {
+    coptic_date(ETHIOPIAN_EPOCH_JDATE, julian_day)
+  }
+
+  fn months_in_year(&self, _y: i32) -> i32 {
+    13
+  }
+
+  fn month_name(&self, _y: i32, m: i32) -> &'static str {
+    ETHIOPIAN_MONTHS[(m - 1) as usize]
+  }
+}
+
+/// A Japanese era, from the day it began.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Era {
+  /// the name in Latin letters, e.g. "Reiwa"
+  pub name: &'static str,
+  /// the name in kanji, e.g. "令和"
+  pub kanji: &'static str,
+  /// the first day of the era
+  pub start: Date
+}
+
+/// The Japanese eras from Meiji on, with the first days of CLDR. Japan took
+/// up the Gregorian calendar in Meiji 6 (1873); earlier Meiji dates are
+/// proleptic Gregorian here.
+pub const JAPANESE_ERAS: [Era;5] = [
+  Era { name: "Meiji", kanji: "明治", start: -47961 },  // 1868-09-08
+  Era { name: "Taisho", kanji: "大正", start: -31931 }, // 1912-07-30
+  Era { name: "Showa", kanji: "昭和", start: -26670 },  // 1926-12-25
+  Era { name: "Heisei", kanji: "平成", start: -4010 },  // 1989-01-08
+  Era { name: "Reiwa", kanji: "令和", start: 7060 }     // 2019-05-01
+];
+
+/// The Japanese era of a date, with the year of the era, the month and
+/// the day. Return None for a date before Meiji or out of range.
+pub fn to_japanese(date: Date) -> Option<(Era, i32, u32, u32)> {
+  if !IS_VALID_DATE(date) {
+    return None;
+  }
+
+  let era = match JAPANESE_ERAS.iter().rev().find(|e| e.start <= date) {
+    Some(e) => e,
+    None => return None
+  };
+  let (first, _, _) = j2date(era.start + POSTGRES_EPOCH_JDATE);
+  let (year, month, day) = j2date(date + POSTGRES_EPOCH_JDATE);
+
+  Some((*era, year - first + 1, month, day))
+}
+
+/// The date of year y, month m, day d of a Japanese era. Return None if
+/// there is no such day in the era.
+pub fn from_japanese(era: &Era, y: i32, m: i32, d: i32) -> Option<Date> {
+  if y < 1 {
+    return None;
+  }
+  let (first, _, _) = j2date(era.start + POSTGRES_EPOCH_JDATE);
+  let year = match first.checked_add(y - 1) {
+    Some(year) if year < JULIAN_MAXYEAR => year,
+    _ => return None
+  };
+  if !Gregorian.is_valid(year, m, d) {
+    return None;
+  }
+
+  let date = date2j(year, m, d) - POSTGRES_EPOCH_JDATE;
+  let next = JAPANESE_ERAS.iter().find(|e| e.start > era.start);
+  if date < era.start || next.map_or(false, |e| date >= e.start) {
+    return None;
+  }
+  Some(date)
+}
+
+/// Format a date in its Japanese era, e.g. "令和6年10月19日". The first year
+/// of an era is "元年". Return None for a date before Meiji or out of range.
+pub fn japanese_out(date: Date) -> Option<String> {
+  match to_japanese(date) {
+    Some((era, 1, month, day)) => Some(format!("{}元年{}月{}日", era.kanji, month, day)),
+    Some((era, year, month, day)) => Some(format!("{}{}年{}月{}日", era.kanji, year, month,
+                                                  day)),
+    None => None
+  }
+}
+
+/// The ISO 8601 week date of a date, as the ISO year, the week, and the
+/// day of week from Monday = 1 to Sunday = 7. Return None for a date out
+/// of range.
+pub fn to_iso_week(date: Date) -> Option<(i32, i32, i32)> {
+  if !IS_VALID_DATE(date) {
+    return None;
+  }
+
+  let julian = date + POSTGRES_EPOCH_JDATE;
+  let (y, m, d) = j2date(julian);
+  let wday = j2day(julian);
+  Some((date2isoyear(y, m as i32, d as i32), date2isoweek(y, m as i32, d as i32),
+        if wday == 0 { 7 } else { wday }))
+}
+
+/// The date of an ISO 8601 week date, with the day of week from Monday = 1
+/// to Sunday = 7. Return None if the year has no such week or the date is
+/// out of range.
+pub fn from_iso_week(year: i32, week: i32, wday: i32) -> Option<Date> {
+  if year <= JULIAN_MINYEAR || year >= JULIAN_MAXYEAR || week < 1 || wday < 1 || wday > 7 {
+    return None;
+  }
+  // the week of Dec 28 is always the last
+  if week > date2isoweek(year, 12, 28) {
+    return None;
+  }
+
+  let date = isoweek2j(year, week) + wday - 1 - POSTGRES_EPOCH_JDATE;
+  if IS_VALID_DATE(date) { Some(date) } else { None }
+}
+
+/// The date in the calendar cal, as (year, month, day). Return None for an
+/// infinite date or one past the last Julian day j2date() takes.
+pub fn to_calendar<C: Calendar>(date: Date, cal: &C) -> Option<(i32, u32, u32)> {
+  if date_is_nobegin(date) || date_is_noend(date) {
+    return None;
+  }
+
+  match date.checked_add(POSTGRES_EPOCH_JDATE) {
+    Some(julian) => Some(cal.j2date(julian)),
+    None => None
+  }
+}
+
+/// The date of year y, month m, day d of the calendar cal. Return None if
+/// the calendar has no such day or it fails IS_VALID_DATE().
+pub fn from_calendar<C: Calendar>(y: i32, m: i32, d: i32, cal: &C) -> Option<Date> {
+  if !cal.is_valid(y, m, d) {
+    return None;
+  }
+
+  match cal.checked_date2j(y, m, d).and_then(|j| j.checked_sub(POSTGRES_EPOCH_JDATE)) {
+    Some(date) if IS_VALID_DATE(date) => Some(date),
+    _ => None
+  }
+}
+
+/// Format a date by day, month name and year in the calendar cal, e.g.
+/// "1 Tishrei 5785". Years before 1 are followed by the label of
+/// Calendar::before_era(), e.g. "15 Mar 44 BC", or are negative if the
+/// calendar has none.
+/// Return None for an infinite date or one past the last Julian day
+/// j2date() takes.
+pub fn date_long<C: Calendar>(date: Date, cal: &C) -> Option<String> {
+  match to_calendar(date, cal) {
+    Some((y, m, d)) if y > 0 => Some(format!("{} {} {}", d, cal.month_name(y, m as i32), y)),
+    Some((y, m, d)) => match cal.before_era() {
+      Some(era) => Some(format!("{} {} {} {}", d, cal.month_name(y, m as i32), 1 - y, era)),
+      None => Some(format!("{} {} {}", d, cal.month_name(y, m as i32), y))
+    },
+    None => None
+  }
+}
+
+/// date_in() for a date in the calendar cal.
+pub fn date_in<C: Calendar>(s: &str, cal: &C) -> Result<Date, DateTimeParseError> {
+  decode_date_text(s, false, cal)
+}
+
+/// date_out() in the calendar cal.
+pub fn date_out<C: Calendar>(date: Date, cal: &C, style: DateStyle) -> Option<String> {
+  date_out_cal(date, style, cal)
+}
+
+/// timestamp_in() for a date in the calendar cal.
+pub fn timestamp_in<C: Calendar>(s: &str, cal: &C) -> Result<Timestamp, DateTimeParseError> {
+  decode_timestamp(s, "timestamp", false, false, cal)
+}
+
+/// timestamp_out() in the calendar cal.
+pub fn timestamp_out<C: Calendar>(timestamp: Timestamp, cal: &C, style: DateStyle)
+                                  -> Option<String> {
+  timestamp_out_cal(timestamp, style, cal)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::{date2j, DateTimeErrorKind, DateStyle, DATEVAL_NOBEGIN, DATEVAL_NOEND,
+                     POSTGRES_EPOCH_JDATE};
+
+  fn date(cal: &Historical, s: &str) -> Date {
+    date_in(s, cal).ok().unwrap()
+  }
+
+  fn error_kind(r: Result<Date, DateTimeParseError>) -> DateTimeErrorKind {
+    r.err().unwrap().kind()
+  }
+
+  /// Check that every day of cal follows the one before, down to the ends
+  /// of an i32.
+  fn assert_successive<C: Calendar>(cal: &C) {
+    let windows = [(::std::i32::MIN, ::std::i32::MIN + 1000), (-2000000, -1900000),
+                   (-1000, 100000), (2000000, 2500000),
+                   (::std::i32::MAX - 1000, ::std::i32::MAX)];
+    for &(from, to) in windows.iter() {
+      let mut prev = cal.j2date(from);
+      assert_eq!(from, cal.date2j(prev.0, prev.1 as i32, prev.2 as i32));
+      for julian in from + 1..to {
+        let (y, m, d) = cal.j2date(julian);
+        assert_eq!(julian, cal.date2j(y, m as i32, d as i32));
+        assert!(cal.is_valid(y, m as i32, d as i32));
+        if d == 1 {
+          // the day before was the last of its month
+          assert!(!cal.is_valid(prev.0, prev.1 as i32, prev.2 as i32 + 1));
+          assert_eq!(prev.1 % cal.months_in_year(prev.0) as u32 + 1, m);
+          assert_eq!(if m == 1 { prev.0 + 1 } else { prev.0 }, y);
+        } else {
+          assert_eq!((prev.0, prev.1, prev.2 + 1), (y, m, d));
+        }
+        prev = (y, m, d);
+      }
+    }
+  }
+
+  #[test]
+  fn test_julian() {
+    assert_eq!(2299160, Julian.date2j(1582, 10, 4));
+    assert_eq!((1582, 10, 4), Julian.j2date(2299160));
+    assert_eq!(date2j(2000, 1, 14), Julian.date2j(2000, 1, 1));
+    // day 0 is 4713 BC in the Julian calendar
+    assert_eq!((-4712, 1, 1), Julian.j2date(0));
+    assert_eq!(0, Julian.date2j(-4712, 1, 1));
+    assert_eq!(Julian.date2j(1500, 3, 1) - 1, Julian.date2j(1500, 2, 29));
+    assert!(Julian.is_valid(1500, 2, 29));
+    assert!(!Gregorian.is_valid(1500, 2, 29));
+    assert!(Julian.is_valid(-4, 2, 29));
+    assert!(!Julian.is_valid(1501, 2, 29));
+    assert_eq!(None, Julian.checked_date2j(::std::i32::MAX, 1, 1));
+    assert_eq!(None, Julian.checked_date2j(::std::i32::MIN, 1, 1));
+
+    // ISO weeks are Gregorian, and name the same day in any calendar
+    let monday = date2j(1500, 3, 5) - POSTGRES_EPOCH_JDATE;
+    assert_eq!(Julian.date2j(1500, 2, 24) - POSTGRES_EPOCH_JDATE, monday);
+    assert_eq!(monday, date_in("1500-W10-1", &Julian).ok().unwrap());
+    assert_eq!(monday, date_in("1500W101", &Julian).ok().unwrap());
+    assert_eq!(timestamp_in("1500-03-05 10:00", &Gregorian).ok(),
+               timestamp_in("1500-W10-1 10:00", &Julian).ok());
+
+    assert_successive(&Julian);
+  }
+
+  #[test]
+  fn test_historical() {
+    let cal = Historical::default();
+    assert_eq!(GREGORIAN_REFORM_JDATE, date2j(1582, 10, 15));
+    assert_eq!(GREGORIAN_REFORM_JDATE, cal.changeover());
+    assert_eq!(GREGORIAN_REFORM_JDATE - 1, cal.date2j(1582, 10, 4));
+    assert_eq!((1582, 10, 4), cal.j2date(GREGORIAN_REFORM_JDATE - 1));
+    assert_eq!((1582, 10, 15), cal.j2date(GREGORIAN_REFORM_JDATE));
+    assert!(!cal.is_valid(1582, 10, 5));
+    assert!(!cal.is_valid(1582, 10, 14));
+    assert!(cal.is_valid(1500, 2, 29));
+    assert!(!cal.is_valid(1700, 2, 29));
+
+    // the skipped days are out on input
+    assert_eq!(date(&cal, "1582-10-04") + 1, date(&cal, "1582-10-15"));
+    assert_eq!(date2j(1582, 10, 14) - POSTGRES_EPOCH_JDATE, date(&cal, "1582-10-04"));
+    assert_eq!(DateTimeErrorKind::FieldOverflow, error_kind(date_in("1582-10-10", &cal)));
+    assert_eq!(DateTimeErrorKind::FieldOverflow, error_kind(date_in("1582-10-05", &cal)));
+    assert_eq!(date(&cal, "1582-10-15"), date(&cal, "1582-278"));
+    assert_eq!(date(&cal, "1582-12-31"), date(&cal, "1582-355"));
+    assert_eq!(DateTimeErrorKind::FieldOverflow, error_kind(date_in("1582-356", &cal)));
+    assert_eq!(date(&cal, "1000-03-01") - 1, date(&cal, "1000-02-29"));
+    assert_eq!(date(&cal, "1582-10-15"), date(&cal, "J2299161"));
+
+    // and never printed
+    let last = date(&cal, "1582-10-04");
+    assert_eq!("1582-10-04", date_out(last, &cal, DateStyle::ISO).unwrap());
+    assert_eq!("1582-10-15", date_out(last + 1, &cal, DateStyle::ISO).unwrap());
+    assert_eq!("1582-10-14", date_out(last, &Gregorian, DateStyle::ISO).unwrap());
+    assert_eq!("1582-277", date_out(last, &cal, DateStyle::Ordinal).unwrap());
+    assert_eq!("1582-278", date_out(last + 1, &cal, DateStyle::Ordinal).unwrap());
+    assert_eq!("1582-W41-4", date_out(last, &cal, DateStyle::IsoWeek).unwrap());
+    assert_eq!("J2299160", date_out(last, &cal, DateStyle::Julian).unwrap());
+    assert_eq!("0044-03-15 BC", date_out(date(&cal, "0044-03-15 BC"), &cal,
+                                         DateStyle::ISO).unwrap());
+  }
+
+  #[test]
+  fn test_british() {
+    let cal = Historical::new(BRITISH_REFORM_JDATE);
+    assert_eq!(BRITISH_REFORM_JDATE, date2j(1752, 9, 14));
+    assert_eq!(date(&cal, "1752-09-02") + 1, date(&cal, "1752-09-14"));
+    assert_eq!(DateTimeErrorKind::FieldOverflow, error_kind(date_in("1752-09-03", &cal)));
+    assert_eq!(date(&cal, "1700-03-01") - 1, date(&cal, "1700-02-29"));
+    assert_eq!(date_in("1582-10-14", &Julian).ok(), date_in("1582-10-14", &cal).ok());
+    assert_eq!("Wed Sep 02 00:00:00 1752",
+               timestamp_out(timestamp_in("1752-09-02", &cal).ok().unwrap(), &cal,
+                             DateStyle::Postgres).unwrap());
+    assert_eq!("Thu Sep 14 12:00:00 1752",
+               timestamp_out(timestamp_in("1752-09-14 12:00", &cal).ok().unwrap(), &cal,
+                             DateStyle::Postgres).unwrap());
+  }
+
+  #[test]
+  fn test_timestamp() {
+    let cal = Historical::default();
+    let t = timestamp_in("1582-10-04 23:59:59.5", &cal).ok().unwrap();
+    assert_eq!(t, timestamp_in("1582-10-14 23:59:59.5", &Gregorian).ok().unwrap());
+    assert_eq!("1582-10-04 23:59:59.5", timestamp_out(t, &cal, DateStyle::ISO).unwrap());
+    assert_eq!("1582-10-15 00:00:00", timestamp_out(t + 500000, &cal, DateStyle::ISO).unwrap());
+    assert_eq!("J2299160.99999421296", timestamp_out(t, &cal, DateStyle::Julian).unwrap());
+    assert!(timestamp_in("1582-10-10 12:00", &cal).is_err());
+    assert_eq!(timestamp_in("1582-10-15", &Gregorian).ok(),
+               timestamp_in("1582-10-15", &cal).ok());
+    assert!(timestamp_in("294276-12-20", &Julian).is_err());
+  }
+
+  #[test]
+  fn test_hebrew() {
+    assert_eq!(HEBREW_EPOCH_JDATE, Julian.date2j(-3760, 10, 7));
+    assert_eq!((1, 1, 1), Hebrew.j2date(HEBREW_EPOCH_JDATE));
+    assert_eq!((5785, 1, 1), Hebrew.j2date(date2j(2024, 10, 3)));
+    assert_eq!((5784, 1, 1), Hebrew.j2date(date2j(2023, 9, 16)));
+    // 5784 is a leap year, with Adar II as month 7
+    assert_eq!(13, Hebrew.months_in_year(5784));
+    assert_eq!(12, Hebrew.months_in_year(5785));
+    assert_eq!((5784, 7, 1), Hebrew.j2date(date2j(2024, 3, 11)));
+    assert_eq!((5784, 8, 15), Hebrew.j2date(date2j(2024, 4, 23)));
+    assert_eq!((5785, 7, 15), Hebrew.j2date(date2j(2025, 4, 13)));
+    assert_eq!(date2j(1948, 5, 14), Hebrew.date2j(5708, 9, 5));
+    assert_eq!("Adar I", Hebrew.month_name(5784, 6));
+    assert_eq!("Adar II", Hebrew.month_name(5784, 7));
+    assert_eq!("Adar", Hebrew.month_name(5785, 6));
+    assert_eq!("Nisan", Hebrew.month_name(5785, 7));
+    assert_eq!("Elul", Hebrew.month_name(5785, 12));
+    assert!(!Hebrew.is_valid(5785, 13, 1));
+    assert!(Hebrew.is_valid(5784, 13, 29));
+
+    // the lengths of the years and of Cheshvan and Kislev
+    for y in 5700..5800 {
+      let days = Hebrew.date2j(y + 1, 1, 1) - Hebrew.date2j(y, 1, 1);
+      let short = if Hebrew.months_in_year(y) == 13 { 383 } else { 353 };
+      assert!(days >= short && days <= short + 2);
+      assert_eq!(days - short == 2, Hebrew.is_valid(y, 2, 30));
+      assert_eq!(days - short != 0, Hebrew.is_valid(y, 3, 30));
+      // Rosh Hashanah is never on a Sunday, Wednesday or Friday
+      let wday = j2day(Hebrew.date2j(y, 1, 1));
+      assert!(wday != 0 && wday != 3 && wday != 5);
+    }
+
+    assert_successive(&Hebrew);
+  }
+
+  #[test]
+  fn test_islamic() {
+    assert_eq!(ISLAMIC_EPOCH_JDATE, Julian.date2j(622, 7, 16));
+    assert_eq!((1, 1, 1), Islamic.j2date(ISLAMIC_EPOCH_JDATE));
+    assert_eq!((1420, 9, 24), Islamic.j2date(date2j(2000, 1, 1)));
+    assert_eq!((1446, 1, 1), Islamic.j2date(date2j(2024, 7, 8)));
+    // 30 years have 10631 days
+    assert_eq!(10631, Islamic.date2j(1441, 1, 1) - Islamic.date2j(1411, 1, 1));
+    assert!(Islamic.is_valid(1445, 12, 30));
+    assert!(!Islamic.is_valid(1444, 12, 30));
+    assert!(!Islamic.is_valid(1445, 2, 30));
+    assert_eq!("Ramadan", Islamic.month_name(1445, 9));
+
+    assert_successive(&Islamic);
+  }
+
+  #[test]
+  fn test_persian() {
+    assert_eq!(PERSIAN_EPOCH_JDATE, Julian.date2j(622, 3, 18));
+    assert_eq!((1, 1, 1), Persian.j2date(PERSIAN_EPOCH_JDATE));
+    assert_eq!((1403, 1, 1), Persian.j2date(date2j(2024, 3, 20)));
+    assert_eq!((1403, 12, 30), Persian.j2date(date2j(2025, 3, 20)));
+    assert_eq!((1404, 1, 1), Persian.j2date(date2j(2025, 3, 21)));
+    assert_eq!((1357, 11, 22), Persian.j2date(date2j(1979, 2, 11)));
+    assert_eq!((1403, 7, 1), Persian.j2date(date2j(2024, 9, 22)));
+    assert!(!Persian.is_valid(1402, 12, 30));
+    assert!(!Persian.is_valid(1403, 7, 31));
+    assert_eq!("Farvardin", Persian.month_name(1403, 1));
+    assert_eq!("Esfand", Persian.month_name(1403, 12));
+
+    assert_successive(&Persian);
+  }
+
+  #[test]
+  fn test_coptic() {
+    assert_eq!(COPTIC_EPOCH_JDATE, Julian.date2j(284, 8, 29));
+    assert_eq!(ETHIOPIAN_EPOCH_JDATE, Julian.date2j(8, 8, 29));
+    assert_eq!((1741, 1, 1), Coptic.j2date(date2j(2024, 9, 11)));
+    assert_eq!((2017, 1, 1), Ethiopian.j2date(date2j(2024, 9, 11)));
+    // Pagume has six days before a Gregorian leap year
+    assert_eq!((2015, 13, 6), Ethiopian.j2date(date2j(2023, 9, 11)));
+    assert_eq!((2016, 4, 28), Ethiopian.j2date(date2j(2024, 1, 7)));
+    assert!(!Coptic.is_valid(1740, 13, 6));
+    assert!(Coptic.is_valid(1739, 13, 6));
+    assert!(!Coptic.is_valid(1739, 14, 1));
+    assert_eq!("Pi Kogi Enavot", Coptic.month_name(1739, 13));
+    assert_eq!("Meskerem", Ethiopian.month_name(2017, 1));
+
+    assert_successive(&Coptic);
+    assert_successive(&Ethiopian);
+  }
+
+  #[test]
+  fn test_japanese() {
+    let date = |y, m, d| date2j(y, m, d) - POSTGRES_EPOCH_JDATE;
+    for era in JAPANESE_ERAS.iter() {
+      assert_eq!(Some((*era, 1, j2date(era.start + POSTGRES_EPOCH_JDATE).1,
+                       j2date(era.start + POSTGRES_EPOCH_JDATE).2)),
+                 to_japanese(era.start));
+    }
+    assert_eq!(date(1868, 9, 8), JAPANESE_ERAS[0].start);
+    assert_eq!(date(2019, 5, 1), JAPANESE_ERAS[4].start);
+
+    assert_eq!(Some((JAPANESE_ERAS[3], 31, 4, 30)), to_japanese(date(2019, 4, 30)));
+    assert_eq!(Some((JAPANESE_ERAS[4], 6, 10, 19)), to_japanese(date(2024, 10, 19)));
+    assert_eq!(None, to_japanese(date(1868, 9, 7)));
+    assert_eq!(Some(date(1989, 1, 7)), from_japanese(&JAPANESE_ERAS[2], 64, 1, 7));
+    assert_eq!(None, from_japanese(&JAPANESE_ERAS[2], 64, 1, 8));
+    assert_eq!(None, from_japanese(&JAPANESE_ERAS[3], 1, 1, 7));
+    assert_eq!(None, from_japanese(&JAPANESE_ERAS[4], 0, 5, 1));
+    assert_eq!(None, from_japanese(&JAPANESE_ERAS[4], ::std::i32::MIN, 5, 1));
+    assert_eq!(None, from_japanese(&JAPANESE_ERAS[4], 2, 2, 30));
+    assert_eq!(Some(date(2024, 10, 19)), from_japanese(&JAPANESE_ERAS[4], 6, 10, 19));
+
+    assert_eq!("令和6年10月19日", japanese_out(date(2024, 10, 19)).unwrap());
+    assert_eq!("令和元年5月1日", japanese_out(date(2019, 5, 1)).unwrap());
+    assert_eq!("平成31年4月30日", japanese_out(date(2019, 4, 30)).unwrap());
+    assert_eq!("明治元年9月8日", japanese_out(date(1868, 9, 8)).unwrap());
+    assert_eq!(None, japanese_out(DATEVAL_NOEND));
+  }
+
+  #[test]
+  fn test_iso_week() {
+    let date = |y, m, d| date2j(y, m, d) - POSTGRES_EPOCH_JDATE;
+    assert_eq!(Some((2016, 45, 5)), to_iso_week(date(2016, 11, 11)));
+    assert_eq!(Some((2009, 53, 7)), to_iso_week(date(2010, 1, 3)));
+    assert_eq!(Some((2025, 1, 1)), to_iso_week(date(2024, 12, 30)));
+    assert_eq!(Some(date(2016, 11, 11)), from_iso_week(2016, 45, 5));
+    assert_eq!(Some(date(2010, 1, 3)), from_iso_week(2009, 53, 7));
+    assert_eq!(None, from_iso_week(2010, 53, 1));
+    assert_eq!(None, from_iso_week(2010, 0, 1));
+    assert_eq!(None, from_iso_week(2010, 1, 8));
+    assert_eq!(None, to_iso_week(DATEVAL_NOBEGIN));
+
+    for d in date(1999, 12, 1)..date(2030, 1, 31) {
+      let (y, w, wd) = to_iso_week(d).unwrap();
+      assert_eq!(Some(d), from_iso_week(y, w, wd));
+    }
+  }
+
+  /// Check that the calendar has no Julian day for the first and last
+  /// i32 years, rather than a wrapped one.
+  fn assert_no_julian_day<C: Calendar>(cal: &C) {
+    assert_eq!(None, cal.checked_date2j(::std::i32::MAX, 1, 1));
+    assert_eq!(None, cal.checked_date2j(::std::i32::MIN, 1, 1));
+    assert_eq!(None, from_calendar(::std::i32::MAX, 1, 1, cal));
+  }
+
+  #[test]
+  fn test_converters() {
+    let date = date2j(2024, 10, 3) - POSTGRES_EPOCH_JDATE;
+    assert_eq!(Some((5785, 1, 1)), to_calendar(date, &Hebrew));
+    assert_eq!(Some(date), from_calendar(5785, 1, 1, &Hebrew));
+    assert_eq!(None, from_calendar(5785, 13, 1, &Hebrew));
+    assert_eq!(Some((1446, 3, 29)), to_calendar(date, &Islamic));
+    assert_eq!(None, to_calendar(DATEVAL_NOEND, &Persian));
+    assert_eq!(None, from_calendar(5874898, 1, 1, &Gregorian));
+    assert_eq!(None, from_calendar(::std::i32::MAX, 1, 1, &Gregorian));
+    assert_no_julian_day(&Julian);
+    assert_no_julian_day(&Hebrew);
+    assert_no_julian_day(&Islamic);
+    assert_no_julian_day(&Persian);
+    assert_no_julian_day(&Coptic);
+    assert_no_julian_day(&Ethiopian);
+
+    assert_eq!("1 Tishrei 5785", date_long(date, &Hebrew).unwrap());
+    assert_eq!("29 Rabi' al-Awwal 1446", date_long(date, &Islamic).unwrap());
+    assert_eq!("12 Mehr 1403", date_long(date, &Persian).unwrap());
+    assert_eq!("23 Thout 1741", date_long(date, &Coptic).unwrap());
+    assert_eq!("23 Meskerem 2017", date_long(date, &Ethiopian).unwrap());
+    assert_eq!("3 Oct 2024", date_long(date, &Gregorian).unwrap());
+    assert_eq!("15 Mar 44 BC", date_long(Julian.date2j(-43, 3, 15) - POSTGRES_EPOCH_JDATE,
+                                         &Julian).unwrap());
+    assert_eq!("1 Tishrei 0", date_long(Hebrew.date2j(0, 1, 1) - POSTGRES_EPOCH_JDATE,
+                                        &Hebrew).unwrap());
+    assert_eq!("1 Tishrei -1", date_long(Hebrew.date2j(-1, 1, 1) - POSTGRES_EPOCH_JDATE,
+                                         &Hebrew).unwrap());
+    assert_eq!("1 Muharram 1 BH", date_long(Islamic.date2j(0, 1, 1) - POSTGRES_EPOCH_JDATE,
+                                            &Islamic).unwrap());
+    assert_eq!("1 Thout -3", date_long(Coptic.date2j(-3, 1, 1) - POSTGRES_EPOCH_JDATE,
+                                       &Coptic).unwrap());
+
+    // the calendar's months on input and output
+    assert_eq!(date, date_in("5785-01-01", &Hebrew).ok().unwrap());
+    assert_eq!(date2j(2024, 3, 11) - POSTGRES_EPOCH_JDATE,
+               date_in("5784-07-01", &Hebrew).ok().unwrap());
+    assert_eq!(DateTimeErrorKind::MdFieldOverflow, error_kind(date_in("5785-13-01", &Hebrew)));
+    assert_eq!("5784-13-29", date_out(date - 1, &Hebrew, DateStyle::ISO).unwrap());
+    assert_eq!("5785-001", date_out(date, &Hebrew, DateStyle::Ordinal).unwrap());
+    // a leap year of 383 days
+    assert_eq!(383, Hebrew.date2j(5785, 1, 1) - Hebrew.date2j(5784, 1, 1));
+    assert_eq!(date - 4, date_in("5784-380", &Hebrew).ok().unwrap());
+    assert_eq!(date - 1, date_in("5784-383", &Hebrew).ok().unwrap());
+    assert_eq!(date - 1, date_in("5784383", &Hebrew).ok().unwrap());
+    assert_eq!("5784-383", date_out(date - 1, &Hebrew, DateStyle::Ordinal).unwrap());
+    assert_eq!(DateTimeErrorKind::FieldOverflow, error_kind(date_in("5784-384", &Hebrew)));
+    assert_eq!(DateTimeErrorKind::FieldOverflow, error_kind(date_in("5785-380", &Hebrew)));
+    assert_eq!("2024-W40-4", date_out(date, &Hebrew, DateStyle::IsoWeek).unwrap());
+    assert_eq!("Thu Tishrei 01 00:00:00 5785",
+               timestamp_out(date as i64 * 86400000000, &Hebrew, DateStyle::Postgres).unwrap());
+    assert_eq!("Tue Pagume 05 00:00:00 2016",
+               timestamp_out((date2j(2024, 9, 10) - POSTGRES_EPOCH_JDATE) as i64 * 86400000000,
+                             &Ethiopian, DateStyle::Postgres).unwrap());
+  }
+}
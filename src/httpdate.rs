@@ -0,0 +1,235 @@
+//! HTTP-date (RFC 7231 section 7.1.1.1), as used in HTTP headers.
+//!
+//! `decode` accepts all three forms a recipient must understand:
+//!
+//! * IMF-fixdate: "Sun, 06 Nov 1994 08:49:37 GMT"
+//! * the obsolete RFC 850 form: "Sunday, 06-Nov-94 08:49:37 GMT"
+//! * the obsolete asctime() form: "Sun Nov  6 08:49:37 1994"
+//!
+//! All of them are in GMT. `encode` writes IMF-fixdate, the only form a
+//! sender may generate.
+
+use std::ops::Range;
+
+use super::{date2j, get_current_timestamp, isalpha, j2day, timestamp2tm, timestamp_not_finite,
+            DateTimeErrorKind, DateTimeParseError, TimestampTz, DAYS, MONTH, MONTHS};
+use super::rfc2822::{Fields, Scanner};
+
+/// Decode an HTTP-date in any of its three forms. A two-digit RFC 850 year
+/// is taken to be within 50 years from now.
+pub fn decode(s: &str) -> Result<TimestampTz, DateTimeParseError> {
+  let current_year = match timestamp2tm(get_current_timestamp()) {
+    Some((tm, _)) => tm.tm_year,
+    None => 2000
+  };
+
+  decode_in_year(s, current_year)
+}
+
+/// The body of decode(), with the year the current one.
+fn decode_in_year(s: &str, current_year: i32) -> Result<TimestampTz, DateTimeParseError> {
+  let mut sc = Scanner::new(s, false);
+
+  let (word, wday_span) = sc.word();
+  match sc.peek() {
+    // asctime-date = day-name SP date3 SP time-of-day SP year
+    Some(b' ') => {
+      let wday = short_day(&sc, &word, &wday_span)?;
+      sc.expect(b' ')?;
+      let (mon, _) = sc.name(MONTH)?;
+      sc.expect(b' ')?;
+      // date3 = month SP ( 2DIGIT / ( SP 1DIGIT ))
+      let mday = if sc.peek() == Some(b' ') {
+        sc.expect(b' ')?;
+        sc.number(1, 1)?
+      } else {
+        sc.number(2, 2)?
+      };
+      sc.expect(b' ')?;
+      let time = sc.time_of_day(false)?;
+      sc.expect(b' ')?;
+      let (year, _) = sc.number(4, 4)?;
+      sc.end()?;
+
+      sc.timestamp(Fields {
+        wday: Some((wday, wday_span)),
+        mday: mday,
+        mon: mon,
+        year: year,
+        time: time,
+        tz: 0
+      })
+    }
+
+    // IMF-fixdate = day-name "," SP date1 SP time-of-day SP GMT
+    Some(b',') if word.len() == 3 => {
+      let wday = short_day(&sc, &word, &wday_span)?;
+      sc.expect(b',')?;
+      sc.expect(b' ')?;
+      // date1 = day SP month SP year
+      let mday = sc.number(2, 2)?;
+      sc.expect(b' ')?;
+      let (mon, _) = sc.name(MONTH)?;
+      sc.expect(b' ')?;
+      let (year, _) = sc.number(4, 4)?;
+      sc.expect(b' ')?;
+      let time = sc.time_of_day(false)?;
+      gmt(&mut sc)?;
+
+      sc.timestamp(Fields {
+        wday: Some((wday, wday_span)),
+        mday: mday,
+        mon: mon,
+        year: year,
+        time: time,
+        tz: 0
+      })
+    }
+
+    // rfc850-date = day-name-l "," SP date2 SP time-of-day SP GMT
+    Some(b',') => {
+      let wday = match DAYS.iter().position(|d| d.as_bytes().eq_ignore_ascii_case(&word)) {
+        Some(i) => i as i32,
+        None => return Err(sc.error(DateTimeErrorKind::BadFormat, wday_span))
+      };
+      sc.expect(b',')?;
+      sc.expect(b' ')?;
+      // date2 = day "-" month "-" 2DIGIT
+      let mday = sc.number(2, 2)?;
+      sc.expect(b'-')?;
+      let (mon, _) = sc.name(MONTH)?;
+      sc.expect(b'-')?;
+      let (yy, _) = sc.number(2, 2)?;
+      sc.expect(b' ')?;
+      let time = sc.time_of_day(false)?;
+      gmt(&mut sc)?;
+
+      sc.timestamp(Fields {
+        wday: Some((wday, wday_span)),
+        mday: mday,
+        mon: mon,
+        year: rfc850_year(yy, current_year),
+        time: time,
+        tz: 0
+      })
+    }
+
+    _ => Err(sc.unexpected())
+  }
+}
+
+/// The value of a three-letter day name already read into word.
+fn short_day(sc: &Scanner, word: &[u8], span: &Range<usize>)
+             -> Result<i32, DateTimeParseError> {
+  match DAYS.iter().position(|d| d.as_bytes()[..3].eq_ignore_ascii_case(word)) {
+    Some(i) if word.len() == 3 => Ok(i as i32),
+    _ => Err(sc.error(DateTimeErrorKind::BadFormat, span.clone()))
+  }
+}
+
+/// Consume the trailing SP "GMT".
+fn gmt(sc: &mut Scanner) -> Result<(), DateTimeParseError> {
+  sc.expect(b' ')?;
+  if !sc.peek().map_or(false, isalpha) {
+    return Err(sc.unexpected());
+  }
+  let (word, span) = sc.word();
+  if word != b"gmt" {
+    return Err(sc.error(DateTimeErrorKind::BadFormat, span));
+  }
+  sc.end()
+}
+
+/// Expand a two-digit RFC 850 year: a year that appears to be more than
+/// 50 years in the future is the most recent past year with the same last
+/// two digits.
+fn rfc850_year(yy: i32, current_year: i32) -> i32 {
+  let year = current_year - current_year % 100 + yy;
+  if year > current_year + 50 {
+    year - 100
+  } else {
+    year
+  }
+}
+
+/// Encode t as an IMF-fixdate, e.g. "Sun, 06 Nov 1994 08:49:37 GMT".
+/// Fractional seconds are dropped. Return None for infinite timestamps and
+/// years that do not have four digits.
+pub fn encode(t: TimestampTz) -> Option<String> {
+  if timestamp_not_finite(t) {
+    return None;
+  }
+
+  let tm = match timestamp2tm(t) {
+    Some((tm, _)) if tm.tm_year >= 1 && tm.tm_year <= 9999 => tm,
+    _ => return None
+  };
+
+  let wday = j2day(date2j(tm.tm_year, tm.tm_mon, tm.tm_mday));
+  Some(format!("{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+               &DAYS[wday as usize][..3], tm.tm_mday, MONTHS[(tm.tm_mon - 1) as usize],
+               tm.tm_year, tm.tm_hour, tm.tm_min, tm.tm_sec))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::DT_NOEND;
+  use super::super::rfc2822::test_helpers::{error_at, ts};
+  use super::super::DateTimeErrorKind::*;
+
+  #[test]
+  fn test_decode() {
+    let t = ts(1994, 11, 6, 8, 49, 37);
+    assert_eq!(t, decode("Sun, 06 Nov 1994 08:49:37 GMT").ok().unwrap());
+    assert_eq!(t, decode("Sunday, 06-Nov-94 08:49:37 GMT").ok().unwrap());
+    assert_eq!(t, decode("Sun Nov  6 08:49:37 1994").ok().unwrap());
+    assert_eq!(ts(1994, 11, 16, 8, 49, 37), decode("Wed Nov 16 08:49:37 1994").ok().unwrap());
+    assert_eq!(t, decode("sun, 06 nov 1994 08:49:37 gmt").ok().unwrap());
+
+    // the two-digit year rule
+    assert_eq!(t, decode_in_year("Sunday, 06-Nov-94 08:49:37 GMT", 2026).ok().unwrap());
+    assert_eq!(ts(2076, 11, 6, 8, 49, 37),
+               decode_in_year("Friday, 06-Nov-76 08:49:37 GMT", 2026).ok().unwrap());
+    assert_eq!(ts(1977, 11, 6, 8, 49, 37),
+               decode_in_year("Sunday, 06-Nov-77 08:49:37 GMT", 2026).ok().unwrap());
+    assert_eq!(1999, rfc850_year(99, 2000));
+    assert_eq!(2050, rfc850_year(50, 2000));
+    assert_eq!(1951, rfc850_year(51, 2000));
+    assert_eq!(2026, rfc850_year(26, 2026));
+
+    assert_eq!((BadFormat, " ".to_owned()),
+               error_at(decode("Sunday, 06 Nov 1994 08:49:37 GMT")));
+    assert_eq!((BadFormat, "Sund".to_owned()),
+               error_at(decode("Sund, 06-Nov-94 08:49:37 GMT")));
+    assert_eq!((BadFormat, "6".to_owned()), error_at(decode("Sun, 6 Nov 1994 08:49:37 GMT")));
+    assert_eq!((BadFormat, "+".to_owned()),
+               error_at(decode("Sun, 06 Nov 1994 08:49:37 +0000")));
+    assert_eq!((BadFormat, "UTC".to_owned()),
+               error_at(decode("Sun, 06 Nov 1994 08:49:37 UTC")));
+    assert_eq!((BadFormat, " ".to_owned()), error_at(decode("Sun, 06 Nov 1994 08:49 GMT")));
+    assert_eq!((BadFormat, " ".to_owned()),
+               error_at(decode("Sun, 06 Nov 1994 08:49:37 GMT ")));
+    assert_eq!((BadFormat, "1994".to_owned()),
+               error_at(decode("Sunday, 06-Nov-1994 08:49:37 GMT")));
+    assert_eq!((BadFormat, "94".to_owned()), error_at(decode("Sun Nov 06 08:49:37 94")));
+    assert_eq!((FieldOverflow, "31".to_owned()),
+               error_at(decode("Sun, 31 Nov 1994 08:49:37 GMT")));
+    assert_eq!((WeekdayMismatch, "Mon".to_owned()),
+               error_at(decode("Mon, 06 Nov 1994 08:49:37 GMT")));
+  }
+
+  #[test]
+  fn test_encode() {
+    let t = ts(1994, 11, 6, 8, 49, 37);
+    assert_eq!("Sun, 06 Nov 1994 08:49:37 GMT", encode(t).unwrap());
+    assert_eq!("Sun, 06 Nov 1994 08:49:37 GMT", encode(t + 999999).unwrap());
+    assert_eq!("Mon, 01 Jan 0001 00:00:00 GMT", encode(ts(1, 1, 1, 0, 0, 0)).unwrap());
+    assert_eq!(None, encode(ts(10000, 1, 1, 0, 0, 0)));
+    assert_eq!(None, encode(DT_NOEND));
+
+    for t in [t, ts(2016, 2, 29, 23, 59, 59), ts(1970, 1, 1, 0, 0, 0)].iter() {
+      assert_eq!(*t, decode(&encode(*t).unwrap()).ok().unwrap());
+    }
+  }
+}
@@ -0,0 +1,576 @@
+//! ISO 8601 dates, times and date-times.
+//!
+//! `decode_date`, `decode_time` and `decode` accept the representations of
+//! ISO 8601-1 with four-digit years, in either the basic format
+//! ("20161111T103000") or the extended one ("2016-11-11T10:30:00"):
+//!
+//! * calendar dates "2016-11-11", ordinal dates "2016-316" and week dates
+//!   "2016-W45-5", and the reduced precision dates "2016-11", "2016" and
+//!   "2016-W45"
+//! * times of day "10:30:00", "10:30" and "10", where the last component
+//!   may have a decimal fraction of 1 to 9 digits ("10.5", "10:30,25"), and
+//!   "24:00" for the end of the day
+//! * a UTC designator "Z" or an offset "+09", "+0900" or "+09:00"
+//!
+//! A date-time must have a complete date, and its date, time and offset
+//! must all be in the same format. Expanded years are not accepted.
+
+use std::ops::Range;
+
+use super::{date2j, isleap, isoweek2j, parse_fraction_of, Date, DateTimeErrorKind,
+            DateTimeParseError, Rounding, Time, Timestamp, DAY_TAB, MAX_TZDISP_HOUR,
+            POSTGRES_EPOCH_JDATE, USECS_PER_DAY, USECS_PER_HOUR, USECS_PER_MINUTE,
+            USECS_PER_SEC};
+use super::rfc2822::Scanner;
+
+/// A date as read.
+struct DatePart {
+  /// Julian day of the date, or of its first day if it is reduced
+  jd: i32,
+  /// whether it was in the extended format, if it had any separator to tell
+  extended: Option<bool>,
+  /// whether it had a day
+  complete: bool,
+  span: Range<usize>
+}
+
+/// A time of day or UTC offset as read.
+struct Part<T> {
+  value: T,
+  /// whether it was in the extended format, if it had any separator to tell
+  extended: Option<bool>,
+  span: Range<usize>
+}
+
+/// Decode an ISO 8601 date. A reduced precision date ("2016-11", "2016" or
+/// "2016-W45") is taken to be its first day.
+pub fn decode_date(s: &str) -> Result<Date, DateTimeParseError> {
+  let mut sc = Scanner::for_type(s, "date");
+  let date = date(&mut sc)?;
+  sc.end()?;
+
+  Ok(date.jd - POSTGRES_EPOCH_JDATE)
+}
+
+/// Decode an ISO 8601 time of day, with or without a leading "T". Return
+/// the time and its UTC offset in seconds west, if it has one.
+pub fn decode_time(s: &str) -> Result<(Time, Option<i32>), DateTimeParseError> {
+  let mut sc = Scanner::for_type(s, "time");
+  if sc.peek() == Some(b'T') {
+    sc.expect(b'T')?;
+  }
+  let time = time(&mut sc)?;
+  let zone = zone(&mut sc)?;
+  sc.end()?;
+
+  if let Some(ref zone) = zone {
+    same_format(&sc, time.extended, zone)?;
+  }
+
+  Ok((time.value, zone.map(|z| z.value)))
+}
+
+/// Decode an ISO 8601 date-time, which must have a complete date. Return
+/// the local timestamp and its UTC offset in seconds west, if it has one.
+pub fn decode(s: &str) -> Result<(Timestamp, Option<i32>), DateTimeParseError> {
+  let mut sc = Scanner::for_type(s, "timestamp");
+  let date = date(&mut sc)?;
+  if !date.complete {
+    return Err(sc.error(DateTimeErrorKind::BadFormat, date.span));
+  }
+  sc.expect(b'T')?;
+  let time = time(&mut sc)?;
+  let zone = zone(&mut sc)?;
+  sc.end()?;
+
+  same_format(&sc, date.extended, &time)?;
+  if let Some(ref zone) = zone {
+    same_format(&sc, time.extended.or(date.extended), zone)?;
+  }
+
+  let t = (date.jd - POSTGRES_EPOCH_JDATE) as i64 * USECS_PER_DAY + time.value;
+  Ok((t, zone.map(|z| z.value)))
+}
+
+/// Fail unless part is in the format of what came before it.
+fn same_format<T>(sc: &Scanner, before: Option<bool>, part: &Part<T>)
+                  -> Result<(), DateTimeParseError> {
+  match (before, part.extended) {
+    (Some(a), Some(b)) if a != b => Err(sc.error(DateTimeErrorKind::BadFormat, part.span.clone())),
+    _ => Ok(())
+  }
+}
+
+/// The error for a run of digits of the wrong length.
+fn bad_run(sc: &Scanner, run: Range<usize>) -> DateTimeParseError {
+  if run.is_empty() {
+    sc.unexpected()
+  } else {
+    sc.error(DateTimeErrorKind::BadFormat, run)
+  }
+}
+
+/// Read a calendar, ordinal or week date, complete or reduced.
+fn date(sc: &mut Scanner) -> Result<DatePart, DateTimeParseError> {
+  let run = sc.digits();
+  let start = run.start;
+  match run.len() {
+    // YYYYMMDD
+    8 => {
+      let year = sc.value(start..start + 4);
+      Ok(DatePart {
+        jd: calendar_date(sc, year, start + 4..start + 6, start + 6..run.end)?,
+        extended: Some(false),
+        complete: true,
+        span: run
+      })
+    }
+
+    // YYYYDDD
+    7 => {
+      let year = sc.value(start..start + 4);
+      Ok(DatePart {
+        jd: ordinal_date(sc, year, start + 4..run.end)?,
+        extended: Some(false),
+        complete: true,
+        span: run
+      })
+    }
+
+    4 => {
+      let year = sc.value(run.clone());
+      match sc.peek() {
+        // YYYYWww[D]
+        Some(b'W') => {
+          sc.expect(b'W')?;
+          week_date(sc, year, false, start)
+        }
+
+        Some(b'-') => {
+          sc.expect(b'-')?;
+          // YYYY-Www[-D]
+          if sc.peek() == Some(b'W') {
+            sc.expect(b'W')?;
+            return week_date(sc, year, true, start);
+          }
+
+          let run = sc.digits();
+          match run.len() {
+            // YYYY-DDD
+            3 => {
+              Ok(DatePart {
+                jd: ordinal_date(sc, year, run.clone())?,
+                extended: Some(true),
+                complete: true,
+                span: start..run.end
+              })
+            }
+
+            // YYYY-MM-DD
+            2 if sc.peek() == Some(b'-') => {
+              sc.expect(b'-')?;
+              let (_, mday) = sc.number(2, 2)?;
+              Ok(DatePart {
+                jd: calendar_date(sc, year, run, mday.clone())?,
+                extended: Some(true),
+                complete: true,
+                span: start..mday.end
+              })
+            }
+
+            // YYYY-MM
+            2 => {
+              Ok(DatePart {
+                jd: date2j(year, month(sc, run.clone())?, 1),
+                extended: Some(true),
+                complete: false,
+                span: start..run.end
+              })
+            }
+
+            _ => Err(bad_run(sc, run))
+          }
+        }
+
+        // YYYY
+        _ => {
+          Ok(DatePart {
+            jd: date2j(year, 1, 1),
+            extended: None,
+            complete: false,
+            span: run
+          })
+        }
+      }
+    }
+
+    _ => Err(bad_run(sc, run))
+  }
+}
+
+fn month(sc: &Scanner, span: Range<usize>) -> Result<i32, DateTimeParseError> {
+  let mon = sc.value(span.clone());
+  if mon < 1 || mon > 12 {
+    return Err(sc.error(DateTimeErrorKind::FieldOverflow, span));
+  }
+  Ok(mon)
+}
+
+/// The Julian day of a calendar date.
+fn calendar_date(sc: &Scanner, year: i32, mon: Range<usize>, mday: Range<usize>)
+                 -> Result<i32, DateTimeParseError> {
+  let mon = month(sc, mon)?;
+  let day = sc.value(mday.clone());
+  if day < 1 || day > DAY_TAB[isleap(year) as usize][(mon - 1) as usize] {
+    return Err(sc.error(DateTimeErrorKind::FieldOverflow, mday));
+  }
+  Ok(date2j(year, mon, day))
+}
+
+/// The Julian day of an ordinal date.
+fn ordinal_date(sc: &Scanner, year: i32, yday: Range<usize>) -> Result<i32, DateTimeParseError> {
+  let day = sc.value(yday.clone());
+  if day < 1 || day > 365 + isleap(year) as i32 {
+    return Err(sc.error(DateTimeErrorKind::FieldOverflow, yday));
+  }
+  Ok(date2j(year, 1, 1) + day - 1)
+}
+
+/// Read the week and day of a week date after the "W". A reduced week date
+/// is taken to be the Monday.
+fn week_date(sc: &mut Scanner, year: i32, extended: bool, start: usize)
+             -> Result<DatePart, DateTimeParseError> {
+  let run = sc.digits();
+  let (week, wday) = match run.len() {
+    2 if extended && sc.peek() == Some(b'-') => {
+      sc.expect(b'-')?;
+      (run, Some(sc.number(1, 1)?.1))
+    }
+    2 => (run, None),
+    3 if !extended => (run.start..run.start + 2, Some(run.start + 2..run.end)),
+    _ => return Err(bad_run(sc, run))
+  };
+  let complete = wday.is_some();
+  let end = wday.as_ref().map_or(week.end, |d| d.end);
+
+  let weeks = (isoweek2j(year + 1, 1) - isoweek2j(year, 1)) / 7;
+  let woy = sc.value(week.clone());
+  if woy < 1 || woy > weeks {
+    return Err(sc.error(DateTimeErrorKind::FieldOverflow, week));
+  }
+
+  let isodow = match wday {
+    Some(span) => {
+      let d = sc.value(span.clone());
+      if d < 1 || d > 7 {
+        return Err(sc.error(DateTimeErrorKind::FieldOverflow, span));
+      }
+      d
+    }
+    None => 1
+  };
+
+  Ok(DatePart {
+    jd: isoweek2j(year, woy) + isodow - 1,
+    extended: Some(extended),
+    complete: complete,
+    span: start..end
+  })
+}
+
+/// Read hours, minutes and seconds, the last of them present with an
+/// optional decimal fraction.
+fn time(sc: &mut Scanner) -> Result<Part<Time>, DateTimeParseError> {
+  let run = sc.digits();
+  let start = run.start;
+  let mut fields = vec![];
+  let extended = match run.len() {
+    // hh:mm[:ss]
+    2 if sc.peek() == Some(b':') => {
+      fields.push(run);
+      sc.expect(b':')?;
+      fields.push(sc.number(2, 2)?.1);
+      if sc.peek() == Some(b':') {
+        sc.expect(b':')?;
+        fields.push(sc.number(2, 2)?.1);
+      }
+      Some(true)
+    }
+    // hh
+    2 => {
+      fields.push(run);
+      None
+    }
+    // hhmm[ss]
+    4 | 6 => {
+      let mut i = run.start;
+      while i < run.end {
+        fields.push(i..i + 2);
+        i += 2;
+      }
+      Some(false)
+    }
+    _ => return Err(bad_run(sc, run))
+  };
+
+  let limits = [24, 59, 60];
+  let units = [USECS_PER_HOUR, USECS_PER_MINUTE, USECS_PER_SEC];
+  let mut time = 0;
+  for (i, span) in fields.iter().enumerate() {
+    let val = sc.value(span.clone());
+    if val > limits[i] {
+      return Err(sc.error(DateTimeErrorKind::FieldOverflow, span.clone()));
+    }
+    time += val as i64 * units[i];
+  }
+
+  // the fraction is of the last component, rounded to microseconds
+  let mut end = fields[fields.len() - 1].end;
+  match sc.peek() {
+    Some(c) if c == b'.' || c == b',' => {
+      sc.expect(c)?;
+      let (_, span) = sc.number(1, 9)?;
+      let unit = units[fields.len() - 1];
+      time += parse_fraction_of(sc.bytes(span.clone()), unit, Rounding::HalfUp).unwrap();
+      end = span.end;
+    }
+    _ => {}
+  }
+
+  // nothing may follow the end of the day
+  if time > USECS_PER_DAY {
+    return Err(sc.error(DateTimeErrorKind::FieldOverflow, start..end));
+  }
+
+  Ok(Part {
+    value: time,
+    extended: extended,
+    span: start..end
+  })
+}
+
+/// Read a UTC designator or offset, if there is one.
+fn zone(sc: &mut Scanner) -> Result<Option<Part<i32>>, DateTimeParseError> {
+  let start = sc.offset();
+  let sign = match sc.peek() {
+    Some(b'Z') => {
+      sc.expect(b'Z')?;
+      return Ok(Some(Part {
+        value: 0,
+        extended: None,
+        span: start..start + 1
+      }));
+    }
+    Some(c) if c == b'+' || c == b'-' => c,
+    _ => return Ok(None)
+  };
+  sc.expect(sign)?;
+
+  let run = sc.digits();
+  let (hour, min, extended) = match run.len() {
+    // +hh:mm
+    2 if sc.peek() == Some(b':') => {
+      sc.expect(b':')?;
+      (run, Some(sc.number(2, 2)?.1), Some(true))
+    }
+    // +hh
+    2 => (run, None, None),
+    // +hhmm
+    4 => (run.start..run.start + 2, Some(run.start + 2..run.end), Some(false)),
+    _ => return Err(bad_run(sc, run))
+  };
+  let span = start..sc.offset();
+
+  let hour = sc.value(hour);
+  let min = match min {
+    Some(min) => sc.value(min),
+    None => 0
+  };
+  if hour > MAX_TZDISP_HOUR || min > 59 {
+    return Err(sc.error(DateTimeErrorKind::TzDispOverflow, span));
+  }
+
+  let east = (hour * 60 + min) * 60;
+  Ok(Some(Part {
+    value: if sign == b'-' { east } else { -east },
+    extended: extended,
+    span: span
+  }))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::time2t;
+  use super::super::rfc2822::test_helpers::{error_at, ts};
+  use super::super::DateTimeErrorKind::*;
+
+  fn date(y: i32, m: i32, d: i32) -> Date {
+    date2j(y, m, d) - POSTGRES_EPOCH_JDATE
+  }
+
+  #[test]
+  fn test_decode_date() {
+    let valid = [
+      // calendar dates
+      ("2016-11-11", date(2016, 11, 11)),
+      ("20161111", date(2016, 11, 11)),
+      ("2016-02-29", date(2016, 2, 29)),
+      ("0000-01-01", date(0, 1, 1)),
+      ("9999-12-31", date(9999, 12, 31)),
+      ("2016-11", date(2016, 11, 1)),
+      ("2016", date(2016, 1, 1)),
+      // ordinal dates
+      ("2016-316", date(2016, 11, 11)),
+      ("2016316", date(2016, 11, 11)),
+      ("2016-366", date(2016, 12, 31)),
+      ("2015-001", date(2015, 1, 1)),
+      // week dates
+      ("2016-W45-5", date(2016, 11, 11)),
+      ("2016W455", date(2016, 11, 11)),
+      ("2016-W45", date(2016, 11, 7)),
+      ("2016W45", date(2016, 11, 7)),
+      ("2009-W01-1", date(2008, 12, 29)),
+      ("2009-W53-7", date(2010, 1, 3)),
+      ("2015-W53-5", date(2016, 1, 1))
+    ];
+    for &(s, d) in valid.iter() {
+      assert_eq!(d, decode_date(s).ok().unwrap(), "{}", s);
+    }
+
+    let invalid = [
+      ("201611", BadFormat, "201611"),
+      ("16-11-11", BadFormat, "16"),
+      ("2016-1111", BadFormat, "1111"),
+      ("2016-11-1", BadFormat, "1"),
+      ("2016-11-11T", BadFormat, "T"),
+      ("2016/11/11", BadFormat, "/"),
+      ("2016-", BadFormat, ""),
+      ("2016-w45-5", BadFormat, "w"),
+      ("2016W45-5", BadFormat, "-"),
+      ("2016-W455", BadFormat, "455"),
+      ("+2016-11-11", BadFormat, "+"),
+      ("2016-13-01", FieldOverflow, "13"),
+      ("2016-00", FieldOverflow, "00"),
+      ("2015-02-29", FieldOverflow, "29"),
+      ("2015-366", FieldOverflow, "366"),
+      ("2016-000", FieldOverflow, "000"),
+      ("2016-W53", FieldOverflow, "53"),
+      ("2016-W00", FieldOverflow, "00"),
+      ("2016-W45-8", FieldOverflow, "8"),
+      ("2016W450", FieldOverflow, "0")
+    ];
+    for &(s, kind, span) in invalid.iter() {
+      assert_eq!((kind, span.to_owned()), error_at(decode_date(s)), "{}", s);
+    }
+  }
+
+  #[test]
+  fn test_decode_time() {
+    let h = USECS_PER_HOUR;
+    let valid = [
+      ("10:30:15", time2t(10, 30, 15, 0), None),
+      ("103015", time2t(10, 30, 15, 0), None),
+      ("10:30", time2t(10, 30, 0, 0), None),
+      ("1030", time2t(10, 30, 0, 0), None),
+      ("10", 10 * h, None),
+      ("T10:30", time2t(10, 30, 0, 0), None),
+      ("T1030", time2t(10, 30, 0, 0), None),
+      ("10:30:15.5", time2t(10, 30, 15, 500000), None),
+      ("10:30:15,5", time2t(10, 30, 15, 500000), None),
+      ("103015.123456789", time2t(10, 30, 15, 123457), None),
+      ("10:30.5", time2t(10, 30, 30, 0), None),
+      ("10,25", time2t(10, 15, 0, 0), None),
+      ("10.5", time2t(10, 30, 0, 0), None),
+      ("10.000000001", 10 * h + 4, None),
+      ("10:30.333333333", time2t(10, 30, 20, 0), None),
+      ("10:30:15.0000005", time2t(10, 30, 15, 1), None),
+      ("24:00", USECS_PER_DAY, None),
+      ("24:00:00.0", USECS_PER_DAY, None),
+      ("23:59:60", USECS_PER_DAY, None),
+      ("10:30Z", time2t(10, 30, 0, 0), Some(0)),
+      ("10:30+09:00", time2t(10, 30, 0, 0), Some(-9 * 3600)),
+      ("1030+0900", time2t(10, 30, 0, 0), Some(-9 * 3600)),
+      ("10:30-05", time2t(10, 30, 0, 0), Some(5 * 3600)),
+      ("1030-0530", time2t(10, 30, 0, 0), Some(19800)),
+      ("10Z", 10 * h, Some(0))
+    ];
+    for &(s, t, zone) in valid.iter() {
+      assert_eq!((t, zone), decode_time(s).ok().unwrap(), "{}", s);
+    }
+
+    let invalid = [
+      ("1", BadFormat, "1"),
+      ("10301", BadFormat, "10301"),
+      ("10:3", BadFormat, "3"),
+      ("10:30:1", BadFormat, "1"),
+      ("10:3015", BadFormat, "3015"),
+      ("10.", BadFormat, ""),
+      ("10:30.1234567890", BadFormat, "1234567890"),
+      ("10:30 Z", BadFormat, " "),
+      ("10:30z", BadFormat, "z"),
+      ("10:30+9", BadFormat, "9"),
+      ("10:30+09:0", BadFormat, "0"),
+      ("10:30+090", BadFormat, "090"),
+      ("10:30+0900", BadFormat, "+0900"),
+      ("1030+09:00", BadFormat, "+09:00"),
+      ("t10:30", BadFormat, "t"),
+      ("25:00", FieldOverflow, "25"),
+      ("10:60", FieldOverflow, "60"),
+      ("10:30:61", FieldOverflow, "61"),
+      ("24:00:01", FieldOverflow, "24:00:01"),
+      ("24.5", FieldOverflow, "24.5"),
+      ("10:30+16:00", TzDispOverflow, "+16:00"),
+      ("10:30+09:60", TzDispOverflow, "+09:60")
+    ];
+    for &(s, kind, span) in invalid.iter() {
+      assert_eq!((kind, span.to_owned()), error_at(decode_time(s)), "{}", s);
+    }
+  }
+
+  #[test]
+  fn test_decode() {
+    let t = ts(2016, 11, 11, 10, 30, 0);
+    let valid = [
+      ("2016-11-11T10:30:00", t, None),
+      ("20161111T103000", t, None),
+      ("20161111T1030Z", t, Some(0)),
+      ("2016-11-11T10:30+09:00", t, Some(-9 * 3600)),
+      ("2016-11-11T10.5", t, None),
+      ("20161111T10,5-05", t, Some(5 * 3600)),
+      ("2016-316T10:30", t, None),
+      ("2016316T1030", t, None),
+      ("2016-W45-5T10:30", t, None),
+      ("2016W455T1030", t, None),
+      ("2016-11-11T10:30:00.25Z", t + 250000, Some(0)),
+      ("2016-11-11T24:00", ts(2016, 11, 12, 0, 0, 0), None),
+      ("2016-12-31T24:00:00Z", ts(2017, 1, 1, 0, 0, 0), Some(0))
+    ];
+    for &(s, t, zone) in valid.iter() {
+      assert_eq!((t, zone), decode(s).ok().unwrap(), "{}", s);
+    }
+
+    let invalid = [
+      ("2016-11-11", BadFormat, ""),
+      ("2016-11-11 10:30", BadFormat, " "),
+      ("2016-11-11t10:30", BadFormat, "t"),
+      ("2016-11T10:30", BadFormat, "2016-11"),
+      ("2016T10", BadFormat, "2016"),
+      ("2016-W45T10:30", BadFormat, "2016-W45"),
+      ("2016-11-11T", BadFormat, ""),
+      ("2016-11-11T1030", BadFormat, "1030"),
+      ("20161111T10:30", BadFormat, "10:30"),
+      ("2016-11-11T10+0900", BadFormat, "+0900"),
+      ("20161111T10+09:00", BadFormat, "+09:00"),
+      ("2016-11-11T10:30Zx", BadFormat, "x"),
+      ("2016-11-31T10:30", FieldOverflow, "31"),
+      ("2016-11-11T24:30", FieldOverflow, "24:30")
+    ];
+    for &(s, kind, span) in invalid.iter() {
+      assert_eq!((kind, span.to_owned()), error_at(decode(s)), "{}", s);
+    }
+
+    let e = decode("2016-11T10:30").err().unwrap();
+    assert_eq!("invalid input syntax for type timestamp: '2016-11T10:30'", e.to_string());
+  }
+}
@@ -0,0 +1,492 @@
+//! Vectorized kernels over columns of dates and timestamps.
+//!
+//! Every kernel reads an input slice and writes one result per row into an
+//! output slice of the same length. The hot loops have no early exits, so
+//! LLVM can vectorize the Julian day math in `date2j`/`j2date`/`j2day`.
+//!
+//! The `_nullable` variants take a validity slice where `true` means the row
+//! is present. Null rows are not evaluated, and their output is set to zero.
+//!
+//! Infinite dates and timestamps pass through unchanged: field extraction
+//! returns `DATEVAL_NOBEGIN`/`DATEVAL_NOEND` for them, and arithmetic leaves
+//! them as they are.
+//!
+//! Kernels that can meet a value out of range return a `RangeError` for the
+//! first such row. For field extraction, that is a finite date whose Julian
+//! day does not fit an i32, as with `date_out`. Truncation and casts reject
+//! finite timestamps outside `IS_VALID_TIMESTAMP`, and arithmetic rejects
+//! results outside it, as `timestamp_pl_interval` does.
+
+use std::fmt;
+
+use super::{Date, Interval, Timestamp, DATEVAL_NOBEGIN, DATEVAL_NOEND, POSTGRES_EPOCH_JDATE,
+            USECS_PER_DAY};
+use super::{date_is_nobegin, date_not_finite, interval_not_finite, j2date, j2day,
+            timestamp_is_nobegin, timestamp_is_noend, timestamp_not_finite,
+            timestamp_pl_interval, timestamp_mi_interval, IS_VALID_TIMESTAMP};
+
+/// Returned when the result for a row can't be represented.
+/// The error carries the index of the first such row.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RangeError {
+  pub row: usize
+}
+
+impl fmt::Display for RangeError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "value out of range at row {}", self.row)
+  }
+}
+
+// ---------------------------------------------------------------------------
+// Field extraction
+// ---------------------------------------------------------------------------
+
+#[inline(always)]
+fn infinite_field(d: Date) -> i32 {
+  if date_is_nobegin(d) { DATEVAL_NOBEGIN } else { DATEVAL_NOEND }
+}
+
+/// Apply f to the Julian day of every present, finite row. A Julian day
+/// that overflows an i32 is flagged rather than branched on, as in
+/// add_offset(), and reported as the first such row.
+#[inline(always)]
+fn map_julian<F>(input: &[Date], valid: Option<&[bool]>, out: &mut [i32], f: F)
+    -> Result<(), RangeError> where F: Fn(i32) -> i32 {
+  assert_eq!(input.len(), out.len());
+  if let Some(v) = valid {
+    assert_eq!(input.len(), v.len());
+  }
+  let mut overflow = false;
+
+  for (i, (o, &d)) in out.iter_mut().zip(input).enumerate() {
+    let present = valid.map_or(true, |v| v[i]);
+    let finite = !date_not_finite(d);
+    let (julian, of) = d.overflowing_add(POSTGRES_EPOCH_JDATE);
+
+    overflow |= present & finite & of;
+    *o = if !present { 0 } else if finite { f(julian) } else { infinite_field(d) };
+  }
+
+  if !overflow {
+    return Ok(());
+  }
+
+  // slow path: locate the offending row
+  for (i, &d) in input.iter().enumerate() {
+    if valid.map_or(true, |v| v[i]) && !date_not_finite(d) &&
+        d.checked_add(POSTGRES_EPOCH_JDATE).is_none() {
+      return Err(RangeError { row: i });
+    }
+  }
+
+  unreachable!()
+}
+
+/// There is no year 0, so years <= 0 are reported as BC (e.g. -1 for 1 BC).
+#[inline(always)]
+fn sql_year(y: i32) -> i32 {
+  if y > 0 { y } else { y - 1 }
+}
+
+fn year(julian: i32) -> i32 {
+  sql_year(j2date(julian).0)
+}
+
+fn month(julian: i32) -> i32 {
+  j2date(julian).1 as i32
+}
+
+fn day(julian: i32) -> i32 {
+  j2date(julian).2 as i32
+}
+
+/// extract(year from date)
+pub fn extract_year(input: &[Date], out: &mut [i32]) -> Result<(), RangeError> {
+  map_julian(input, None, out, year)
+}
+
+pub fn extract_year_nullable(input: &[Date], valid: &[bool], out: &mut [i32])
+    -> Result<(), RangeError> {
+  map_julian(input, Some(valid), out, year)
+}
+
+/// extract(month from date), 1..12
+pub fn extract_month(input: &[Date], out: &mut [i32]) -> Result<(), RangeError> {
+  map_julian(input, None, out, month)
+}
+
+pub fn extract_month_nullable(input: &[Date], valid: &[bool], out: &mut [i32])
+    -> Result<(), RangeError> {
+  map_julian(input, Some(valid), out, month)
+}
+
+/// extract(day from date), 1..31
+pub fn extract_day(input: &[Date], out: &mut [i32]) -> Result<(), RangeError> {
+  map_julian(input, None, out, day)
+}
+
+pub fn extract_day_nullable(input: &[Date], valid: &[bool], out: &mut [i32])
+    -> Result<(), RangeError> {
+  map_julian(input, Some(valid), out, day)
+}
+
+/// extract(dow from date), 0..6 == Sun..Sat; j2day() over a column.
+pub fn extract_dow(input: &[Date], out: &mut [i32]) -> Result<(), RangeError> {
+  map_julian(input, None, out, j2day)
+}
+
+pub fn extract_dow_nullable(input: &[Date], valid: &[bool], out: &mut [i32])
+    -> Result<(), RangeError> {
+  map_julian(input, Some(valid), out, j2day)
+}
+
+// ---------------------------------------------------------------------------
+// Truncation and casts
+// ---------------------------------------------------------------------------
+
+/// Apply f to every present row that is infinite or within
+/// IS_VALID_TIMESTAMP. A finite timestamp out of range is flagged, as in
+/// map_julian(), and reported as the first such row.
+#[inline(always)]
+fn map_timestamp<T, F>(input: &[Timestamp], valid: Option<&[bool]>, out: &mut [T], f: F)
+    -> Result<(), RangeError> where T: Copy + Default, F: Fn(Timestamp) -> T {
+  assert_eq!(input.len(), out.len());
+  if let Some(v) = valid {
+    assert_eq!(input.len(), v.len());
+  }
+  let mut overflow = false;
+
+  for (i, (o, &ts)) in out.iter_mut().zip(input).enumerate() {
+    let present = valid.map_or(true, |v| v[i]);
+    let in_range = timestamp_not_finite(ts) | IS_VALID_TIMESTAMP(ts);
+
+    overflow |= present & !in_range;
+    *o = if present & in_range { f(ts) } else { T::default() };
+  }
+
+  if !overflow {
+    return Ok(());
+  }
+
+  // slow path: locate the offending row
+  for (i, &ts) in input.iter().enumerate() {
+    if valid.map_or(true, |v| v[i]) && !timestamp_not_finite(ts) && !IS_VALID_TIMESTAMP(ts) {
+      return Err(RangeError { row: i });
+    }
+  }
+
+  unreachable!()
+}
+
+/// The start of the day of ts, which must be infinite or within
+/// IS_VALID_TIMESTAMP so that the floor cannot overflow.
+#[inline(always)]
+fn trunc_day(ts: Timestamp) -> Timestamp {
+  if timestamp_not_finite(ts) {
+    return ts;
+  }
+
+  let rem = ts % USECS_PER_DAY;
+  let floor = ts - rem;
+  if rem < 0 { floor - USECS_PER_DAY } else { floor }
+}
+
+/// date_trunc('day', timestamp)
+pub fn date_trunc_day(input: &[Timestamp], out: &mut [Timestamp]) -> Result<(), RangeError> {
+  map_timestamp(input, None, out, trunc_day)
+}
+
+pub fn date_trunc_day_nullable(input: &[Timestamp], valid: &[bool], out: &mut [Timestamp])
+    -> Result<(), RangeError> {
+  map_timestamp(input, Some(valid), out, trunc_day)
+}
+
+#[inline(always)]
+fn timestamp_date(ts: Timestamp) -> Date {
+  if timestamp_is_nobegin(ts) {
+    DATEVAL_NOBEGIN
+  } else if timestamp_is_noend(ts) {
+    DATEVAL_NOEND
+  } else {
+    (trunc_day(ts) / USECS_PER_DAY) as Date
+  }
+}
+
+/// timestamp::date
+pub fn timestamp_to_date(input: &[Timestamp], out: &mut [Date]) -> Result<(), RangeError> {
+  map_timestamp(input, None, out, timestamp_date)
+}
+
+pub fn timestamp_to_date_nullable(input: &[Timestamp], valid: &[bool], out: &mut [Date])
+    -> Result<(), RangeError> {
+  map_timestamp(input, Some(valid), out, timestamp_date)
+}
+
+// ---------------------------------------------------------------------------
+// Interval arithmetic
+// ---------------------------------------------------------------------------
+
+/// Add a fixed number of microseconds to every finite row. A sum that
+/// overflows or falls outside IS_VALID_TIMESTAMP is accumulated into a
+/// flag instead of branching out of the loop.
+#[inline(always)]
+fn add_offset(input: &[Timestamp], valid: Option<&[bool]>, delta: i64, out: &mut [Timestamp])
+    -> Result<(), RangeError> {
+  let mut overflow = false;
+
+  for (i, (o, &ts)) in out.iter_mut().zip(input).enumerate() {
+    let present = valid.map_or(true, |v| v[i]);
+    let (sum, of) = ts.overflowing_add(delta);
+    let finite = !timestamp_not_finite(ts);
+
+    overflow |= present & finite & (of | !IS_VALID_TIMESTAMP(sum));
+    *o = if !present { 0 } else if finite { sum } else { ts };
+  }
+
+  if !overflow {
+    return Ok(());
+  }
+
+  // slow path: locate the offending row
+  for (i, &ts) in input.iter().enumerate() {
+    if valid.map_or(false, |v| !v[i]) || timestamp_not_finite(ts) {
+      continue;
+    }
+    match ts.checked_add(delta) {
+      Some(t) if IS_VALID_TIMESTAMP(t) => {}
+      _ => return Err(RangeError { row: i })
+    }
+  }
+
+  unreachable!()
+}
+
+fn apply<F>(input: &[Timestamp], valid: Option<&[bool]>, out: &mut [Timestamp], f: F)
+    -> Result<(), RangeError> where F: Fn(Timestamp) -> Option<Timestamp> {
+  for (i, (o, &ts)) in out.iter_mut().zip(input).enumerate() {
+    if valid.map_or(false, |v| !v[i]) {
+      *o = 0;
+      continue;
+    }
+    *o = match f(ts) {
+      Some(t) => t,
+      None => return Err(RangeError { row: i })
+    };
+  }
+
+  Ok(())
+}
+
+fn pl_interval(input: &[Timestamp], valid: Option<&[bool]>, span: &Interval,
+    out: &mut [Timestamp]) -> Result<(), RangeError> {
+  assert_eq!(input.len(), out.len());
+  if let Some(v) = valid {
+    assert_eq!(input.len(), v.len());
+  }
+
+  // Without a month part, an interval is a fixed offset for timestamps
+  // without time zone, so the whole column is a single vector add.
+  if span.month == 0 {
+    if let Some(delta) = (span.day as i64).checked_mul(USECS_PER_DAY)
+        .and_then(|d| d.checked_add(span.time)) {
+      return add_offset(input, valid, delta, out);
+    }
+  }
+
+  apply(input, valid, out, |ts| timestamp_pl_interval(ts, span))
+}
+
+/// timestamp + interval
+pub fn add_interval(input: &[Timestamp], span: Interval, out: &mut [Timestamp])
+    -> Result<(), RangeError> {
+  pl_interval(input, None, &span, out)
+}
+
+pub fn add_interval_nullable(input: &[Timestamp], valid: &[bool], span: Interval,
+    out: &mut [Timestamp]) -> Result<(), RangeError> {
+  pl_interval(input, Some(valid), &span, out)
+}
+
+/// timestamp - interval
+pub fn sub_interval(input: &[Timestamp], span: Interval, out: &mut [Timestamp])
+    -> Result<(), RangeError> {
+  match (span.month.checked_neg(), span.day.checked_neg(), span.time.checked_neg()) {
+    (Some(month), Some(day), Some(time)) if !interval_not_finite(&span) => {
+      pl_interval(input, None, &Interval::new(month, day, time), out)
+    }
+    _ => {
+      assert_eq!(input.len(), out.len());
+      apply(input, None, out, |ts| timestamp_mi_interval(ts, &span))
+    }
+  }
+}
+
+pub fn sub_interval_nullable(input: &[Timestamp], valid: &[bool], span: Interval,
+    out: &mut [Timestamp]) -> Result<(), RangeError> {
+  match (span.month.checked_neg(), span.day.checked_neg(), span.time.checked_neg()) {
+    (Some(month), Some(day), Some(time)) if !interval_not_finite(&span) => {
+      pl_interval(input, Some(valid), &Interval::new(month, day, time), out)
+    }
+    _ => {
+      assert_eq!(input.len(), out.len());
+      assert_eq!(input.len(), valid.len());
+      apply(input, Some(valid), out, |ts| timestamp_mi_interval(ts, &span))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::{date2j, DT_NOBEGIN, DT_NOEND, INTERVAL_NOEND, MAX_TIMESTAMP, MIN_TIMESTAMP,
+                     USECS_PER_HOUR};
+
+  fn date(y: i32, m: i32, d: i32) -> Date {
+    date2j(y, m, d) - POSTGRES_EPOCH_JDATE
+  }
+
+  fn ts(y: i32, m: i32, d: i32, hour: i64) -> Timestamp {
+    date(y, m, d) as i64 * USECS_PER_DAY + hour * USECS_PER_HOUR
+  }
+
+  #[test]
+  fn test_extract() {
+    let dates = [date(2016, 11, 11), date(1999, 12, 31), date(0, 6, 1), DATEVAL_NOEND];
+    let mut out = [0; 4];
+
+    extract_year(&dates, &mut out).unwrap();
+    assert_eq!([2016, 1999, -1, DATEVAL_NOEND], out);
+    extract_month(&dates, &mut out).unwrap();
+    assert_eq!([11, 12, 6, DATEVAL_NOEND], out);
+    extract_day(&dates, &mut out).unwrap();
+    assert_eq!([11, 31, 1, DATEVAL_NOEND], out);
+    extract_dow(&dates, &mut out).unwrap();
+    assert_eq!([5, 5, 4, DATEVAL_NOEND], out);
+
+    extract_year_nullable(&dates, &[true, false, true, false], &mut out).unwrap();
+    assert_eq!([2016, 0, -1, 0], out);
+  }
+
+  #[test]
+  fn test_extract_overflow() {
+    // the Julian day of the last finite dates overflows an i32
+    let dates = [0, DATEVAL_NOEND - 1, DATEVAL_NOEND];
+    let mut out = [0; 3];
+
+    assert_eq!(Err(RangeError { row: 1 }), extract_year(&dates, &mut out));
+    assert_eq!(Err(RangeError { row: 1 }), extract_dow(&dates, &mut out));
+    assert_eq!(Err(RangeError { row: 1 }),
+               extract_day_nullable(&dates, &[true, true, false], &mut out));
+    extract_month_nullable(&dates, &[true, false, true], &mut out).unwrap();
+    assert_eq!([1, 0, DATEVAL_NOEND], out);
+
+    // days before the Julian epoch are fine
+    let early = [0, -POSTGRES_EPOCH_JDATE - 1];
+    extract_year(&early, &mut out[..2]).unwrap();
+    assert_eq!([2000, -4714], out[..2]);
+  }
+
+  #[test]
+  fn test_date_trunc_day() {
+    let input = [ts(2016, 11, 11, 10), ts(1999, 12, 31, 23), ts(1970, 1, 1, 0) - 1, DT_NOEND];
+    let mut out = [0; 4];
+
+    date_trunc_day(&input, &mut out).unwrap();
+    assert_eq!([ts(2016, 11, 11, 0), ts(1999, 12, 31, 0), ts(1969, 12, 31, 0), DT_NOEND], out);
+
+    let mut dates = [0; 4];
+    timestamp_to_date(&input, &mut dates).unwrap();
+    assert_eq!([date(2016, 11, 11), date(1999, 12, 31), date(1969, 12, 31), DATEVAL_NOEND],
+               dates);
+
+    date_trunc_day_nullable(&input, &[true, false, true, true], &mut out).unwrap();
+    assert_eq!([ts(2016, 11, 11, 0), 0, ts(1969, 12, 31, 0), DT_NOEND], out);
+    timestamp_to_date_nullable(&input, &[false, true, true, false], &mut dates).unwrap();
+    assert_eq!([0, date(1999, 12, 31), date(1969, 12, 31), 0], dates);
+  }
+
+  #[test]
+  fn test_date_trunc_day_range() {
+    let input = [MIN_TIMESTAMP, MAX_TIMESTAMP, DT_NOBEGIN, DT_NOEND];
+    let mut out = [0; 4];
+    let mut dates = [0; 4];
+
+    date_trunc_day(&input, &mut out).unwrap();
+    assert_eq!([MIN_TIMESTAMP, MAX_TIMESTAMP + 1 - USECS_PER_DAY, DT_NOBEGIN, DT_NOEND], out);
+    timestamp_to_date(&input, &mut dates).unwrap();
+    assert_eq!([date(-4713, 11, 24), date(294276, 12, 31), DATEVAL_NOBEGIN, DATEVAL_NOEND],
+               dates);
+
+    // finite timestamps past the limits, where the floor would overflow
+    let input = [0, DT_NOBEGIN + 1, MIN_TIMESTAMP - 1, DT_NOEND - 1];
+    assert_eq!(Err(RangeError { row: 1 }), date_trunc_day(&input, &mut out));
+    assert_eq!(Err(RangeError { row: 1 }), timestamp_to_date(&input, &mut dates));
+    assert_eq!(Err(RangeError { row: 2 }),
+               date_trunc_day_nullable(&input, &[true, false, true, true], &mut out));
+    assert_eq!(Err(RangeError { row: 3 }),
+               timestamp_to_date_nullable(&input, &[true, false, false, true], &mut dates));
+    timestamp_to_date_nullable(&input, &[true, false, false, false], &mut dates).unwrap();
+    assert_eq!([date(2000, 1, 1), 0, 0, 0], dates);
+  }
+
+  #[test]
+  fn test_add_interval() {
+    let input = [ts(2016, 1, 31, 10), ts(2016, 11, 11, 0), DT_NOEND];
+    let mut out = [0; 3];
+
+    add_interval(&input, Interval::new(0, 1, 2 * USECS_PER_HOUR), &mut out).unwrap();
+    assert_eq!([ts(2016, 2, 1, 12), ts(2016, 11, 12, 2), DT_NOEND], out);
+
+    add_interval(&input, Interval::new(1, 0, 0), &mut out).unwrap();
+    assert_eq!([ts(2016, 2, 29, 10), ts(2016, 12, 11, 0), DT_NOEND], out);
+
+    sub_interval(&input, Interval::new(13, 0, 0), &mut out).unwrap();
+    assert_eq!([ts(2014, 12, 31, 10), ts(2015, 10, 11, 0), DT_NOEND], out);
+
+    add_interval_nullable(&input, &[false, true, true], Interval::new(0, 0, 1), &mut out)
+      .unwrap();
+    assert_eq!([0, ts(2016, 11, 11, 0) + 1, DT_NOEND], out);
+
+    add_interval(&input, INTERVAL_NOEND, &mut out).unwrap();
+    assert_eq!([DT_NOEND, DT_NOEND, DT_NOEND], out);
+    sub_interval(&input[..2], INTERVAL_NOEND, &mut out[..2]).unwrap();
+    assert_eq!([DT_NOBEGIN, DT_NOBEGIN], out[..2]);
+    // infinity - infinity is undefined
+    assert_eq!(Err(RangeError { row: 2 }), sub_interval(&input, INTERVAL_NOEND, &mut out));
+  }
+
+  #[test]
+  fn test_add_interval_overflow() {
+    let input = [0, ::std::i64::MAX - 10, 0];
+    let mut out = [0; 3];
+
+    assert_eq!(Err(RangeError { row: 1 }), add_interval(&input, Interval::new(0, 0, 10), &mut out));
+    assert!(add_interval_nullable(&input, &[true, false, true], Interval::new(0, 0, 10), &mut out)
+      .is_ok());
+  }
+
+  #[test]
+  fn test_add_interval_range() {
+    let input = [0, MAX_TIMESTAMP, MIN_TIMESTAMP];
+    let mut out = [0; 3];
+    let day = Interval::new(0, 1, 0);
+
+    // the sums fit an i64 but are past the last and first valid timestamps
+    assert_eq!(Err(RangeError { row: 1 }), add_interval(&input, day, &mut out));
+    assert_eq!(Err(RangeError { row: 2 }), sub_interval(&input, day, &mut out));
+    assert_eq!(Err(RangeError { row: 1 }), add_interval(&input, Interval::new(0, 0, 1), &mut out));
+    assert_eq!(Err(RangeError { row: 2 }),
+               add_interval_nullable(&input, &[true, false, true], Interval::new(0, 0, -1),
+                                     &mut out));
+    assert_eq!(Err(RangeError { row: 1 }), add_interval(&input, Interval::new(1, 0, 0), &mut out));
+
+    add_interval(&input[..2], Interval::new(0, 0, 0), &mut out[..2]).unwrap();
+    assert_eq!([0, MAX_TIMESTAMP], out[..2]);
+    assert_eq!(Err(RangeError { row: 2 }),
+               sub_interval_nullable(&input, &[true, false, true], day, &mut out));
+    add_interval_nullable(&input, &[true, true, false], Interval::new(0, -1, 0), &mut out)
+      .unwrap();
+    assert_eq!([-USECS_PER_DAY, MAX_TIMESTAMP - USECS_PER_DAY, 0], out);
+  }
+}
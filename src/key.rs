@@ -0,0 +1,234 @@
+//! Order-preserving (memcomparable) byte encoding of date/time values.
+//!
+//! Comparing two encoded keys with `memcmp` gives the same result as
+//! comparing the values. Dates (`i32`) and times/timestamps (`i64`) are stored
+//! big-endian with the sign bit flipped. Negative Julian-relative days, BC
+//! dates and the infinity sentinels need no special handling because
+//! infinities are already the extreme values of the type.
+//!
+//! Intervals compare like PostgreSQL, by their total span with a month
+//! counted as 30 days, so '1 mon' and '30 days' are equal and get equal keys.
+//! The key is only that span as a 128-bit integer. Decoding gives back the
+//! justified interval, with the span spread over months, days and time as
+//! `justify_interval` would.
+//!
+//! Descending keys are the bitwise complement of ascending keys.
+
+use super::{Interval, DAYS_PER_MONTH, USECS_PER_DAY};
+
+pub trait MemComparable: Sized {
+  /// Append an ascending key for this value.
+  fn encode_key(&self, buf: &mut Vec<u8>);
+
+  /// Decode an ascending key, returning the value and the remaining bytes.
+  fn decode_key(buf: &[u8]) -> Option<(Self, &[u8])>;
+
+  /// Append a descending key for this value.
+  fn encode_key_desc(&self, buf: &mut Vec<u8>) {
+    let start = buf.len();
+    self.encode_key(buf);
+    for b in &mut buf[start..] {
+      *b = !*b;
+    }
+  }
+
+  /// Decode a descending key, returning the value and the remaining bytes.
+  fn decode_key_desc(buf: &[u8]) -> Option<(Self, &[u8])> {
+    let len = Self::key_len();
+    if buf.len() < len {
+      return None;
+    }
+    let flipped: Vec<u8> = buf[..len].iter().map(|b| !b).collect();
+    match Self::decode_key(&flipped) {
+      Some((v, _)) => Some((v, &buf[len..])),
+      None => None
+    }
+  }
+
+  /// The size of an encoded key; every key of a type has the same size.
+  fn key_len() -> usize;
+}
+
+/// Date
+impl MemComparable for i32 {
+  fn encode_key(&self, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&((*self as u32) ^ (1 << 31)).to_be_bytes());
+  }
+
+  fn decode_key(buf: &[u8]) -> Option<(i32, &[u8])> {
+    if buf.len() < 4 {
+      return None;
+    }
+    let v = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) ^ (1 << 31);
+    Some((v as i32, &buf[4..]))
+  }
+
+  fn key_len() -> usize {
+    4
+  }
+}
+
+/// Time, Timestamp and TimestampTz
+impl MemComparable for i64 {
+  fn encode_key(&self, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&((*self as u64) ^ (1 << 63)).to_be_bytes());
+  }
+
+  fn decode_key(buf: &[u8]) -> Option<(i64, &[u8])> {
+    if buf.len() < 8 {
+      return None;
+    }
+    let mut b = [0u8; 8];
+    b.copy_from_slice(&buf[..8]);
+    Some(((u64::from_be_bytes(b) ^ (1 << 63)) as i64, &buf[8..]))
+  }
+
+  fn key_len() -> usize {
+    8
+  }
+}
+
+/// The span used to order intervals, as in PostgreSQL's interval_cmp_value().
+pub(crate) fn interval_span(span: &Interval) -> i128 {
+  let days = span.month as i128 * DAYS_PER_MONTH as i128 + span.day as i128;
+  days * USECS_PER_DAY as i128 + span.time as i128
+}
+
+/// Take as many whole units of unit as fit in an i32 off rest.
+fn split_units(rest: &mut i128, unit: i128) -> i32 {
+  let n = *rest / unit;
+  let n = if n > ::std::i32::MAX as i128 {
+    ::std::i32::MAX
+  } else if n < ::std::i32::MIN as i128 {
+    ::std::i32::MIN
+  } else {
+    n as i32
+  };
+  *rest -= n as i128 * unit;
+  n
+}
+
+impl MemComparable for Interval {
+  fn encode_key(&self, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&((interval_span(self) as u128) ^ (1 << 127)).to_be_bytes());
+  }
+
+  fn decode_key(buf: &[u8]) -> Option<(Interval, &[u8])> {
+    if buf.len() < 16 {
+      return None;
+    }
+    let mut b = [0u8; 16];
+    b.copy_from_slice(&buf[..16]);
+    let mut span = (u128::from_be_bytes(b) ^ (1 << 127)) as i128;
+
+    let month = split_units(&mut span, DAYS_PER_MONTH as i128 * USECS_PER_DAY as i128);
+    let day = split_units(&mut span, USECS_PER_DAY as i128);
+    if span < ::std::i64::MIN as i128 || span > ::std::i64::MAX as i128 {
+      return None;
+    }
+    Some((Interval::new(month, day, span as i64), &buf[16..]))
+  }
+
+  fn key_len() -> usize {
+    16
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::{date2j, DATEVAL_NOBEGIN, DATEVAL_NOEND, DT_NOBEGIN, DT_NOEND,
+                     INTERVAL_NOBEGIN, INTERVAL_NOEND, POSTGRES_EPOCH_JDATE, USECS_PER_HOUR};
+
+  fn key<T: MemComparable>(v: &T) -> Vec<u8> {
+    let mut buf = Vec::new();
+    v.encode_key(&mut buf);
+    buf
+  }
+
+  fn key_desc<T: MemComparable>(v: &T) -> Vec<u8> {
+    let mut buf = Vec::new();
+    v.encode_key_desc(&mut buf);
+    buf
+  }
+
+  fn assert_sorted<T: MemComparable>(values: &[T]) {
+    for w in values.windows(2) {
+      assert!(key(&w[0]) < key(&w[1]));
+      assert!(key_desc(&w[0]) > key_desc(&w[1]));
+    }
+  }
+
+  #[test]
+  fn test_date_key() {
+    let dates = [DATEVAL_NOBEGIN,
+                 date2j(-4713, 11, 24) - POSTGRES_EPOCH_JDATE,  // BC, Julian day 0
+                 date2j(0, 1, 1) - POSTGRES_EPOCH_JDATE,        // 1 BC
+                 -1, 0, 1,
+                 date2j(2016, 11, 11) - POSTGRES_EPOCH_JDATE,
+                 DATEVAL_NOEND];
+    assert_sorted(&dates);
+
+    for d in dates.iter() {
+      assert_eq!((*d, &[][..]), i32::decode_key(&key(d)).unwrap());
+      assert_eq!((*d, &[][..]), i32::decode_key_desc(&key_desc(d)).unwrap());
+    }
+  }
+
+  #[test]
+  fn test_timestamp_key() {
+    let ts: [i64; 6] = [DT_NOBEGIN, -USECS_PER_DAY, -1, 0, 1, DT_NOEND];
+    assert_sorted(&ts);
+
+    let mut buf = key(&ts[1]);
+    buf.push(0xaa);
+    assert_eq!((ts[1], &[0xaa][..]), i64::decode_key(&buf).unwrap());
+    assert!(i64::decode_key(&buf[..7]).is_none());
+
+    // only the key itself is complemented, not what follows it
+    let mut buf = key_desc(&ts[1]);
+    buf.push(0xaa);
+    assert_eq!((ts[1], &[0xaa][..]), i64::decode_key_desc(&buf).unwrap());
+    assert!(i64::decode_key_desc(&buf[..7]).is_none());
+  }
+
+  #[test]
+  fn test_interval_key() {
+    let spans = [INTERVAL_NOBEGIN,
+                 Interval::new(-1, 0, 0),
+                 Interval::new(0, -1, 1),
+                 Interval::new(0, 0, 0),
+                 Interval::new(0, 29, USECS_PER_DAY - 1),
+                 Interval::new(1, 0, 0),
+                 Interval::new(0, 30, 1),
+                 INTERVAL_NOEND];
+
+    for w in spans.windows(2) {
+      assert!(key(&w[0]) <= key(&w[1]));
+      assert!(key_desc(&w[0]) >= key_desc(&w[1]));
+    }
+
+    for span in spans[1..spans.len() - 1].iter() {
+      assert!(key(span) < key(&INTERVAL_NOEND));
+      assert!(key(span) > key(&INTERVAL_NOBEGIN));
+    }
+
+    // '30 days' == '1 mon' == '720 hours'
+    assert_eq!(key(&Interval::new(1, 0, 0)), key(&Interval::new(0, 30, 0)));
+    assert_eq!(key(&Interval::new(1, 0, 0)), key(&Interval::new(0, 0, 720 * USECS_PER_HOUR)));
+    assert_eq!(key_desc(&Interval::new(1, 0, 0)), key_desc(&Interval::new(0, 30, 0)));
+
+    let k = key(&Interval::new(0, 65, 25 * USECS_PER_HOUR));
+    let (back, rest) = Interval::decode_key(&k).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!((2, 6, USECS_PER_HOUR), (back.month(), back.day(), back.time()));
+
+    let (back, _) = Interval::decode_key_desc(&key_desc(&Interval::new(0, -31, 0))).unwrap();
+    assert_eq!((-1, -1, 0), (back.month(), back.day(), back.time()));
+
+    for span in [INTERVAL_NOBEGIN, INTERVAL_NOEND].iter() {
+      let (back, _) = Interval::decode_key(&key(span)).unwrap();
+      assert_eq!((span.month(), span.day(), span.time()), (back.month(), back.day(), back.time()));
+    }
+  }
+}
@@ -1,14 +1,43 @@
 #![allow(dead_code)]
 #![feature(const_fn)]
 extern crate radish;
-
+#[cfg(feature = "arrow")]
+extern crate arrow_array;
+#[cfg(feature = "arrow")]
+extern crate arrow_buffer;
+#[cfg(feature = "arrow")]
+extern crate arrow_schema;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+
+use std::error::Error;
 use std::fmt;
+use std::ops::Range;
 use std::cmp::Ordering;
 use std::str::{self, FromStr};
 
 use radish::err::ParseNumErr;
 use radish::ascii::{FromBytes, isalnum, isalpha, isdigit, strtod, strtoi, tolower};
 
+use calendar::{Calendar, Gregorian};
+
+pub mod arith;
+pub mod calendar;
+pub mod httpdate;
+pub mod iso8601;
+pub mod kernels;
+pub mod key;
+pub mod nanos;
+pub mod pgwire;
+pub mod rfc2822;
+pub mod rfc3339;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "serde")]
+pub mod serialize;
+
 
 
 // Date Orders
@@ -21,6 +50,36 @@ pub enum DateOrder {
 
 pub static DATE_ORDER: DateOrder = DateOrder::YMD;
 
+// Date Styles (output formats)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DateStyle {
+  /// 1997-12-17 07:37:16-08
+  ISO,
+  /// 12/17/1997 07:37:16.00 PST
+  SQL,
+  /// Wed Dec 17 07:37:16 1997 PST
+  Postgres,
+  /// 17.12.1997 07:37:16.00 PST
+  German,
+  /// 1997-12-17T07:37:16-08:00
+  XSD,
+  /// 1997-351 07:37:16-08 (ISO 8601 ordinal date)
+  Ordinal,
+  /// 1997-W51-3 07:37:16-08 (ISO 8601 week date)
+  IsoWeek,
+  /// J2450800.31755 (Julian day, without time zone)
+  Julian
+}
+
+// Interval Styles (output formats)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum IntervalStyle {
+  /// 1 year 2 mons 3 days 04:05:06
+  Postgres,
+  /// P1Y2M3DT4H5M6S
+  ISO8601
+}
+
 // ---------------------------------------------------------------------------
 // Ported from datetime.h
 // ---------------------------------------------------------------------------
@@ -203,15 +262,153 @@ pub type TimestampTz = i64;
 pub type TimeOffset  = i64;
 pub type FracSec     = i32;
 
+#[derive(Clone, Copy, Debug)]
 pub struct Interval {
   time: TimeOffset,
   day: i32,
   month: i32
 }
 
+impl Interval {
+  pub fn new(month: i32, day: i32, time: TimeOffset) -> Interval {
+    Interval {
+      time: time,
+      day: day,
+      month: month
+    }
+  }
+
+  /// months and years, after time for alignment
+  pub fn month(&self) -> i32 {
+    self.month
+  }
+
+  /// days, after time for alignment
+  pub fn day(&self) -> i32 {
+    self.day
+  }
+
+  /// all time units other than days, months and years
+  pub fn time(&self) -> TimeOffset {
+    self.time
+  }
+}
+
 const MAX_TIMESTAMP_PRECISION :i32 = 6;
 const MAX_INTERVAL_PRECISION  :i32 = 6;
 
+// Interval typmods hold the field range, a mask of the fields present, in
+// the upper half and the seconds precision in the lower half.
+const INTERVAL_FULL_RANGE     :i32 = 0x7FFF;
+const INTERVAL_RANGE_MASK     :i32 = 0x7FFF;
+const INTERVAL_FULL_PRECISION :i32 = 0xFFFF;
+const INTERVAL_PRECISION_MASK :i32 = 0xFFFF;
+
+#[allow(non_snake_case)]
+const fn INTERVAL_MASK(b: i8) -> i32 {
+  (0x01 << b)
+}
+
+#[allow(non_snake_case)]
+const fn INTERVAL_TYPMOD(p: i32, r: i32) -> i32 {
+  ((r & INTERVAL_RANGE_MASK) << 16) | (p & INTERVAL_PRECISION_MASK)
+}
+
+#[allow(non_snake_case)]
+fn INTERVAL_PRECISION(t: i32) -> i32 {
+  t & INTERVAL_PRECISION_MASK
+}
+
+#[allow(non_snake_case)]
+fn INTERVAL_RANGE(t: i32) -> i32 {
+  (t >> 16) & INTERVAL_RANGE_MASK
+}
+
+/// The fields of a qualified interval type, e.g. `interval day to second`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IntervalFields {
+  Year,
+  Month,
+  Day,
+  Hour,
+  Minute,
+  Second,
+  YearToMonth,
+  DayToHour,
+  DayToMinute,
+  DayToSecond,
+  HourToMinute,
+  HourToSecond,
+  MinuteToSecond
+}
+
+impl IntervalFields {
+  /// The range half of the typmod.
+  fn range(&self) -> i32 {
+    match *self {
+      IntervalFields::Year => INTERVAL_MASK(YEAR),
+      IntervalFields::Month => INTERVAL_MASK(MONTH),
+      IntervalFields::Day => INTERVAL_MASK(DAY),
+      IntervalFields::Hour => INTERVAL_MASK(HOUR),
+      IntervalFields::Minute => INTERVAL_MASK(MINUTE),
+      IntervalFields::Second => INTERVAL_MASK(SECOND),
+      IntervalFields::YearToMonth => INTERVAL_MASK(YEAR) | INTERVAL_MASK(MONTH),
+      IntervalFields::DayToHour => INTERVAL_MASK(DAY) | INTERVAL_MASK(HOUR),
+      IntervalFields::DayToMinute => {
+        INTERVAL_MASK(DAY) | INTERVAL_MASK(HOUR) | INTERVAL_MASK(MINUTE)
+      }
+      IntervalFields::DayToSecond => {
+        INTERVAL_MASK(DAY) | INTERVAL_MASK(HOUR) | INTERVAL_MASK(MINUTE) | INTERVAL_MASK(SECOND)
+      }
+      IntervalFields::HourToMinute => INTERVAL_MASK(HOUR) | INTERVAL_MASK(MINUTE),
+      IntervalFields::HourToSecond => {
+        INTERVAL_MASK(HOUR) | INTERVAL_MASK(MINUTE) | INTERVAL_MASK(SECOND)
+      }
+      IntervalFields::MinuteToSecond => INTERVAL_MASK(MINUTE) | INTERVAL_MASK(SECOND)
+    }
+  }
+}
+
+// Infinite timestamps are represented by the extreme values of the type.
+pub const DT_NOBEGIN :Timestamp = ::std::i64::MIN;
+pub const DT_NOEND   :Timestamp = ::std::i64::MAX;
+
+pub fn timestamp_is_nobegin(t: Timestamp) -> bool {
+  t == DT_NOBEGIN
+}
+
+pub fn timestamp_is_noend(t: Timestamp) -> bool {
+  t == DT_NOEND
+}
+
+pub fn timestamp_not_finite(t: Timestamp) -> bool {
+  timestamp_is_nobegin(t) || timestamp_is_noend(t)
+}
+
+// Infinite intervals have every field at the extreme value of its type.
+pub const INTERVAL_NOBEGIN :Interval = Interval {
+  time: ::std::i64::MIN,
+  day: ::std::i32::MIN,
+  month: ::std::i32::MIN
+};
+pub const INTERVAL_NOEND   :Interval = Interval {
+  time: ::std::i64::MAX,
+  day: ::std::i32::MAX,
+  month: ::std::i32::MAX
+};
+
+pub fn interval_is_nobegin(i: &Interval) -> bool {
+  i.time == ::std::i64::MIN && i.day == ::std::i32::MIN && i.month == ::std::i32::MIN
+}
+
+pub fn interval_is_noend(i: &Interval) -> bool {
+  i.time == ::std::i64::MAX && i.day == ::std::i32::MAX && i.month == ::std::i32::MAX
+}
+
+pub fn interval_not_finite(i: &Interval) -> bool {
+  interval_is_nobegin(i) || interval_is_noend(i)
+}
+
 
 // Assorted constants for datetime-related calculations
 const DAYS_PER_YEAR    :f32 = 365.25; // assumes leap year every four years
@@ -241,12 +438,131 @@ const USECS_PER_SEC    :i64 = 1000000;
 
 /// maximum allowed hour part
 const MAX_TZDISP_HOUR  : i32 = 15;
+/// zone displacements must be strictly within this many seconds of UTC
+const TZDISP_LIMIT     : i32 = (MAX_TZDISP_HOUR + 1) * SECS_PER_HOUR;
+
+// Julian-date equivalents of Day 0 in Unix and Postgres reckoning
+pub const UNIX_EPOCH_JDATE     :i32 = 2440588; // == date2j(1970, 1, 1)
+pub const POSTGRES_EPOCH_JDATE :i32 = 2451545; // == date2j(2000, 1, 1)
+
+// Range limits for dates and timestamps.  Although the ranges determined
+// by the Julian-day routines are much larger, the Julian-day routines
+// cannot be relied upon past these limits.  The END values are the first
+// values past the range; MAX_DATE and MAX_TIMESTAMP are the last ones in it.
+pub const DATETIME_MIN_JULIAN  :i32 = 0;
+pub const DATE_END_JULIAN      :i32 = 2147483494; // == date2j(JULIAN_MAXYEAR, 1, 1)
+pub const TIMESTAMP_END_JULIAN :i32 = 109203528;  // == date2j(294277, 1, 1)
+
+// == (DATETIME_MIN_JULIAN - POSTGRES_EPOCH_JDATE) * USECS_PER_DAY
+pub const MIN_TIMESTAMP :Timestamp = -211813488000000000;
+// == (TIMESTAMP_END_JULIAN - POSTGRES_EPOCH_JDATE) * USECS_PER_DAY
+pub const END_TIMESTAMP :Timestamp = 9223371331200000000;
+pub const MAX_TIMESTAMP :Timestamp = END_TIMESTAMP - 1;
+
+pub const MIN_DATE :Date = -2451545;   // == DATETIME_MIN_JULIAN - POSTGRES_EPOCH_JDATE
+pub const END_DATE :Date = 2145031949; // == DATE_END_JULIAN - POSTGRES_EPOCH_JDATE
+pub const MAX_DATE :Date = END_DATE - 1;
+
+/// Whether a finite date lies in the supported range.
+#[allow(non_snake_case)]
+pub fn IS_VALID_DATE(d: Date) -> bool {
+  MIN_DATE <= d && d < END_DATE
+}
+
+/// Whether a finite timestamp lies in the supported range.
+#[allow(non_snake_case)]
+pub fn IS_VALID_TIMESTAMP(t: Timestamp) -> bool {
+  MIN_TIMESTAMP <= t && t < END_TIMESTAMP
+}
+
+// ---------------------------------------------------------------------------
+// Ported from date.h
+// ---------------------------------------------------------------------------
+
+const MAX_TIME_PRECISION :i32 = 6;
+
+/// Days since 2000-01-01 (POSTGRES_EPOCH_JDATE)
+pub type Date = i32;
+/// Microseconds since midnight
+pub type Time = i64;
+
+// Infinite dates are represented by the extreme values of the type.
+pub const DATEVAL_NOBEGIN :Date = ::std::i32::MIN;
+pub const DATEVAL_NOEND   :Date = ::std::i32::MAX;
+
+pub fn date_is_nobegin(d: Date) -> bool {
+  d == DATEVAL_NOBEGIN
+}
+
+pub fn date_is_noend(d: Date) -> bool {
+  d == DATEVAL_NOEND
+}
+
+pub fn date_not_finite(d: Date) -> bool {
+  date_is_nobegin(d) || date_is_noend(d)
+}
+
+/// Time of day with a fixed UTC offset
+#[derive(Clone, Copy, Debug)]
+pub struct TimeTz {
+  /// all time units other than months and years
+  time: Time,
+  /// numeric time zone, in seconds west of UTC
+  zone: i32
+}
+
+impl TimeTz {
+  pub fn new(time: Time, zone: i32) -> TimeTz {
+    TimeTz {
+      time: time,
+      zone: zone
+    }
+  }
+
+  pub fn time(&self) -> Time {
+    self.time
+  }
+
+  pub fn zone(&self) -> i32 {
+    self.zone
+  }
+}
+
+// Ported from timetz_cmp_internal()
+impl Ord for TimeTz {
+  fn cmp(&self, other: &TimeTz) -> Ordering {
+    // Primary sort is by true (GMT-equivalent) time
+    let t1 = self.time + self.zone as i64 * USECS_PER_SEC;
+    let t2 = other.time + other.zone as i64 * USECS_PER_SEC;
+
+    // If same GMT time, sort by timezone; we only want to say that two
+    // timetz's are equal if both the time and zone parts are equal.
+    match t1.cmp(&t2) {
+      Ordering::Equal => self.zone.cmp(&other.zone),
+      ord => ord
+    }
+  }
+}
+
+impl PartialOrd for TimeTz {
+  fn partial_cmp(&self, other: &TimeTz) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl PartialEq for TimeTz {
+  fn eq(&self, other: &TimeTz) -> bool {
+    self.cmp(other) == Ordering::Equal
+  }
+}
+
+impl Eq for TimeTz {}
 
 // ---------------------------------------------------------------------------
 // Ported from pgtime.h
 // ---------------------------------------------------------------------------
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct TimeMeta {
   tm_sec: i32,
 	tm_min: i32,
@@ -285,6 +601,10 @@ impl TimeMeta {
 // Ported from datetime.c
 // ---------------------------------------------------------------------------
 
+pub fn isleap(y: i32) -> bool {
+  (y % 4) == 0 && ((y % 100) != 0 || (y % 400) == 0)
+}
+
 const DAY_TAB: [[i32;13];2] = [
   [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31, 0],
   [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31, 0]
@@ -316,7 +636,7 @@ macro_rules! token {
   }
 }
 
-pub static DATETK_TBL: [DateToken;74] = [
+pub static DATETK_TBL: [DateToken;85] = [
   token!(EARLY, RESERV, DTK_EARLY),
   token!(DA_D, ADBC, AD),                     // "ad" for years > 0
   token!(b"allballs", RESERV, DTK_ZULU),      // 00:00:00
@@ -327,6 +647,8 @@ pub static DATETK_TBL: [DateToken;74] = [
   token!(b"aug", MONTH, 8),
   token!(b"august", MONTH, 8),
   token!(DB_C, ADBC, BC),                     // "bc" for years <= 0
+  token!(b"cdt", DTZ, -18000),                // Central Daylight Time
+  token!(b"cst", TZ, -21600),                 // Central Standard Time
   token!(DCURRENT, RESERV, DTK_CURRENT),      // "current" is always now
   token!(b"d", UNITS, DTK_DAY),               // "day of month" for ISO input
   token!(b"dec", MONTH, 12),
@@ -334,11 +656,14 @@ pub static DATETK_TBL: [DateToken;74] = [
   token!(b"dow", RESERV, DTK_DOW),            // day of week
   token!(b"doy", RESERV, DTK_DOY),            // day of year
   token!(b"dst", DTZMOD, SECS_PER_HOUR),
+  token!(b"edt", DTZ, -14400),                // Eastern Daylight Time
   token!(EPOCH, RESERV, DTK_EPOCH),           // "epoch" reserved for system epoch time
+  token!(b"est", TZ, -18000),                 // Eastern Standard Time
   token!(b"feb", MONTH, 2),
   token!(b"february", MONTH, 2),
   token!(b"fri", DOW, 5),
   token!(b"friday", DOW, 5),
+  token!(b"gmt", TZ, 0),                       // Greenwich Mean Time
   token!(b"h", UNITS, DTK_HOUR),              // "hour"
   token!(LATE, RESERV, DTK_LATE),             // "infinity" reserved for "late time"
   token!(INVALID, RESERV, DTK_INVALID),       // "invalid" reserved for bad time
@@ -357,16 +682,20 @@ pub static DATETK_TBL: [DateToken;74] = [
   token!(b"mar", MONTH, 3),
   token!(b"march", MONTH, 3),
   token!(b"may", MONTH, 5),
+  token!(b"mdt", DTZ, -21600),                 // Mountain Daylight Time
   token!(b"mm", UNITS, DTK_MINUTE),            // "minute" for ISO input
   token!(b"mon", DOW, 1),
   token!(b"monday", DOW, 1),
+  token!(b"mst", TZ, -25200),                  // Mountain Standard Time
   token!(b"nov", MONTH, 11),
   token!(b"november", MONTH, 11),
   token!(NOW, RESERV, DTK_NOW),                // current transaction time
   token!(b"oct", MONTH, 10),
   token!(b"october", MONTH, 10),
   token!(b"on", IGNORE_DTF, 0),                // "on" (throwaway)
+  token!(b"pdt", DTZ, -25200),                 // Pacific Daylight Time
   token!(b"pm", AMPM, PM),
+  token!(b"pst", TZ, -28800),                  // Pacific Standard Time
   token!(b"s", UNITS, DTK_SECOND),             // "seconds" for ISO input
   token!(b"sat", DOW, 6),
   token!(b"saturday", DOW, 6),
@@ -386,6 +715,8 @@ pub static DATETK_TBL: [DateToken;74] = [
   token!(b"tues", DOW, 2),
   token!(b"tuesday", DOW, 2),
   token!(b"undefined", RESERV, DTK_INVALID),   // pre-v6.1 invalid time
+  token!(b"ut", TZ, 0),                        // Universal Time
+  token!(b"utc", TZ, 0),                       // Coordinated Universal Time
   token!(b"wed", DOW, 3),
   token!(b"wednesday", DOW, 3),
   token!(b"weds", DOW, 3),
@@ -476,7 +807,23 @@ static DELTATK_TBL: [DateToken;63] = [
 /// 0 to 2147483647  (Nov 24, -4713 to Jun 3, 5874898) assuming
 /// a 32-bit integer. Longer types should also work to the limits
 /// of their precision.
-pub fn date2j(mut y: i32, mut m: i32, d: i32) -> i32 {
+///
+/// Both now compute in 64 bits and extend the proleptic Gregorian calendar
+/// back through negative Julian days, so that they agree for every i32
+/// day count: -2147483648 is Jan 26, -5884323 (5884324 BC).
+///
+/// Panics if the Julian day does not fit in an i32; see checked_date2j().
+#[inline]
+pub fn date2j(y: i32, m: i32, d: i32) -> i32 {
+  match checked_date2j(y, m, d) {
+    Some(julian) => julian,
+    None => panic!("Julian day out of range: {}-{}-{}", y, m, d)
+  }
+}
+
+/// date2j(), or None if the Julian day does not fit in an i32.
+pub fn checked_date2j(y: i32, m: i32, d: i32) -> Option<i32> {
+  let (mut y, mut m) = (y as i64, m as i64);
   if m > 2 {
       m += 1;
       y += 4800;
@@ -485,17 +832,33 @@ pub fn date2j(mut y: i32, mut m: i32, d: i32) -> i32 {
       y += 4799;
   }
 
-  let century: i32 = y / 100;
-  let mut julian: i32 = y * 365 - 32167;
-  julian += y / 4 - century + century / 4;
-  julian += 7834 * m / 256 + d;
+  // round leap days down before year -4800, too
+  let century: i64 = div_floor(y, 100);
+  let mut julian: i64 = y * 365 - 32167;
+  julian += div_floor(y, 4) - century + div_floor(century, 4);
+  julian += 7834 * m / 256 + d as i64;
+
+  julian_day(julian)
+}
 
-  julian
+/// A Julian day computed in 64 bits, or None if it does not fit in an i32.
+fn julian_day(julian: i64) -> Option<i32> {
+  if julian < ::std::i32::MIN as i64 || julian > ::std::i32::MAX as i64 {
+    None
+  } else {
+    Some(julian as i32)
+  }
 }
 
-fn j2date(julian_day: u32) -> (i32, u32, u32) {
-  let mut julian: u32 = julian_day;
-  julian += 32044;
+#[inline]
+pub fn j2date(julian_day: i32) -> (i32, u32, u32) {
+  // The arithmetic below wants a non-negative day count. Move earlier days
+  // forward by whole 400-year cycles of 146097 days, and the year back
+  // again after.
+  let shifted: i64 = julian_day as i64 + 32044;
+  let cycles: i64 = if shifted < 0 { (146096 - shifted) / 146097 } else { 0 };
+
+  let mut julian: u32 = (shifted + cycles * 146097) as u32;
   let mut quad: u32 = julian / 146097;
   let extra: u32 = (julian - quad * 146097) * 4 + 3;
   julian += 60 + quad * 3 + extra / 146097;
@@ -506,11 +869,12 @@ fn j2date(julian_day: u32) -> (i32, u32, u32) {
   julian = if y != 0 {
     ((julian + 305) % 365)
   } else {
-    ((julian + 306) % 366) + 123
+    ((julian + 306) % 366)
   };
+  julian += 123;
 
   y += quad * 4;
-  let year :i32 = (y - 4800) as i32;
+  let year :i32 = (y as i64 - 4800 - cycles * 400) as i32;
   quad = julian * 2141 / 65536;
   let day: u32 = julian - 7834 * quad / 256;
   let month: u32 = (quad + 10) % MONTHS_PER_YEAR as u32 + 1;
@@ -524,6 +888,7 @@ fn j2date(julian_day: u32) -> (i32, u32, u32) {
 /// Note: various places use the locution j2day(date - 1) to produce a
 /// result according to the convention 0..6 = Mon..Sun.  This is a bit of
 /// a crock, but will work as long as the computation here is just a modulo.
+#[inline]
 pub fn j2day(mut date: i32) -> i32 {
   date += 1;
   date %= 7;
@@ -535,38 +900,285 @@ pub fn j2day(mut date: i32) -> i32 {
   date
 }
 
-#[derive(PartialEq, Eq)]
-pub enum DateTimeParseError {
-  BadFormat(String),
-  TimezoneOverflow
+/// The kind of a date/time input error. These mirror the DTERR_* codes of
+/// datetime.h.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DateTimeErrorKind {
+  /// A field value is out of range, e.g. hour 25 (DTERR_FIELD_OVERFLOW).
+  FieldOverflow,
+  /// A month or day is out of range, which may mean the fields are in a
+  /// different order than DATE_ORDER expects (DTERR_MD_FIELD_OVERFLOW).
+  MdFieldOverflow,
+  /// Valid fields whose value is outside the supported range, e.g. a date
+  /// beyond the Julian-day limits.
+  OutOfRange,
+  /// The input does not match any accepted syntax (DTERR_BAD_FORMAT).
+  BadFormat,
+  /// An interval field is out of range (DTERR_INTERVAL_OVERFLOW).
+  IntervalOverflow,
+  /// A numeric time zone offset is out of range (DTERR_TZDISP_OVERFLOW).
+  TzDispOverflow,
+  /// A word that is not a known date/time keyword.
+  UnknownToken,
+  /// A field that repeats or conflicts with an earlier one, e.g. two years.
+  AmbiguousField,
+  /// A day of week that does not fall on the given date, e.g. "Thu, 11 Nov
+  /// 2016". Only reported by the strict decoders.
+  WeekdayMismatch
 }
 
-impl fmt::Debug for DateTimeParseError {
-  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl DateTimeErrorKind {
+  /// The SQLSTATE PostgreSQL reports for this kind of error.
+  pub fn code(&self) -> &'static str {
     match *self {
-      DateTimeParseError::BadFormat(ref s) => write!(f, "{}", s),
-      DateTimeParseError::TimezoneOverflow => {
-        write!(f, "overflow or underflow in timezone")
-      }
+      // datetime_field_overflow
+      DateTimeErrorKind::FieldOverflow |
+      DateTimeErrorKind::MdFieldOverflow |
+      DateTimeErrorKind::OutOfRange => "22008",
+      // invalid_datetime_format
+      DateTimeErrorKind::BadFormat |
+      DateTimeErrorKind::UnknownToken |
+      DateTimeErrorKind::AmbiguousField |
+      DateTimeErrorKind::WeekdayMismatch => "22007",
+      // interval_field_overflow
+      DateTimeErrorKind::IntervalOverflow => "22015",
+      // invalid_time_zone_displacement_value
+      DateTimeErrorKind::TzDispOverflow => "22009"
+    }
+  }
+
+  fn message(&self) -> &'static str {
+    match *self {
+      DateTimeErrorKind::FieldOverflow |
+      DateTimeErrorKind::MdFieldOverflow => "date/time field value out of range",
+      DateTimeErrorKind::OutOfRange => "value out of range",
+      DateTimeErrorKind::BadFormat => "invalid input syntax",
+      DateTimeErrorKind::IntervalOverflow => "interval field value out of range",
+      DateTimeErrorKind::TzDispOverflow => "time zone displacement out of range",
+      DateTimeErrorKind::UnknownToken => "unrecognized date/time token",
+      DateTimeErrorKind::AmbiguousField => "conflicting or repeated date/time field",
+      DateTimeErrorKind::WeekdayMismatch => "day of week does not match date"
+    }
+  }
+}
+
+/// An error in date/time input.
+///
+/// The error refers to some input text: the whole string when it comes from
+/// one of the *_in() functions, or the field handed to a lower-level decoder.
+/// When known, span() is the byte range of the offending characters within
+/// that text, and field_type() is the DTK_* type parse_datetime() gave them.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DateTimeParseError {
+  kind: DateTimeErrorKind,
+  input: String,
+  span: Option<Range<usize>>,
+  field: Option<usize>,
+  field_type: Option<i32>,
+  type_name: Option<&'static str>
+}
+
+impl DateTimeParseError {
+  pub fn new(kind: DateTimeErrorKind, input: &str) -> DateTimeParseError {
+    DateTimeParseError {
+      kind: kind,
+      input: input.to_owned(),
+      span: None,
+      field: None,
+      field_type: None,
+      type_name: None
+    }
+  }
+
+  fn from_bytes(kind: DateTimeErrorKind, input: &[u8]) -> DateTimeParseError {
+    DateTimeParseError::new(kind, &String::from_utf8_lossy(input))
+  }
+
+  pub fn kind(&self) -> DateTimeErrorKind {
+    self.kind
+  }
+
+  /// SQLSTATE of the error, e.g. "22007".
+  pub fn code(&self) -> &'static str {
+    self.kind.code()
+  }
+
+  /// A suggestion for fixing the input, if there is one.
+  pub fn hint(&self) -> Option<&'static str> {
+    match self.kind {
+      DateTimeErrorKind::MdFieldOverflow => Some("Perhaps you need a different date order."),
+      _ => None
+    }
+  }
+
+  /// The text the error refers to.
+  pub fn input(&self) -> &str {
+    &self.input
+  }
+
+  /// Byte range of the offending characters within input().
+  pub fn span(&self) -> Option<Range<usize>> {
+    self.span.clone()
+  }
+
+  /// DTK_* type of the offending field, e.g. DTK_DATE.
+  pub fn field_type(&self) -> Option<i32> {
+    self.field_type
+  }
+
+  /// Point the error at part of its input.
+  fn at(mut self, span: Range<usize>) -> DateTimeParseError {
+    self.span = Some(span);
+    self
+  }
+
+  /// Re-anchor an error from the sub-string at range of s. A span the error
+  /// already has is taken relative to range.start; otherwise the error
+  /// covers the whole range.
+  fn within(mut self, s: &[u8], range: Range<usize>) -> DateTimeParseError {
+    self.span = Some(match self.span {
+      Some(ref sub) => range.start + sub.start..range.start + sub.end,
+      None => range
+    });
+    self.input = String::from_utf8_lossy(s).into_owned();
+    self
+  }
+
+  /// Record which parse_datetime() field the error came from. A span the
+  /// error already has is kept only if it refers to the same field text.
+  fn in_field(mut self, i: usize, ftype: i32, field: &[u8]) -> DateTimeParseError {
+    if self.input.as_bytes() != field {
+      self.input = String::from_utf8_lossy(field).into_owned();
+      self.span = None;
+    }
+    self.field = Some(i);
+    self.field_type = Some(ftype);
+    self
+  }
+
+  /// Report the error against the whole input s of the named type, mapping
+  /// a field-relative span through the field spans of parse_datetime().
+  fn locate(mut self, s: &str, type_name: &'static str, spans: &[Range<usize>])
+            -> DateTimeParseError {
+    if let Some(i) = self.field {
+      let field = spans[i].clone();
+      self.span = match self.span {
+        // the field is a verbatim copy of the input only if the lengths agree
+        Some(ref sub) if field.len() == self.input.len() => {
+          Some(field.start + sub.start..field.start + sub.end)
+        }
+        _ => Some(field)
+      };
+    }
+    self.input = s.to_owned();
+    self.type_name = Some(type_name);
+    self
+  }
+}
+
+impl fmt::Display for DateTimeParseError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.type_name {
+      Some(ty) => write!(f, "{} for type {}: '{}'", self.kind.message(), ty, self.input),
+      None => write!(f, "{}: '{}'", self.kind.message(), self.input)
     }
   }
 }
 
+impl Error for DateTimeParseError {}
+
 impl From<ParseNumErr> for DateTimeParseError {
-  fn from(e: ParseNumErr) -> Self {
-    DateTimeParseError::BadFormat(format!("{}", e))
+  fn from(_: ParseNumErr) -> Self {
+    // the caller attaches the offending text
+    DateTimeParseError::new(DateTimeErrorKind::BadFormat, "")
+  }
+}
+
+/// How to drop digits that do not fit.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Rounding {
+  /// to the nearest value, halves to the later one
+  HalfUp,
+  /// to the nearest value, halves to the even one
+  HalfEven,
+  /// to the earlier value
+  Floor
+}
+
+/// Convert the digits after a decimal point to units of 10^-precision,
+/// e.g. 6 for microseconds or 9 for nanoseconds, rounded as given. Any
+/// number of digits is read exactly; the result is 10^precision if they
+/// round up to one. Return None unless digits is a non-empty run of ASCII
+/// digits.
+pub fn parse_fraction(digits: &[u8], precision: u32, rounding: Rounding) -> Option<i64> {
+  debug_assert!(precision <= 18);
+
+  if digits.is_empty() || !digits.iter().all(|&c| isdigit(c)) {
+    return None;
+  }
+
+  // the digits that fit, padded with zeros
+  let kept = (0..precision as usize).fold(0i64, |n, i| {
+    n * 10 + digits.get(i).map_or(0, |&c| (c - b'0') as i64)
+  });
+
+  // compare the digits that do not fit with one half
+  let rest = if digits.len() > precision as usize { &digits[precision as usize..] } else { &[] };
+  let (above_half, half) = match rest.first() {
+    Some(&b'5') => {
+      let sticky = rest[1..].iter().any(|&c| c != b'0');
+      (sticky, !sticky)
+    }
+    Some(&c) => (c > b'5', false),
+    None => (false, false)
+  };
+
+  Some(match rounding {
+    Rounding::HalfUp if above_half || half => kept + 1,
+    Rounding::HalfEven if above_half || (half && kept % 2 != 0) => kept + 1,
+    _ => kept
+  })
+}
+
+/// Convert the digits after a decimal point to a count of 1/unit parts of a
+/// whole, e.g. the microseconds in a fraction of a day, rounded as given.
+/// parse_fraction(digits, p, rounding) is the same as unit 10^p. Any number
+/// of digits is read exactly. Return None unless digits is a non-empty run of
+/// ASCII digits.
+fn parse_fraction_of(digits: &[u8], unit: i64, rounding: Rounding) -> Option<i64> {
+  debug_assert!(unit > 0 && unit <= ::std::i64::MAX / 20);
+
+  if digits.is_empty() || !digits.iter().all(|&c| isdigit(c)) {
+    return None;
+  }
+
+  // 2 * unit * 0.d1d2..dn by Horner's rule from the last digit, keeping the
+  // whole part and whether anything was left over
+  let mut twice = 0i64;
+  let mut inexact = false;
+  for &c in digits.iter().rev() {
+    let n = (c - b'0') as i64 * 2 * unit + twice;
+    inexact |= n % 10 != 0;
+    twice = n / 10;
   }
+
+  let kept = twice / 2;
+  let half = twice % 2 != 0;
+  Some(match rounding {
+    Rounding::HalfUp if half => kept + 1,
+    Rounding::HalfEven if half && (inexact || kept % 2 != 0) => kept + 1,
+    _ => kept
+  })
 }
 
-/// Parse a string to a fractional second.
+/// Parse a string such as ".12345" to a fractional second in microseconds.
 pub fn parse_fractional_second(s: &str) -> Result<i64, DateTimeParseError> {
   debug_assert!(s.len() > 1);
   debug_assert!(s.as_bytes()[0] == b'.');
 
-  let part = &s[1..];
-  match i64::from_str(part) {
-    Ok(frac) => Ok(frac * 1000000),
-    Err(e) => Err(DateTimeParseError::BadFormat(format!("{}: '{}'", e, s)))
+  match parse_fraction(&s.as_bytes()[1..], 6, Rounding::HalfUp) {
+    Some(frac) => Ok(frac),
+    None => Err(DateTimeParseError::new(DateTimeErrorKind::BadFormat, s))
   }
 }
 
@@ -584,8 +1196,9 @@ pub fn decode_date(s: &[u8], fmask: &mut i32, tmask: &mut i32, is2digits: &mut b
   let mut idx = 0;
   let mut dmask: i32 = 0;
   let mut fields: Vec<&[u8]> = Vec::with_capacity(MAXDATEFIELDS);
+  let mut starts: Vec<usize> = Vec::with_capacity(MAXDATEFIELDS);
   let mut has_text_month = false;
-  let mut fields_identified: [bool;8] = unsafe { ::std::mem::zeroed() };
+  let mut fields_identified = [false; MAXDATEFIELDS];
 
   // parse this string...
   while idx < len && fields.len() < MAXDATEFIELDS {
@@ -596,8 +1209,7 @@ pub fn decode_date(s: &[u8], fmask: &mut i32, tmask: &mut i32, is2digits: &mut b
     }
 
     if idx == len {
-      return Err(DateTimeParseError::BadFormat(format!("bad date format: '{}'",
-        unsafe { str::from_utf8_unchecked(s) })));
+      return Err(bad_format(s));
     }
 
     let field_start_idx = idx;
@@ -611,11 +1223,9 @@ pub fn decode_date(s: &[u8], fmask: &mut i32, tmask: &mut i32, is2digits: &mut b
       }
     }
     fields.push(&s[field_start_idx .. idx]);
+    starts.push(field_start_idx);
   }
-
-  for i in 0..fields.len() {
-    println!("fields[{}]=\"{}\"", i, unsafe { str::from_utf8_unchecked(fields[i]) });
-  }
+  let span = |i: usize| starts[i]..starts[i] + fields[i].len();
 
   // look first for text fields, since that will be unambiguous month
   for i in 0..fields.len() {
@@ -635,59 +1245,53 @@ pub fn decode_date(s: &[u8], fmask: &mut i32, tmask: &mut i32, is2digits: &mut b
              has_text_month = true;             
            }
            _ => {
-             return Err(DateTimeParseError::BadFormat(format!("1 bad date format: '{}'",
-              unsafe { str::from_utf8_unchecked(s) })));
+             return Err(bad_format(s).at(span(i)));
            }
          };
 
          if (*fmask & dmask) != 0 {
-				  return Err(DateTimeParseError::BadFormat(format!("2 bad date format: '{}'",
-              unsafe { str::from_utf8_unchecked(s) })));
+				  return Err(DateTimeParseError::from_bytes(DateTimeErrorKind::AmbiguousField, s)
+                     .at(span(i)));
          }
 
          *fmask = *fmask | dmask;
 			   *tmask = *tmask | dmask;
          
        } else {
-         return Err(DateTimeParseError::BadFormat(format!("3 bad date format: '{}'",
-          unsafe { str::from_utf8_unchecked(s) })));
+         return Err(DateTimeParseError::from_bytes(DateTimeErrorKind::UnknownToken, s)
+                    .at(span(i)));
        }
 
        fields_identified[i] = true;
      }
   }
 
-  for i in 0..fields.len() {
-    println!("all fields parsed...");
-  }
   for i in 0..fields.len() {
     if fields_identified[i] {
-      println!("fields_identified[{}]=true", i);
       continue;
-    } else {
-      println!("fields_identified[{}]=false", i);
     }
 
     let len = fields[i].len();
-    if len <= 0 {
-      return Err(DateTimeParseError::BadFormat(format!("bad date format: '{}'",
-          unsafe { str::from_utf8_unchecked(s) })));
+    if len == 0 {
+      return Err(bad_format(s));
     }
 
-    decode_number(len, fields[i], has_text_month, fmask, &mut dmask, tm, &mut 0, is2digits)?;
+    if let Err(e) = decode_number(len, fields[i], has_text_month, fmask, &mut dmask, tm,
+                                  &mut 0, is2digits) {
+      return Err(e.within(s, span(i)));
+    }
 
     if (*fmask & dmask) != 0 {
-			return Err(DateTimeParseError::BadFormat(format!("bad date format: '{}'",
-          unsafe { str::from_utf8_unchecked(s) })));
+			return Err(DateTimeParseError::from_bytes(DateTimeErrorKind::AmbiguousField, s)
+                 .at(span(i)));
     }
 
 		*fmask = *fmask | dmask;
 		*tmask = *tmask | dmask;
   }
 
-  if (*fmask & !(DTK_M(DOY) | DTK_M(TZ))) != DTK_DATE_M {
-    return Err(DateTimeParseError::BadFormat(format!("bad date format: '{}'",
-          unsafe { str::from_utf8_unchecked(s) })));
+  if (*fmask & DTK_DATE_M) != DTK_DATE_M {
+    return Err(bad_format(s));
   }
 
   Ok(())
@@ -710,14 +1314,12 @@ fn decode_number(flen: usize, s: &[u8], has_text_month: bool, fmask: &mut i32,
     }
 
   } else if remain.is_some() {
-    return Err(DateTimeParseError::BadFormat(
-      format!("invalid number format: '{}'",
-        unsafe { str::from_utf8_unchecked(s) } )));
+    return Err(bad_format(s));
   }
 
-  // Special case for day of year
-  if flen == 3 && (*fmask & DTK_DATE_M) == DTK_M(YEAR) &&
-     val > 1 && val <= 366 {
+  // Special case for day of year. Some calendars have years of more than
+  // 366 days, so the length of the year is checked later.
+  if flen == 3 && (*fmask & DTK_DATE_M) == DTK_M(YEAR) && val >= 1 {
     *tmask = (DTK_M(DOY) | DTK_M(MONTH) | DTK_M(DAY));
     tm.tm_yday = val;
     return Ok(())
@@ -812,8 +1414,7 @@ fn decode_number(flen: usize, s: &[u8], has_text_month: bool, fmask: &mut i32,
       return Ok(())
     }
     _ => {
-      return Err(DateTimeParseError::BadFormat(format!("bad date format: '{}'",
-              unsafe { str::from_utf8_unchecked(s) })));
+      return Err(bad_format(s));
     }
   };
 
@@ -841,21 +1442,31 @@ fn decode_number_field(mut len: usize, s: &[u8], fmask: i32, tmask: &mut i32,
 	// field...
   let decimal_point_idx = s.iter().position(|&c| c == b'.');
   if let Some(idx) = decimal_point_idx {
-		 // Can we use ParseFractionalSecond here?  Not clear whether trailing
-		 // junk should be rejected ...
-     let (frac, remaion) = unsafe { strtod(&s[idx..])? };
-     *fsec = (frac * 1000000f64).round() as i32;
+     *fsec = decode_fraction(&s[idx..])?;
      /* Now truncate off the fraction for further processing */
-     len = idx - 1;
+     len = idx;
 
   // No decimal point and no complete date yet?
   } else if (fmask & DTK_DATE_M) != DTK_DATE_M {
 
+    // yyyyddd, the ISO 8601 basic ordinal date
+    if len == 7 {
+      *tmask = DTK_DATE_M | DTK_M(DOY);
+      tm.tm_yday = unsafe { i32::from_bytes(&s[4..])? };
+      tm.tm_year = unsafe { i32::from_bytes(&s[..4])? };
+
+      if tm.tm_yday < 1 {
+        return Err(DateTimeParseError::from_bytes(DateTimeErrorKind::FieldOverflow, s));
+      }
+
+      return Ok(DTK_DATE);
+    }
+
     // yyyymmdd or yymmdd
     if len >= 6 {
       *tmask = DTK_DATE_M;
-      tm.tm_mon = unsafe { i32::from_bytes(&s[(len - 2)..])? };
-      tm.tm_mday = unsafe { i32::from_bytes(&s[(len - 4)..(len - 2)])? };
+      tm.tm_mday = unsafe { i32::from_bytes(&s[(len - 2)..])? };
+      tm.tm_mon = unsafe { i32::from_bytes(&s[(len - 4)..(len - 2)])? };
       tm.tm_year = unsafe { i32::from_bytes(&s[..(len-4)])? };
 
       if (len - 4) == 2 {
@@ -866,6 +1477,8 @@ fn decode_number_field(mut len: usize, s: &[u8], fmask: i32, tmask: &mut i32,
     }
   }
 
+  let s = &s[..len];
+
   /* not all time fields are specified? */
   if fmask & DTK_TIME_M != DTK_TIME_M {
 
@@ -887,7 +1500,7 @@ fn decode_number_field(mut len: usize, s: &[u8], fmask: i32, tmask: &mut i32,
     }
   }
 
-  Err(DateTimeParseError::BadFormat(format!("")))
+  Err(bad_format(s))
 }
 
 /// Parse a string to a timezone in seconds.
@@ -900,21 +1513,20 @@ pub fn decode_timezone(tzstr: &str) -> Result<i32, DateTimeParseError> {
 
   let plus_or_minus = buf[0];
   if plus_or_minus != b'+' && plus_or_minus != b'-' {
-    return Err(DateTimeParseError::BadFormat(
-      format!("leading characer in timezone must be '+' or '-': '{}'", tzstr)));
+    return Err(bad_format(buf));
   }
 
-  let r = unsafe { strtoi(&buf[1..])? };
+  let r = unsafe { strtoi(&buf[1..]) }.map_err(|_| bad_format(buf))?;
   hr = r.0;
   remains = r.1;
 
   if remains.is_some() && remains.unwrap()[0] == b':' {
-    let r = unsafe { strtoi(&remains.unwrap()[1..])? };
+    let r = unsafe { strtoi(&remains.unwrap()[1..]) }.map_err(|_| bad_format(buf))?;
     min = r.0;
     remains = r.1;
 
     if remains.is_some() && remains.unwrap()[0] == b':' {
-      let r = unsafe { strtoi(&remains.unwrap()[1..])? };
+      let r = unsafe { strtoi(&remains.unwrap()[1..]) }.map_err(|_| bad_format(buf))?;
       sec = r.0;
       remains = r.1;
     }
@@ -926,13 +1538,13 @@ pub fn decode_timezone(tzstr: &str) -> Result<i32, DateTimeParseError> {
   }
 
   if hr < 0 || hr > MAX_TZDISP_HOUR {
-    return Err(DateTimeParseError::TimezoneOverflow);
+    return Err(DateTimeParseError::new(DateTimeErrorKind::TzDispOverflow, tzstr));
   }
   if min < 0 || min >= MINS_PER_HOUR {
-    return Err(DateTimeParseError::TimezoneOverflow)
+    return Err(DateTimeParseError::new(DateTimeErrorKind::TzDispOverflow, tzstr));
   }
   if sec < 0 || sec >= SECS_PER_MINUTE {
-    return Err(DateTimeParseError::TimezoneOverflow)
+    return Err(DateTimeParseError::new(DateTimeErrorKind::TzDispOverflow, tzstr));
   }
 
   let mut tz = (hr * MINS_PER_HOUR + min) * SECS_PER_MINUTE + sec;
@@ -942,8 +1554,7 @@ pub fn decode_timezone(tzstr: &str) -> Result<i32, DateTimeParseError> {
   }
 
   if remains.is_some() {
-    return Err(DateTimeParseError::BadFormat(
-      format!("bad format in timezone: '{}'", tzstr)));
+    return Err(bad_format(buf));
   }
 
   Ok(-tz)
@@ -975,6 +1586,9 @@ pub fn datebsearch<'a>(key: &[u8], data: &'a [DateToken])
     }
 
     if result < 0 {
+      if position == 0 {
+        break;
+      }
       last = position - 1;
     } else {
       base = position + 1;
@@ -984,85 +1598,3650 @@ pub fn datebsearch<'a>(key: &[u8], data: &'a [DateToken])
   None
 }
 
-#[cfg(test)]
-mod tests {
-  use super::*;
-  use super::DateTimeParseError::*;
+fn bad_format(s: &[u8]) -> DateTimeParseError {
+  DateTimeParseError::from_bytes(DateTimeErrorKind::BadFormat, s)
+}
 
-  fn assert_decode_date(s: &str, year: i32, month: i32, day: i32, is2digity: bool) {
-    let mut tmask: i32 = 0;
-    let mut fmask: i32 = 0;
-    let mut is2digits: bool = false;
-    let mut tm = TimeMeta::empty();
-    decode_date(s.as_bytes(), &mut tmask, &mut fmask, &mut is2digits, &mut tm).ok().unwrap();
-    println!("{:?}", tm);
-    assert_eq!(tm.tm_year, year);
-    assert_eq!(tm.tm_mon, month);
-    assert_eq!(tm.tm_mday, day);
-    assert_eq!(is2digits, is2digity);
-  }
+fn unknown_token(s: &[u8]) -> DateTimeParseError {
+  DateTimeParseError::from_bytes(DateTimeErrorKind::UnknownToken, s)
+}
 
-  #[test]
-  fn test_decode_date() {
-    assert_decode_date("Feb-7-1997", 1997, 2, 7, false);
-    assert_decode_date("2-7-1997", 1997, 2, 7, false);
-    assert_decode_date("1997-2-7", 1997, 2, 7, false);
-    //decode_date("Feb-7-1997".as_bytes(), &mut tmask, &mut fmask, &mut is2digits, &mut tm);
-    //decode_date("2-7-1997".as_bytes(), &mut tmask, &mut fmask, &mut is2digits, &mut tm);
-    //decode_date("1997-2-7".as_bytes(), &mut tmask, &mut fmask, &mut is2digits, &mut tm);
-    //decode_date("1997.038".as_bytes(), &mut tmask, &mut fmask, &mut is2digits, &mut tm);
+/// Byte range of the UTF-8 character starting at s[idx], or an empty range
+/// at the end of s.
+fn char_at(s: &[u8], idx: usize) -> Range<usize> {
+  if idx >= s.len() {
+    return s.len()..s.len();
   }
 
-  #[test]
-  fn test_parse_fractional_second() {
-    assert_eq!(12345000000i64, parse_fractional_second(".12345").ok().unwrap());
-  }
+  let c = s[idx];
+  let width = if c >= 0xf0 { 4 } else if c >= 0xe0 { 3 } else if c >= 0xc0 { 2 } else { 1 };
+  idx..::std::cmp::min(idx + width, s.len())
+}
 
-  #[test]
-  fn test_parse_fractional_second_fail1() {
-    let err = parse_fractional_second(".inv").err().unwrap();
-    assert_eq!(BadFormat("invalid digit found in string: '.inv'".to_owned()),
-      err);
-  }
+/// Convert a ".fff" suffix to microseconds.
+fn decode_fraction(s: &[u8]) -> Result<FracSec, DateTimeParseError> {
+  debug_assert!(s[0] == b'.');
 
-  #[test]
-  fn test_j2day() {
-    let jd = date2j(2016, 11, 11);
-    assert_eq!(5, j2day(jd));
+  match parse_fraction(&s[1..], 6, Rounding::HalfUp) {
+    Some(frac) => Ok(frac as FracSec),
+    None => Err(bad_format(s))
   }
+}
 
-  #[test]
-  fn test_decode_timezone() {
-    assert_eq!(-3600, decode_timezone("+1").ok().unwrap());
-    assert_eq!(3600,  decode_timezone("-1").ok().unwrap());
-    assert_eq!(-5400, decode_timezone("+1:30").ok().unwrap());
-    assert_eq!(5400,  decode_timezone("-1:30").ok().unwrap());
+/// Look up a text token in DATETK_TBL.
+/// Return (type, value), with type UNKNOWN_FIELD if not found.
+fn decode_special(field: &[u8]) -> (i8, i32) {
+  match datebsearch(field, &DATETK_TBL) {
+    Some(tk) => (tk.ty, tk.value),
+    None => (UNKNOWN_FIELD, 0)
   }
+}
 
-  #[test]
-  fn test_decode_timezone_failure() {
-    match decode_timezone("+17") {
-      Err(TimezoneOverflow) => {},
-      _ => assert!(false, "Overflow must happen")
-    };
+/// parse_datetime()
+/// Break string into tokens based on a date/time context.
+/// Return the lower-cased fields, their types and the byte range each one
+/// came from in s, or a DateTimeParseError.
+///
+/// Several field types are assigned:
+///  DTK_NUMBER - digits and (possibly) a decimal point
+///  DTK_DATE - digits and two delimiters, or digits and text
+///  DTK_TIME - digits, colon delimiters, and possibly a decimal point
+///  DTK_STRING - text (no digits or punctuation)
+///  DTK_SPECIAL - leading "+" or "-" followed by text
+///  DTK_TZ - leading "+" or "-" followed by digits (also eats ':', '.', '-')
+///
+/// Note that some field types can hold unexpected items:
+///  DTK_NUMBER can hold date fields (yy.ddd)
+///  DTK_STRING can hold months (january) and time zones (pst)
+///  DTK_DATE can hold time zone names (america/new_york, gmt-8)
+pub fn parse_datetime(s: &str)
+                      -> Result<(Vec<Vec<u8>>, Vec<i32>, Vec<Range<usize>>), DateTimeParseError> {
+  let buf = s.as_bytes();
+  let len = buf.len();
+  let mut idx = 0;
+  let mut fields: Vec<Vec<u8>> = Vec::new();
+  let mut ftypes: Vec<i32> = Vec::new();
+  let mut spans: Vec<Range<usize>> = Vec::new();
+
+  // outer loop through fields
+  while idx < len {
+    // Ignore spaces between fields
+    if buf[idx].is_ascii_whitespace() {
+      idx += 1;
+      continue;
+    }
 
-    match decode_timezone("+1:60") {
-      Err(TimezoneOverflow) => {},
-      _ => assert!(false, "Overflow must happen")
-    };
+    if fields.len() >= MAXDATEFIELDS {
+      return Err(bad_format(buf).at(idx..len));
+    }
 
-    match decode_timezone("+1:0:60") {
-      Err(TimezoneOverflow) => {},
-      _ => assert!(false, "Overflow must happen")
-    };
-  }
+    let start = idx;
+    let mut field: Vec<u8> = Vec::new();
+    let ftype;
 
-  #[test]
-  fn test_datebsearch() {
-    assert_eq!(b"april", datebsearch(b"april", &DATETK_TBL).unwrap().token);
-    assert_eq!(b"monday", datebsearch(b"monday", &DATETK_TBL).unwrap().token);
-    assert_eq!(b"friday", datebsearch(b"friday", &DATETK_TBL).unwrap().token);
+    // leading digit? then date or time
+    if isdigit(buf[idx]) {
+      while idx < len && isdigit(buf[idx]) {
+        field.push(buf[idx]);
+        idx += 1;
+      }
 
-    assert!(datebsearch(b"not_found", &DATETK_TBL).is_none());
-  }
-}
\ No newline at end of file
+      // time field?
+      if idx < len && buf[idx] == b':' {
+        ftype = DTK_TIME;
+        while idx < len && (isdigit(buf[idx]) || buf[idx] == b':' || buf[idx] == b'.') {
+          field.push(buf[idx]);
+          idx += 1;
+        }
+
+      // date field? allow embedded text month
+      } else if idx < len && (buf[idx] == b'-' || buf[idx] == b'/' || buf[idx] == b'.') {
+        // save delimiting character to use later
+        let delim = buf[idx];
+        field.push(delim);
+        idx += 1;
+
+        // second field is all digits? then no embedded text month
+        if idx < len && isdigit(buf[idx]) {
+          let mut ty = if delim == b'.' { DTK_NUMBER } else { DTK_DATE };
+          while idx < len && isdigit(buf[idx]) {
+            field.push(buf[idx]);
+            idx += 1;
+          }
+
+          // insist that the delimiters match to get a three-field date.
+          if idx < len && buf[idx] == delim {
+            ty = DTK_DATE;
+            while idx < len && (isdigit(buf[idx]) || buf[idx] == delim) {
+              field.push(buf[idx]);
+              idx += 1;
+            }
+          }
+          ftype = ty;
+        } else {
+          ftype = DTK_DATE;
+          while idx < len && (isalnum(buf[idx]) || buf[idx] == delim) {
+            field.push(buf[idx].to_ascii_lowercase());
+            idx += 1;
+          }
+        }
+
+      // ISO 8601 week date in the basic format, e.g. 2016W455? The "w"
+      // would otherwise be taken as a unit label.
+      } else if idx < len && (buf[idx] == b'W' || buf[idx] == b'w') && field.len() == 4 {
+        ftype = DTK_DATE;
+        while idx < len && isalnum(buf[idx]) {
+          field.push(buf[idx].to_ascii_lowercase());
+          idx += 1;
+        }
+
+      // otherwise, number only and will determine year, month, day, or
+      // concatenated fields later...
+      } else {
+        ftype = DTK_NUMBER;
+      }
+
+    // Leading decimal point? Then fractional seconds...
+    } else if buf[idx] == b'.' {
+      field.push(buf[idx]);
+      idx += 1;
+      while idx < len && isdigit(buf[idx]) {
+        field.push(buf[idx]);
+        idx += 1;
+      }
+      ftype = DTK_NUMBER;
+
+    // text? then date string, month, day of week, special, or timezone
+    } else if isalpha(buf[idx]) {
+      while idx < len && isalpha(buf[idx]) {
+        field.push(buf[idx].to_ascii_lowercase());
+        idx += 1;
+      }
+
+      // Dates can have embedded '-', '/', or '.' separators.  It could
+      // also be a timezone name containing embedded '/', '+', '-', '_',
+      // or ':' (but '_' or ':' can't be the first punctuation). If the
+      // next character is a digit or '+', we need to check whether what
+      // we have so far is a recognized non-timezone keyword --- if so,
+      // don't believe that this is the start of a timezone.
+      let mut is_date = false;
+      if idx < len {
+        let c = buf[idx];
+        if c == b'-' || c == b'/' || c == b'.' {
+          is_date = true;
+        } else if (c == b'+' || isdigit(c)) && datebsearch(&field, &DATETK_TBL).is_none() {
+          is_date = true;
+        }
+      }
+
+      if is_date {
+        ftype = DTK_DATE;
+        loop {
+          field.push(buf[idx].to_ascii_lowercase());
+          idx += 1;
+          if idx == len || !(isalnum(buf[idx]) || b"+-/_.:".contains(&buf[idx])) {
+            break;
+          }
+        }
+      } else {
+        ftype = DTK_STRING;
+      }
+
+    // sign? then special or numeric timezone
+    } else if buf[idx] == b'+' || buf[idx] == b'-' {
+      field.push(buf[idx]);
+      idx += 1;
+
+      // soak up leading whitespace
+      while idx < len && buf[idx].is_ascii_whitespace() {
+        idx += 1;
+      }
+
+      // numeric timezone?
+      // note that "DTK_TZ" could also be a signed float or yyyy-mm
+      if idx < len && isdigit(buf[idx]) {
+        ftype = DTK_TZ;
+        while idx < len && (isdigit(buf[idx]) || buf[idx] == b':' || buf[idx] == b'.' ||
+                            buf[idx] == b'-') {
+          field.push(buf[idx]);
+          idx += 1;
+        }
+
+      // special?
+      } else if idx < len && isalpha(buf[idx]) {
+        ftype = DTK_SPECIAL;
+        while idx < len && isalpha(buf[idx]) {
+          field.push(buf[idx].to_ascii_lowercase());
+          idx += 1;
+        }
+
+      // otherwise something wrong...
+      } else {
+        return Err(bad_format(buf).at(start..idx));
+      }
+
+    // ignore other punctuation but use as delimiter
+    } else if buf[idx].is_ascii_punctuation() {
+      idx += 1;
+      continue;
+
+    // otherwise, something is not right...
+    } else {
+      return Err(bad_format(buf).at(char_at(buf, idx)));
+    }
+
+    fields.push(field);
+    ftypes.push(ftype);
+    spans.push(start..idx);
+  }
+
+  Ok((fields, ftypes, spans))
+}
+
+/// The broken-down current time, in UTC.
+fn get_current_time() -> (TimeMeta, FracSec) {
+  timestamp2tm(get_current_timestamp()).expect("current time out of range")
+}
+
+/// Decode a numeric field that follows a unit label, as in the ISO forms
+/// "y2001m02d04" and "20011225T040506", or the Julian day "J2451187.5".
+/// ptype is the DTK value of the label.
+fn decode_labeled_number(ptype: i32, field: &[u8], fmask: i32, tmask: &mut i32,
+                         tm: &mut TimeMeta, fsec: &mut FracSec, is2digits: &mut bool)
+                         -> Result<(), DateTimeParseError> {
+  let (val, remain) = unsafe { strtoi(field)? };
+
+  // only a few kinds are allowed to have an embedded decimal
+  match remain {
+    Some(r) if r[0] == b'.' => {
+      if ptype != DTK_TIME && ptype != DTK_SECOND && ptype != DTK_JULIAN {
+        return Err(bad_format(field));
+      }
+    }
+    Some(_) => return Err(bad_format(field)),
+    None => {}
+  }
+
+  match ptype {
+    DTK_YEAR => {
+      tm.tm_year = val;
+      *tmask = DTK_M(YEAR);
+    }
+    DTK_MONTH => {
+      // already have a month and hour? then assume minutes
+      if (fmask & DTK_M(MONTH)) != 0 && (fmask & DTK_M(HOUR)) != 0 {
+        tm.tm_min = val;
+        *tmask = DTK_M(MINUTE);
+      } else {
+        tm.tm_mon = val;
+        *tmask = DTK_M(MONTH);
+      }
+    }
+    DTK_DAY => {
+      tm.tm_mday = val;
+      *tmask = DTK_M(DAY);
+    }
+    DTK_HOUR => {
+      tm.tm_hour = val;
+      *tmask = DTK_M(HOUR);
+    }
+    DTK_MINUTE => {
+      tm.tm_min = val;
+      *tmask = DTK_M(MINUTE);
+    }
+    DTK_SECOND => {
+      tm.tm_sec = val;
+      *tmask = DTK_M(SECOND);
+      if let Some(r) = remain {
+        *fsec = decode_fraction(r)?;
+        *tmask = DTK_ALL_SECS_M;
+      }
+    }
+    DTK_JULIAN => {
+      // previous field was a label for "julian date"
+      if val < 0 {
+        return Err(DateTimeParseError::from_bytes(DateTimeErrorKind::FieldOverflow, field));
+      }
+      *tmask = DTK_DATE_M;
+      let (year, mon, mday) = j2date(val);
+      tm.tm_year = year;
+      tm.tm_mon = mon as i32;
+      tm.tm_mday = mday as i32;
+
+      // fractional Julian Day?
+      if let Some(r) = remain {
+        let frac = match parse_fraction_of(&r[1..], USECS_PER_DAY, Rounding::HalfUp) {
+          Some(frac) => frac,
+          None => return Err(bad_format(field))
+        };
+        let (hour, min, sec, frac_sec) = dt2time(frac);
+        tm.tm_hour = hour;
+        tm.tm_min = min;
+        tm.tm_sec = sec;
+        *fsec = frac_sec;
+        *tmask |= DTK_TIME_M;
+      }
+    }
+    DTK_TIME => {
+      // previous field was "t" for ISO time
+      decode_number_field(field.len(), field, fmask | DTK_DATE_M, tmask, tm, fsec, is2digits)?;
+      if *tmask != DTK_TIME_M {
+        return Err(bad_format(field));
+      }
+    }
+    _ => return Err(bad_format(field))
+  }
+
+  Ok(())
+}
+
+/// Decode a run-together time with a trailing numeric time zone, such as
+/// "040506-08". Return the time zone in seconds west of UTC.
+fn decode_time_with_zone(field: &[u8], fmask: i32, tmask: &mut i32, tm: &mut TimeMeta,
+                         fsec: &mut FracSec, is2digits: &mut bool)
+                         -> Result<i32, DateTimeParseError> {
+  // Starts with a digit but we already have a time field? Then we are in
+  // trouble with a date and time already...
+  if !isdigit(field[0]) || (fmask & DTK_TIME_M) == DTK_TIME_M {
+    return Err(bad_format(field));
+  }
+
+  let cp = match field.iter().position(|&c| c == b'-') {
+    Some(cp) => cp,
+    None => return Err(bad_format(field))
+  };
+
+  // Get the time zone from the end of the string
+  let tz = decode_timezone(unsafe { str::from_utf8_unchecked(&field[cp..]) })?;
+
+  // Then read the rest of the field as a concatenated time
+  decode_number_field(cp, &field[..cp], fmask | DTK_DATE_M, tmask, tm, fsec, is2digits)?;
+
+  // modify tmask after returning from decode_number_field()
+  *tmask |= DTK_M(TZ);
+
+  Ok(tz)
+}
+
+/// Split an ISO 8601 week date, "yyyy-www-d" or "yyyywwwd" with the day
+/// optional, into the ISO year, week and day of week (Monday = 1).
+/// Return None if field is not a week date.
+fn split_week_date(field: &[u8]) -> Option<(i32, i32, i32)> {
+  let num = |s: &[u8]| -> Option<i32> {
+    if s.iter().all(|&c| isdigit(c)) {
+      Some(s.iter().fold(0, |n, &c| n * 10 + (c - b'0') as i32))
+    } else {
+      None
+    }
+  };
+
+  if field.len() <= 4 {
+    return None;
+  }
+  let extended = field[4] == b'-';
+  let week = if extended { &field[5..] } else { &field[4..] };
+  if week.len() < 3 || week[0] != b'w' {
+    return None;
+  }
+
+  let day = &week[3..];
+  let dow = match (extended, day.len()) {
+    // a week without a day means its Monday
+    (_, 0) => 1,
+    (true, 2) if day[0] == b'-' => num(&day[1..])?,
+    (false, 1) => num(day)?,
+    _ => return None
+  };
+
+  Some((num(&field[..4])?, num(&week[1..3])?, dow))
+}
+
+/// Set the date fields of tm, in the calendar cal, from an ISO 8601 week
+/// date. The ISO year is Gregorian whatever the calendar.
+fn decode_week_date<C: Calendar>(year: i32, week: i32, dow: i32, tm: &mut TimeMeta, cal: &C)
+                                 -> Result<(), DateTimeErrorKind> {
+  // December 28 always falls in the last week of its ISO year
+  if year < 1 || week < 1 || week > date2isoweek(year, 12, 28) || dow < 1 || dow > 7 {
+    return Err(DateTimeErrorKind::FieldOverflow);
+  }
+
+  // the day count is the same in every calendar
+  let (y, m, d) = cal.j2date(isoweek2j(year, week) + dow - 1);
+  tm.tm_year = y;
+  tm.tm_mon = m as i32;
+  tm.tm_mday = d as i32;
+
+  Ok(())
+}
+
+/// Is field i a bare hour in front of an AM/PM marker, as in "7pm"?
+fn is_meridian_hour(fields: &[Vec<u8>], ftypes: &[i32], i: usize) -> bool {
+  let field = &fields[i];
+  field.len() <= 2 && field.iter().all(|&c| isdigit(c)) &&
+    i + 1 < fields.len() && ftypes[i + 1] == DTK_STRING && decode_special(&fields[i + 1]).0 == AMPM
+}
+
+/// Adjust the hour in tm for an AM/PM marker. 12 AM is midnight and an
+/// hour past 12 cannot take a marker.
+fn apply_meridian(mer: i32, tm: &mut TimeMeta) -> Result<(), DateTimeErrorKind> {
+  if mer != HR24 && tm.tm_hour > HOURS_PER_DAY / 2 {
+    return Err(DateTimeErrorKind::FieldOverflow);
+  }
+  if mer == AM && tm.tm_hour == HOURS_PER_DAY / 2 {
+    tm.tm_hour = 0;
+  } else if mer == PM && tm.tm_hour != HOURS_PER_DAY / 2 {
+    tm.tm_hour += HOURS_PER_DAY / 2;
+  }
+
+  Ok(())
+}
+
+/// decode_datetime()
+/// Interpret previously parsed fields for general date and time.
+/// Return the kind of value found: DTK_DATE for an ordinary date and time,
+/// or DTK_EPOCH, DTK_LATE or DTK_EARLY for the special values.
+///
+/// If the input carries a time zone, its offset (seconds west of UTC) is
+/// stored into tzp; otherwise tzp is None and the caller decides.
+///
+/// External format(s):
+///  "<month>-<day>-<year> <hour>:<minute>:<second>"
+///  "Feb-7-1997 15:23:27"
+///  "2-7-1997 15:23:27"
+///  "1997-2-7 15:23:27"
+///  "1997-02-07T15:23:27+09"
+///  "2016-W45-5 10:00:00"
+///  "Feb 7 1997 7:30 pm"
+///  "Fri, 11 Nov 2016 10:00:00 GMT"
+/// Also supports input in compact time:
+///  "970207 152327"
+///  "20011225T040506.789-07"
+///
+/// A day of week in the input is kept in tm_wday but not checked against
+/// the date; see decode_datetime_strict().
+pub fn decode_datetime(fields: &[Vec<u8>], ftypes: &[i32], tm: &mut TimeMeta,
+                       fsec: &mut FracSec, tzp: &mut Option<i32>)
+                       -> Result<i32, DateTimeParseError> {
+  decode_datetime_cal(fields, ftypes, tm, fsec, tzp, false, &Gregorian)
+}
+
+/// Same as decode_datetime(), but a day of week in the input must fall on
+/// the decoded date, else the error is WeekdayMismatch.
+pub fn decode_datetime_strict(fields: &[Vec<u8>], ftypes: &[i32], tm: &mut TimeMeta,
+                              fsec: &mut FracSec, tzp: &mut Option<i32>)
+                              -> Result<i32, DateTimeParseError> {
+  decode_datetime_cal(fields, ftypes, tm, fsec, tzp, true, &Gregorian)
+}
+
+/// decode_datetime() or, if strict, decode_datetime_strict(), with the date
+/// in tm read and checked in the calendar cal.
+fn decode_datetime_cal<C: Calendar>(fields: &[Vec<u8>], ftypes: &[i32], tm: &mut TimeMeta,
+                                    fsec: &mut FracSec, tzp: &mut Option<i32>, strict: bool,
+                                    cal: &C) -> Result<i32, DateTimeParseError> {
+  let mut cur = None;
+  decode_datetime_fields(fields, ftypes, tm, fsec, tzp, strict, cal, &mut cur)
+    .map_err(|e| field_error(e, fields, ftypes, cur))
+}
+
+/// Attach the field being decoded, if any, to an error.
+fn field_error(e: DateTimeParseError, fields: &[Vec<u8>], ftypes: &[i32], cur: Option<usize>)
+               -> DateTimeParseError {
+  match cur {
+    Some(i) => e.in_field(i, ftypes[i], &fields[i]),
+    None => e
+  }
+}
+
+/// An error about the input as a whole rather than any one field.
+fn fields_error(kind: DateTimeErrorKind, fields: &[Vec<u8>]) -> DateTimeParseError {
+  DateTimeParseError::from_bytes(kind, &fields.join(&b' '))
+}
+
+/// The body of decode_datetime(). strict checks the day of week against the
+/// date, and cal is the calendar of the date. cur tracks the field being
+/// decoded so that errors can be pinned to it.
+fn decode_datetime_fields<C: Calendar>(fields: &[Vec<u8>], ftypes: &[i32], tm: &mut TimeMeta,
+                                       fsec: &mut FracSec, tzp: &mut Option<i32>,
+                                       strict: bool, cal: &C, cur: &mut Option<usize>)
+                          -> Result<i32, DateTimeParseError> {
+  let nf = fields.len();
+  let mut fmask: i32 = 0;
+  let mut tmask: i32;
+  // "prefix type" for ISO y2001m02d04 format
+  let mut ptype: i32 = 0;
+  let mut have_text_month = false;
+  let mut is2digits = false;
+  let mut isjulian = false;
+  let mut bc = false;
+  let mut mer = HR24;
+  // the field holding a day of week, if any
+  let mut dow_field: Option<usize> = None;
+  let mut dtype = DTK_DATE;
+
+  tm.tm_hour = 0;
+  tm.tm_min = 0;
+  tm.tm_sec = 0;
+  *fsec = 0;
+  // don't know daylight savings time status apriori
+  tm.tm_isdst = -1;
+  *tzp = None;
+
+  for i in 0..nf {
+    let field = &fields[i][..];
+    tmask = 0;
+    *cur = Some(i);
+
+    match ftypes[i] {
+      DTK_DATE => {
+        // Already have a date? Then this might be a run-together time with
+        // trailing time zone (e.g. hhmmss-zz). We consider it a time zone
+        // if we already have month & day.
+        if ptype != 0 || (fmask & DTK_MONTH_DAY_M) == DTK_MONTH_DAY_M {
+          // Sanity check; should not fail this test
+          if ptype != 0 && ptype != DTK_TIME {
+            return Err(bad_format(field));
+          }
+          ptype = 0;
+
+          *tzp = Some(decode_time_with_zone(field, fmask, &mut tmask, tm, fsec,
+                                            &mut is2digits)?);
+        } else if let Some((year, week, dow)) = split_week_date(field) {
+          decode_week_date(year, week, dow, tm, cal)
+            .map_err(|kind| DateTimeParseError::from_bytes(kind, field))?;
+          tmask = DTK_DATE_M;
+        } else {
+          let mut dmask = fmask;
+          decode_date(field, &mut dmask, &mut tmask, &mut is2digits, tm)?;
+        }
+      }
+
+      DTK_TIME => {
+        // This might be an ISO time following a "t" field.
+        if ptype != 0 {
+          // Sanity check; should not fail this test
+          if ptype != DTK_TIME {
+            return Err(bad_format(field));
+          }
+          ptype = 0;
+        }
+
+        decode_time(field, &mut tmask, tm, fsec)?;
+      }
+
+      DTK_TZ => {
+        *tzp = Some(decode_timezone(unsafe { str::from_utf8_unchecked(field) })?);
+        tmask = DTK_M(TZ);
+      }
+
+      DTK_NUMBER => {
+        // Was this an "ISO date" with embedded field labels? An example is
+        // "y2001m02d04" - thomas 2001-02-04
+        if ptype != 0 {
+          decode_labeled_number(ptype, field, fmask, &mut tmask, tm, fsec, &mut is2digits)?;
+          if ptype == DTK_JULIAN {
+            isjulian = true;
+            // the day count is the same in every calendar
+            let (y, m, d) = cal.j2date(date2j(tm.tm_year, tm.tm_mon, tm.tm_mday));
+            tm.tm_year = y;
+            tm.tm_mon = m as i32;
+            tm.tm_mday = d as i32;
+          }
+          ptype = 0;
+          dtype = DTK_DATE;
+        } else if (fmask & DTK_DATE_M) == DTK_DATE_M && (fmask & DTK_TIME_M) == 0 &&
+                  is_meridian_hour(fields, ftypes, i) {
+          tm.tm_hour = unsafe { i32::from_bytes(field)? };
+          tmask = DTK_TIME_M;
+        } else {
+          let flen = field.len();
+          let cp = field.iter().position(|&c| c == b'.');
+
+          // Embedded decimal and no date yet?
+          if cp.is_some() && (fmask & DTK_DATE_M) == 0 {
+            let mut dmask = fmask;
+            decode_date(field, &mut dmask, &mut tmask, &mut is2digits, tm)?;
+
+          // embedded decimal and several digits before?
+          } else if cp.map_or(false, |cp| cp > 2) {
+            // Interpret as a concatenated date or time. Set the type field
+            // to allow decoding other fields later. Example: 20011223 or
+            // 040506
+            decode_number_field(flen, field, fmask, &mut tmask, tm, fsec, &mut is2digits)?;
+
+          // Is this a YMD or HMS specification, or a year number? YMD and
+          // HMS are required to be six digits or more, so if it is 5 digits,
+          // it is a year.  If it is six or more digits, we assume it is YMD
+          // or HMS unless no date and no time values have been specified.
+          // This forces 6+ digit years to be at the end of the string, or to
+          // use the ISO date specification.
+          } else if flen >= 6 && ((fmask & DTK_DATE_M) == 0 || (fmask & DTK_TIME_M) == 0) {
+            decode_number_field(flen, field, fmask, &mut tmask, tm, fsec, &mut is2digits)?;
+
+          // otherwise it is a single date/time field...
+          } else {
+            decode_number(flen, field, have_text_month, &mut fmask, &mut tmask, tm, fsec,
+                          &mut is2digits)?;
+          }
+        }
+      }
+
+      DTK_STRING | DTK_SPECIAL => {
+        let (ty, val) = decode_special(field);
+        if ty == IGNORE_DTF {
+          continue;
+        }
+
+        tmask = DTK_M(ty);
+        match ty {
+          RESERV => {
+            // a reserved word stands for a whole date and time, so it
+            // takes no other fields unless it says otherwise below
+            tmask = DTK_DATE_M | DTK_TIME_M | DTK_M(TZ);
+            match val {
+              // "current" is no longer supported
+              DTK_CURRENT => return Err(bad_format(field)),
+              DTK_NOW => {
+                dtype = DTK_DATE;
+                let (cur_tm, cur_fsec) = get_current_time();
+                *tm = cur_tm;
+                *fsec = cur_fsec;
+                *tzp = Some(0);
+              }
+              DTK_YESTERDAY | DTK_TODAY | DTK_TOMORROW => {
+                tmask = DTK_DATE_M;
+                dtype = DTK_DATE;
+                let (cur_tm, _) = get_current_time();
+                // -1, 0 or +1 days from today
+                let offset = val - DTK_TODAY;
+                let (y, m, d) = cal.j2date(date2j(cur_tm.tm_year, cur_tm.tm_mon,
+                                                  cur_tm.tm_mday) + offset);
+                tm.tm_year = y;
+                tm.tm_mon = m as i32;
+                tm.tm_mday = d as i32;
+              }
+              DTK_ZULU => {
+                tmask = DTK_TIME_M | DTK_M(TZ);
+                dtype = DTK_DATE;
+                tm.tm_hour = 0;
+                tm.tm_min = 0;
+                tm.tm_sec = 0;
+                *tzp = Some(0);
+              }
+              _ => dtype = val
+            }
+          }
+
+          MONTH => {
+            // already have a (numeric) month? then see if we can
+            // substitute...
+            if (fmask & DTK_M(MONTH)) != 0 && !have_text_month &&
+                (fmask & DTK_M(DAY)) == 0 && tm.tm_mon >= 1 && tm.tm_mon <= 31 {
+              tm.tm_mday = tm.tm_mon;
+              tmask = DTK_M(DAY);
+            }
+            have_text_month = true;
+            tm.tm_mon = val;
+          }
+
+          ADBC => {
+            bc = val == BC;
+          }
+
+          AMPM => {
+            mer = val;
+          }
+
+          DOW => {
+            tm.tm_wday = val;
+            dow_field = Some(i);
+          }
+
+          DTZMOD => {
+            // daylight savings time modifier (solves "MET DST" syntax)
+            tmask |= DTK_M(DTZ);
+            tm.tm_isdst = 1;
+            match *tzp {
+              Some(tz) => *tzp = Some(tz - val),
+              None => return Err(bad_format(field))
+            }
+          }
+
+          DTZ => {
+            // set mask for TZ here _or_ check for DTZ later when getting
+            // default timezone
+            tmask |= DTK_M(TZ);
+            tm.tm_isdst = 1;
+            *tzp = Some(-val);
+          }
+
+          TZ => {
+            tm.tm_isdst = 0;
+            *tzp = Some(-val);
+          }
+
+          UNITS => {
+            tmask = 0;
+            ptype = val;
+          }
+
+          ISOTIME => {
+            // This is a filler field "t" indicating that the next field is
+            // time. Try to verify that this is sensible.
+            tmask = 0;
+
+            // No preceding date? Then quit...
+            if (fmask & DTK_DATE_M) != DTK_DATE_M {
+              return Err(bad_format(field));
+            }
+
+            // We will need one of the following fields:
+            //  DTK_NUMBER should be hhmmss.fff
+            //  DTK_TIME should be hh:mm:ss.fff
+            //  DTK_DATE should be hhmmss-zz
+            if i >= nf - 1 || (ftypes[i + 1] != DTK_NUMBER && ftypes[i + 1] != DTK_TIME &&
+                               ftypes[i + 1] != DTK_DATE) {
+              return Err(bad_format(field));
+            }
+
+            ptype = val;
+          }
+
+          UNKNOWN_FIELD => return Err(unknown_token(field)),
+          _ => return Err(bad_format(field))
+        }
+      }
+
+      _ => return Err(bad_format(field))
+    }
+
+    if (tmask & fmask) != 0 {
+      return Err(DateTimeParseError::from_bytes(DateTimeErrorKind::AmbiguousField, field));
+    }
+    fmask |= tmask;
+  }
+  *cur = None;
+
+  // a dangling unit label
+  if ptype != 0 {
+    return Err(fields_error(DateTimeErrorKind::BadFormat, fields));
+  }
+
+  // a reserved word such as "epoch" stands alone, and its value is filled
+  // in by the caller
+  if dtype != DTK_DATE {
+    return Ok(dtype);
+  }
+
+  // handle AM/PM
+  if let Err(kind) = apply_meridian(mer, tm) {
+    return Err(fields_error(kind, fields));
+  }
+
+  // do final checking/adjustment of Y/M/D fields
+  if let Err(kind) = validate_date(fmask, isjulian, is2digits, bc, tm, cal) {
+    return Err(fields_error(kind, fields));
+  }
+  if let Err(kind) = validate_time(tm, *fsec) {
+    return Err(fields_error(kind, fields));
+  }
+
+  // do additional checking for full date specs...
+  if dtype == DTK_DATE && (fmask & DTK_DATE_M) != DTK_DATE_M {
+    return Err(fields_error(DateTimeErrorKind::BadFormat, fields));
+  }
+
+  // does the day of week agree with the date?
+  if let Some(i) = dow_field {
+    // a date without a Julian day is left to the range check
+    let julian = cal.checked_date2j(tm.tm_year, tm.tm_mon, tm.tm_mday);
+    if strict && dtype == DTK_DATE && julian.map_or(false, |j| j2day(j) != tm.tm_wday) {
+      *cur = Some(i);
+      return Err(DateTimeParseError::from_bytes(DateTimeErrorKind::WeekdayMismatch,
+                                                &fields[i]));
+    }
+  }
+
+  Ok(dtype)
+}
+
+/// decode_time()
+/// Decode time string which includes delimiters.
+/// Return () if okay, a DateTimeParseError if not.
+fn decode_time(s: &[u8], tmask: &mut i32, tm: &mut TimeMeta, fsec: &mut FracSec)
+               -> Result<(), DateTimeParseError> {
+  *tmask = DTK_TIME_M;
+
+  let (hour, remain) = unsafe { strtoi(s)? };
+  tm.tm_hour = hour;
+
+  let remain = match remain {
+    Some(r) if r[0] == b':' => &r[1..],
+    _ => return Err(bad_format(s))
+  };
+
+  let (min, remain) = unsafe { strtoi(remain)? };
+  tm.tm_min = min;
+
+  match remain {
+    None => {
+      tm.tm_sec = 0;
+      *fsec = 0;
+    }
+    Some(r) if r[0] == b'.' => {
+      // always assume mm:ss.sss is MINUTE TO SECOND
+      *fsec = decode_fraction(r)?;
+      tm.tm_sec = tm.tm_min;
+      tm.tm_min = tm.tm_hour;
+      tm.tm_hour = 0;
+    }
+    Some(r) if r[0] == b':' => {
+      let (sec, remain) = unsafe { strtoi(&r[1..])? };
+      tm.tm_sec = sec;
+      match remain {
+        None => *fsec = 0,
+        Some(r) if r[0] == b'.' => *fsec = decode_fraction(r)?,
+        Some(_) => return Err(bad_format(s))
+      }
+    }
+    Some(_) => return Err(bad_format(s))
+  }
+
+  // do a sanity check
+  validate_time(tm, *fsec).map_err(|kind| DateTimeParseError::from_bytes(kind, s))
+}
+
+/// decode_time_only()
+/// Interpret parsed string as time fields only.
+/// Return DTK_TIME if successful, a DateTimeParseError if not.
+///
+/// A date is accepted only as the first field when a time follows it, and
+/// is otherwise ignored. If the input carries a time zone, its offset
+/// (seconds west of UTC) is stored into tzp.
+pub fn decode_time_only(fields: &[Vec<u8>], ftypes: &[i32], tm: &mut TimeMeta,
+                        fsec: &mut FracSec, tzp: &mut Option<i32>)
+                        -> Result<i32, DateTimeParseError> {
+  let mut cur = None;
+  decode_time_only_fields(fields, ftypes, tm, fsec, tzp, &mut cur)
+    .map_err(|e| field_error(e, fields, ftypes, cur))
+}
+
+/// The body of decode_time_only(). cur tracks the field being decoded so
+/// that errors can be pinned to it.
+fn decode_time_only_fields(fields: &[Vec<u8>], ftypes: &[i32], tm: &mut TimeMeta,
+                           fsec: &mut FracSec, tzp: &mut Option<i32>, cur: &mut Option<usize>)
+                           -> Result<i32, DateTimeParseError> {
+  let nf = fields.len();
+  let mut fmask: i32 = 0;
+  let mut tmask: i32;
+  // "prefix type" for ISO h04mm05s06 format
+  let mut ptype: i32 = 0;
+  let mut is2digits = false;
+  let mut mer = HR24;
+
+  tm.tm_hour = 0;
+  tm.tm_min = 0;
+  tm.tm_sec = 0;
+  *fsec = 0;
+  // don't know daylight savings time status apriori
+  tm.tm_isdst = -1;
+  *tzp = None;
+
+  for i in 0..nf {
+    let field = &fields[i][..];
+    tmask = 0;
+    *cur = Some(i);
+
+    match ftypes[i] {
+      DTK_DATE => {
+        // Under limited circumstances, we will accept a date...
+        if i == 0 && nf >= 2 && (ftypes[nf - 1] == DTK_DATE || ftypes[1] == DTK_TIME) {
+          let mut dmask = fmask;
+          decode_date(field, &mut dmask, &mut tmask, &mut is2digits, tm)?;
+
+        // otherwise, this is a time and/or time zone
+        } else {
+          *tzp = Some(decode_time_with_zone(field, fmask, &mut tmask, tm, fsec,
+                                            &mut is2digits)?);
+        }
+      }
+
+      DTK_TIME => {
+        decode_time(field, &mut tmask, tm, fsec)?;
+      }
+
+      DTK_TZ => {
+        *tzp = Some(decode_timezone(unsafe { str::from_utf8_unchecked(field) })?);
+        tmask = DTK_M(TZ);
+      }
+
+      DTK_NUMBER => {
+        // Was this an "ISO time" with embedded field labels? An example is
+        // "h04mm05s06" - thomas 2001-02-04
+        if ptype != 0 {
+          decode_labeled_number(ptype, field, fmask, &mut tmask, tm, fsec, &mut is2digits)?;
+          ptype = 0;
+        } else if (fmask & DTK_TIME_M) == 0 && is_meridian_hour(fields, ftypes, i) {
+          tm.tm_hour = unsafe { i32::from_bytes(field)? };
+          tmask = DTK_TIME_M;
+        } else {
+          let flen = field.len();
+          match field.iter().position(|&c| c == b'.') {
+            // Embedded decimal?
+            Some(cp) => {
+              // Under limited circumstances, we will accept a date...
+              if i == 0 && nf >= 2 && ftypes[nf - 1] == DTK_DATE {
+                let mut dmask = fmask;
+                decode_date(field, &mut dmask, &mut tmask, &mut is2digits, tm)?;
+
+              // embedded decimal and several digits before?
+              } else if cp > 2 {
+                // Interpret as a concatenated date or time. Example: 040506
+                decode_number_field(flen, field, fmask | DTK_DATE_M, &mut tmask, tm, fsec,
+                                    &mut is2digits)?;
+              } else {
+                return Err(bad_format(field));
+              }
+            }
+            None if flen > 4 => {
+              decode_number_field(flen, field, fmask | DTK_DATE_M, &mut tmask, tm, fsec,
+                                  &mut is2digits)?;
+            }
+            // otherwise it is a single date/time field...
+            None => {
+              let mut dmask = fmask | DTK_DATE_M;
+              decode_number(flen, field, false, &mut dmask, &mut tmask, tm, fsec,
+                            &mut is2digits)?;
+            }
+          }
+        }
+      }
+
+      DTK_STRING | DTK_SPECIAL => {
+        let (ty, val) = decode_special(field);
+        if ty == IGNORE_DTF {
+          continue;
+        }
+
+        tmask = DTK_M(ty);
+        match ty {
+          RESERV => {
+            match val {
+              DTK_NOW => {
+                tmask = DTK_TIME_M;
+                let (cur_tm, cur_fsec) = get_current_time();
+                tm.tm_hour = cur_tm.tm_hour;
+                tm.tm_min = cur_tm.tm_min;
+                tm.tm_sec = cur_tm.tm_sec;
+                *fsec = cur_fsec;
+              }
+              DTK_ZULU => {
+                tmask = DTK_TIME_M | DTK_M(TZ);
+                tm.tm_hour = 0;
+                tm.tm_min = 0;
+                tm.tm_sec = 0;
+                tm.tm_isdst = 0;
+                *tzp = Some(0);
+              }
+              _ => return Err(bad_format(field))
+            }
+          }
+
+          DTZMOD => {
+            // daylight savings time modifier (solves "MET DST" syntax)
+            tmask |= DTK_M(DTZ);
+            tm.tm_isdst = 1;
+            match *tzp {
+              Some(tz) => *tzp = Some(tz - val),
+              None => return Err(bad_format(field))
+            }
+          }
+
+          DTZ => {
+            // set mask for TZ here _or_ check for DTZ later when getting
+            // default timezone
+            tmask |= DTK_M(TZ);
+            tm.tm_isdst = 1;
+            *tzp = Some(-val);
+          }
+
+          TZ => {
+            tm.tm_isdst = 0;
+            *tzp = Some(-val);
+          }
+
+          AMPM => {
+            mer = val;
+          }
+
+          UNITS => {
+            tmask = 0;
+            ptype = val;
+          }
+
+          ISOTIME => {
+            tmask = 0;
+
+            // We will need one of the following fields:
+            //  DTK_NUMBER should be hhmmss.fff
+            //  DTK_TIME should be hh:mm:ss.fff
+            //  DTK_DATE should be hhmmss-zz
+            if i >= nf - 1 || (ftypes[i + 1] != DTK_NUMBER && ftypes[i + 1] != DTK_TIME &&
+                               ftypes[i + 1] != DTK_DATE) {
+              return Err(bad_format(field));
+            }
+
+            ptype = val;
+          }
+
+          UNKNOWN_FIELD => return Err(unknown_token(field)),
+          _ => return Err(bad_format(field))
+        }
+      }
+
+      _ => return Err(bad_format(field))
+    }
+
+    if (tmask & fmask) != 0 {
+      return Err(DateTimeParseError::from_bytes(DateTimeErrorKind::AmbiguousField, field));
+    }
+    fmask |= tmask;
+  }
+  *cur = None;
+
+  if ptype != 0 || (fmask & DTK_TIME_M) != DTK_TIME_M {
+    return Err(fields_error(DateTimeErrorKind::BadFormat, fields));
+  }
+
+  // handle AM/PM
+  if let Err(kind) = apply_meridian(mer, tm) {
+    return Err(fields_error(kind, fields));
+  }
+
+  // do final checking/adjustment of Y/M/D fields
+  if let Err(kind) = validate_date(fmask, false, is2digits, false, tm, &Gregorian) {
+    return Err(fields_error(kind, fields));
+  }
+  if let Err(kind) = validate_time(tm, *fsec) {
+    return Err(fields_error(kind, fields));
+  }
+
+  Ok(DTK_TIME)
+}
+
+/// validate_date()
+/// Check valid year/month/day values, handle BC and DOY cases. The date is
+/// in the calendar cal.
+/// Return () if okay, the kind of error if not.
+fn validate_date<C: Calendar>(fmask: i32, isjulian: bool, is2digits: bool, bc: bool,
+                              tm: &mut TimeMeta, cal: &C) -> Result<(), DateTimeErrorKind> {
+  if (fmask & DTK_M(YEAR)) != 0 {
+    if isjulian {
+      // tm_year is correct and should not be touched
+    } else if bc {
+      // there is no year zero in AD/BC notation
+      if tm.tm_year <= 0 {
+        return Err(DateTimeErrorKind::FieldOverflow);
+      }
+      // internally, we represent 1 BC as year zero, 2 BC as -1, etc.
+      tm.tm_year = -(tm.tm_year - 1);
+    } else if is2digits {
+      // process 1 or 2-digit input as 1970-2069 AD, allow '0' and '00'
+      if tm.tm_year < 0 {
+        // just paranoia
+        return Err(DateTimeErrorKind::FieldOverflow);
+      }
+      if tm.tm_year < 70 {
+        tm.tm_year += 2000;
+      } else if tm.tm_year < 100 {
+        tm.tm_year += 1900;
+      }
+    } else {
+      // there is no year zero in AD/BC notation
+      if tm.tm_year <= 0 {
+        return Err(DateTimeErrorKind::FieldOverflow);
+      }
+    }
+  }
+
+  // now that we have correct year, decode DOY by counting days from the
+  // first of the year, which also skips any days the calendar leaves out.
+  // Years differ in length between calendars, so a day past the end is
+  // found by landing in the next year.
+  if (fmask & DTK_M(DOY)) != 0 {
+    if tm.tm_yday < 1 {
+      return Err(DateTimeErrorKind::FieldOverflow);
+    }
+    let jan1 = match cal.checked_date2j(tm.tm_year, 1, 1) {
+      Some(julian) if cal.j2date(julian) == (tm.tm_year, 1, 1) => julian,
+      _ => return Err(DateTimeErrorKind::OutOfRange)
+    };
+    let (year, mon, mday) = match jan1.checked_add(tm.tm_yday - 1) {
+      Some(julian) => cal.j2date(julian),
+      None => return Err(DateTimeErrorKind::OutOfRange)
+    };
+    if year != tm.tm_year {
+      return Err(DateTimeErrorKind::FieldOverflow);
+    }
+    tm.tm_mon = mon as i32;
+    tm.tm_mday = mday as i32;
+  }
+
+  // check for valid month
+  if (fmask & DTK_M(MONTH)) != 0 &&
+      (tm.tm_mon < 1 || tm.tm_mon > cal.months_in_year(tm.tm_year)) {
+    return Err(DateTimeErrorKind::MdFieldOverflow);
+  }
+
+  // minimal check for valid day
+  if (fmask & DTK_M(DAY)) != 0 && (tm.tm_mday < 1 || tm.tm_mday > 31) {
+    return Err(DateTimeErrorKind::MdFieldOverflow);
+  }
+
+  // Check for valid day of month, now that we know for sure the month and
+  // year. Note we don't use MdFieldOverflow here, since it seems unlikely
+  // that "Feb 29" is a YMD-order error.
+  if (fmask & DTK_DATE_M) == DTK_DATE_M &&
+      !cal.is_valid(tm.tm_year, tm.tm_mon, tm.tm_mday) {
+    return Err(DateTimeErrorKind::FieldOverflow);
+  }
+
+  Ok(())
+}
+
+/// validate_time()
+/// Check the time fields: up to 24:00:00, and a second of 60 is allowed
+/// for leap-second input.
+/// Return () if okay, the kind of error if not.
+fn validate_time(tm: &TimeMeta, fsec: FracSec) -> Result<(), DateTimeErrorKind> {
+  if tm.tm_hour < 0 || tm.tm_min < 0 || tm.tm_min > MINS_PER_HOUR - 1 ||
+      tm.tm_sec < 0 || tm.tm_sec > SECS_PER_MINUTE ||
+      tm.tm_hour > HOURS_PER_DAY ||
+      // test for > 24:00:00
+      (tm.tm_hour == HOURS_PER_DAY && (tm.tm_min > 0 || tm.tm_sec > 0 || fsec > 0)) ||
+      fsec < 0 || fsec as i64 > USECS_PER_SEC {
+    return Err(DateTimeErrorKind::FieldOverflow);
+  }
+
+  Ok(())
+}
+
+/// Read a possibly-signed, possibly-fractional number at the start of an
+/// ISO 8601 interval field. Return the integral and fractional parts and
+/// the rest of the string.
+fn parse_iso8601_number(s: &[u8]) -> Result<(i32, f64, &[u8]), DateTimeParseError> {
+  if s.is_empty() || !(isdigit(s[0]) || s[0] == b'-' || s[0] == b'.') {
+    return Err(bad_format(s).at(char_at(s, 0)));
+  }
+
+  let (val, remain) = match unsafe { strtod(s) } {
+    Ok(r) => r,
+    Err(_) => return Err(bad_format(s).at(char_at(s, 0)))
+  };
+  let len = s.len() - remain.map_or(0, |r| r.len());
+
+  // watch out for overflow
+  if val < ::std::i32::MIN as f64 || val > ::std::i32::MAX as f64 {
+    return Err(DateTimeParseError::from_bytes(DateTimeErrorKind::IntervalOverflow, s).at(0..len));
+  }
+
+  // be very sure we truncate towards zero (cf dtrunc())
+  let ipart = val.trunc() as i32;
+  Ok((ipart, val - ipart as f64, remain.unwrap_or(&[])))
+}
+
+/// Determine number of integral digits in a valid ISO 8601 number field
+/// (we should ignore sign and any fraction part)
+fn iso8601_integer_width(fieldstart: &[u8]) -> usize {
+  // We might have had a leading '-'
+  let digits = if !fieldstart.is_empty() && fieldstart[0] == b'-' {
+    &fieldstart[1..]
+  } else {
+    fieldstart
+  };
+
+  digits.iter().take_while(|&&c| isdigit(c)).count()
+}
+
+/// Multiply frac by scale (to produce seconds) and add to *tm & *fsec.
+/// We assume the caller has already added the integral part.
+fn adjust_fract_seconds(frac: f64, tm: &mut TimeMeta, fsec: &mut FracSec, scale: i32) {
+  if frac == 0f64 {
+    return;
+  }
+
+  let frac = frac * scale as f64;
+  let sec = frac as i32;
+  tm.tm_sec += sec;
+  *fsec += ((frac - sec as f64) * 1000000f64).round() as FracSec;
+}
+
+/// As above, but initial scale produces days
+fn adjust_fract_days(frac: f64, tm: &mut TimeMeta, fsec: &mut FracSec, scale: i32) {
+  if frac == 0f64 {
+    return;
+  }
+
+  let frac = frac * scale as f64;
+  let extra_days = frac as i32;
+  tm.tm_mday += extra_days;
+  adjust_fract_seconds(frac - extra_days as f64, tm, fsec, SECS_PER_DAY);
+}
+
+/// decode_iso8601_interval()
+/// Decode an ISO 8601 time interval of the "format with designators"
+/// (section 4.4.3.2) or "alternative format" (section 4.4.3.3).
+/// Examples:  P1D  for 1 day
+///            PT1H for 1 hour
+///            P2Y6M7DT1H30M for 2 years, 6 months, 7 days 1 hour 30 min
+///            P0002-06-07T01:30:00 the same value in alternative format
+///
+/// A few kinds of inputs are accepted which the ISO standard does not:
+///  Fractional values are allowed in any field, not only the last one.
+///  Negative values are allowed in any field.
+pub fn decode_iso8601_interval(s: &str) -> Result<Interval, DateTimeParseError> {
+  let buf = s.as_bytes();
+  let mut tm = TimeMeta::empty();
+  let mut fsec: FracSec = 0;
+  let mut datepart = true;
+  let mut havefield = false;
+  // offset of a tail of buf
+  let off = |r: &[u8]| buf.len() - r.len();
+
+  if buf.len() < 2 || buf[0] != b'P' {
+    return Err(bad_format(buf));
+  }
+
+  let mut rest = &buf[1..];
+  while !rest.is_empty() {
+    // T indicates the beginning of the time part
+    if rest[0] == b'T' {
+      datepart = false;
+      havefield = false;
+      rest = &rest[1..];
+      continue;
+    }
+
+    let fieldstart = rest;
+    let (val, fval, remain) = parse_iso8601_number(rest)
+      .map_err(|e| e.within(buf, off(rest)..off(rest)))?;
+
+    // A missing unit means we stepped off the end of the string.
+    let unit = if remain.is_empty() { 0 } else { remain[0] };
+    rest = if remain.is_empty() { remain } else { &remain[1..] };
+
+    if datepart {
+      // before T: Y M W D
+      match unit {
+        b'Y' => {
+          tm.tm_year += val;
+          tm.tm_mon += (fval * MONTHS_PER_YEAR as f64) as i32;
+        }
+        b'M' => {
+          tm.tm_mon += val;
+          adjust_fract_days(fval, &mut tm, &mut fsec, DAYS_PER_MONTH);
+        }
+        b'W' => {
+          tm.tm_mday += val * 7;
+          adjust_fract_days(fval, &mut tm, &mut fsec, 7);
+        }
+        b'D' => {
+          tm.tm_mday += val;
+          adjust_fract_seconds(fval, &mut tm, &mut fsec, SECS_PER_DAY);
+        }
+        b'T' | 0 | b'-' => {
+          // ISO 8601 4.4.3.3 Alternative Format / Basic
+          if unit != b'-' && iso8601_integer_width(fieldstart) == 8 && !havefield {
+            tm.tm_year += val / 10000;
+            tm.tm_mon += (val / 100) % 100;
+            tm.tm_mday += val % 100;
+            adjust_fract_seconds(fval, &mut tm, &mut fsec, SECS_PER_DAY);
+            if unit == 0 {
+              break;
+            }
+            datepart = false;
+            havefield = false;
+            continue;
+          }
+
+          // Else, ISO 8601 4.4.3.3 Alternative Format, Extended
+          if havefield {
+            return Err(bad_format(buf).at(off(fieldstart)..off(rest)));
+          }
+
+          tm.tm_year += val;
+          tm.tm_mon += (fval * MONTHS_PER_YEAR as f64) as i32;
+          if unit == 0 {
+            break;
+          }
+          if unit == b'T' {
+            datepart = false;
+            havefield = false;
+            continue;
+          }
+
+          let (val, fval, remain) = parse_iso8601_number(rest)
+            .map_err(|e| e.within(buf, off(rest)..off(rest)))?;
+          tm.tm_mon += val;
+          adjust_fract_days(fval, &mut tm, &mut fsec, DAYS_PER_MONTH);
+          if remain.is_empty() {
+            break;
+          }
+          if remain[0] == b'T' {
+            datepart = false;
+            havefield = false;
+            rest = &remain[1..];
+            continue;
+          }
+          if remain[0] != b'-' {
+            return Err(bad_format(buf).at(char_at(buf, off(remain))));
+          }
+
+          let (val, fval, remain) = parse_iso8601_number(&remain[1..])
+            .map_err(|e| e.within(buf, off(remain) + 1..off(remain) + 1))?;
+          tm.tm_mday += val;
+          adjust_fract_seconds(fval, &mut tm, &mut fsec, SECS_PER_DAY);
+          if remain.is_empty() {
+            break;
+          }
+          if remain[0] == b'T' {
+            datepart = false;
+            havefield = false;
+            rest = &remain[1..];
+            continue;
+          }
+          return Err(bad_format(buf).at(off(remain)..buf.len()));
+        }
+        // not a valid date unit suffix
+        _ => return Err(bad_format(buf).at(off(fieldstart)..off(rest)))
+      }
+    } else {
+      // after T: H M S
+      match unit {
+        b'H' => {
+          tm.tm_hour += val;
+          adjust_fract_seconds(fval, &mut tm, &mut fsec, SECS_PER_HOUR);
+        }
+        b'M' => {
+          tm.tm_min += val;
+          adjust_fract_seconds(fval, &mut tm, &mut fsec, SECS_PER_MINUTE);
+        }
+        b'S' => {
+          tm.tm_sec += val;
+          adjust_fract_seconds(fval, &mut tm, &mut fsec, 1);
+        }
+        0 | b':' => {
+          // ISO 8601 4.4.3.3 Alternative Format
+          if unit == 0 && iso8601_integer_width(fieldstart) == 6 && !havefield {
+            tm.tm_hour += val / 10000;
+            tm.tm_min += (val / 100) % 100;
+            tm.tm_sec += val % 100;
+            adjust_fract_seconds(fval, &mut tm, &mut fsec, 1);
+            break;
+          }
+
+          // Else, ISO 8601 4.4.3.3 Alternative Format, Extended
+          if havefield {
+            return Err(bad_format(buf).at(off(fieldstart)..off(rest)));
+          }
+
+          tm.tm_hour += val;
+          adjust_fract_seconds(fval, &mut tm, &mut fsec, SECS_PER_HOUR);
+          if unit == 0 {
+            break;
+          }
+
+          let (val, fval, remain) = parse_iso8601_number(rest)
+            .map_err(|e| e.within(buf, off(rest)..off(rest)))?;
+          tm.tm_min += val;
+          adjust_fract_seconds(fval, &mut tm, &mut fsec, SECS_PER_MINUTE);
+          if remain.is_empty() {
+            break;
+          }
+          if remain[0] != b':' {
+            return Err(bad_format(buf).at(char_at(buf, off(remain))));
+          }
+
+          let (val, fval, remain) = parse_iso8601_number(&remain[1..])
+            .map_err(|e| e.within(buf, off(remain) + 1..off(remain) + 1))?;
+          tm.tm_sec += val;
+          adjust_fract_seconds(fval, &mut tm, &mut fsec, 1);
+          if remain.is_empty() {
+            break;
+          }
+          return Err(bad_format(buf).at(off(remain)..buf.len()));
+        }
+        // not a valid time unit suffix
+        _ => return Err(bad_format(buf).at(off(fieldstart)..off(rest)))
+      }
+    }
+
+    havefield = true;
+  }
+
+  match tm2interval(&tm, fsec) {
+    Some(span) => Ok(span),
+    None => Err(DateTimeParseError::new(DateTimeErrorKind::IntervalOverflow, s))
+  }
+}
+
+/// Append seconds and fractional seconds (if any) to buf. The fraction is
+/// printed without trailing zeros.
+fn append_seconds(buf: &mut String, sec: i32, fsec: FracSec, fillzeros: bool) {
+  if fillzeros {
+    buf.push_str(&format!("{:02}", sec.abs()));
+  } else {
+    buf.push_str(&format!("{}", sec.abs()));
+  }
+
+  if fsec != 0 {
+    let frac = format!("{:06}", fsec.abs());
+    buf.push('.');
+    buf.push_str(frac.trim_end_matches('0'));
+  }
+}
+
+/// Append a year, zero-padded to four digits. BC years are printed as
+/// positive numbers; the caller adds the " BC" suffix.
+fn append_year(buf: &mut String, year: i32) {
+  let year = if year > 0 { year } else { -(year - 1) };
+  buf.push_str(&format!("{:04}", year));
+}
+
+/// encode_timezone()
+/// Append a representation of a numeric time zone offset (seconds west of
+/// UTC) to buf.
+fn encode_timezone(buf: &mut String, tz: i32, style: DateStyle) {
+  let mut sec = tz.abs();
+  let mut min = sec / SECS_PER_MINUTE;
+  sec -= min * SECS_PER_MINUTE;
+  let hour = min / MINS_PER_HOUR;
+  min -= hour * MINS_PER_HOUR;
+
+  // TZ is negated compared to sign we wish to display ...
+  buf.push(if tz <= 0 { '+' } else { '-' });
+
+  if sec != 0 {
+    buf.push_str(&format!("{:02}:{:02}:{:02}", hour, min, sec));
+  } else if min != 0 || style == DateStyle::XSD {
+    buf.push_str(&format!("{:02}:{:02}", hour, min));
+  } else {
+    buf.push_str(&format!("{:02}", hour));
+  }
+}
+
+/// Day of the year of the date in tm, from 1, in the calendar cal.
+fn day_of_year<C: Calendar>(tm: &TimeMeta, cal: &C) -> i32 {
+  cal.date2j(tm.tm_year, tm.tm_mon, tm.tm_mday) - cal.date2j(tm.tm_year, 1, 1) + 1
+}
+
+/// A copy of tm with the date moved from the calendar cal to the proleptic
+/// Gregorian one, for the formats that are defined on the latter.
+fn gregorian_tm<C: Calendar>(tm: &TimeMeta, cal: &C) -> TimeMeta {
+  let mut greg = tm.clone();
+  to_gregorian(&mut greg, cal);
+  greg
+}
+
+/// ISO 8601 week date of the date in tm, e.g. "2016-W45-5", or "2016W455"
+/// in the basic format.
+pub fn encode_week_date(tm: &TimeMeta, basic: bool) -> String {
+  let mut buf = String::with_capacity(10);
+  let wday = j2day(date2j(tm.tm_year, tm.tm_mon, tm.tm_mday));
+
+  append_year(&mut buf, date2isoyear(tm.tm_year, tm.tm_mon, tm.tm_mday));
+  buf.push_str(&format!("{}W{:02}{}{}",
+                        if basic { "" } else { "-" },
+                        date2isoweek(tm.tm_year, tm.tm_mon, tm.tm_mday),
+                        if basic { "" } else { "-" },
+                        // ISO day of week, Sunday == 7
+                        if wday == 0 { 7 } else { wday }));
+  buf
+}
+
+/// Julian day of the date and time in tm, e.g. "J2451187" at midnight or
+/// "J2451187.5" at noon. As with "J" input, days start at midnight rather
+/// than at noon as in astronomical usage.
+pub fn encode_julian(tm: &TimeMeta, fsec: FracSec) -> String {
+  let mut julian = date2j(tm.tm_year, tm.tm_mon, tm.tm_mday);
+  let mut time = time2t(tm.tm_hour, tm.tm_min, tm.tm_sec, fsec);
+
+  // allow for 24:00:00
+  if time >= USECS_PER_DAY {
+    julian += 1;
+    time -= USECS_PER_DAY;
+  }
+
+  let mut buf = format!("J{}", julian);
+  if time > 0 {
+    // eleven places are enough to tell microseconds apart
+    let frac = format!("{:.11}", time as f64 / USECS_PER_DAY as f64);
+    buf.push_str(frac[1..].trim_end_matches('0'));
+  }
+  buf
+}
+
+/// encode_date_only()
+/// Encode date as local time.
+pub fn encode_date_only(tm: &TimeMeta, style: DateStyle) -> String {
+  encode_date_only_cal(tm, style, &Gregorian)
+}
+
+/// encode_date_only() for a date in the calendar cal. ISO week dates are
+/// always given in the Gregorian calendar.
+pub(crate) fn encode_date_only_cal<C: Calendar>(tm: &TimeMeta, style: DateStyle, cal: &C)
+                                                -> String {
+  debug_assert!(tm.tm_mon >= 1 && tm.tm_mon <= cal.months_in_year(tm.tm_year));
+
+  let mut buf = String::with_capacity(16);
+
+  match style {
+    DateStyle::ISO | DateStyle::XSD => {
+      // compatible with ISO date formats
+      append_year(&mut buf, tm.tm_year);
+      buf.push_str(&format!("-{:02}-{:02}", tm.tm_mon, tm.tm_mday));
+    }
+    DateStyle::Ordinal => {
+      // ISO 8601 ordinal date
+      append_year(&mut buf, tm.tm_year);
+      buf.push_str(&format!("-{:03}", day_of_year(tm, cal)));
+    }
+    DateStyle::IsoWeek => {
+      buf.push_str(&encode_week_date(&gregorian_tm(tm, cal), false));
+    }
+    DateStyle::Julian => {
+      buf.push_str(&format!("J{}", cal.date2j(tm.tm_year, tm.tm_mon, tm.tm_mday)));
+    }
+    DateStyle::SQL => {
+      // compatible with Oracle/Ingres date formats
+      if DATE_ORDER == DateOrder::DMY {
+        buf.push_str(&format!("{:02}/{:02}/", tm.tm_mday, tm.tm_mon));
+      } else {
+        buf.push_str(&format!("{:02}/{:02}/", tm.tm_mon, tm.tm_mday));
+      }
+      append_year(&mut buf, tm.tm_year);
+    }
+    DateStyle::German => {
+      // German-style date format
+      buf.push_str(&format!("{:02}.{:02}.", tm.tm_mday, tm.tm_mon));
+      append_year(&mut buf, tm.tm_year);
+    }
+    DateStyle::Postgres => {
+      // traditional date-only style for Postgres
+      if DATE_ORDER == DateOrder::DMY {
+        buf.push_str(&format!("{:02}-{:02}-", tm.tm_mday, tm.tm_mon));
+      } else {
+        buf.push_str(&format!("{:02}-{:02}-", tm.tm_mon, tm.tm_mday));
+      }
+      append_year(&mut buf, tm.tm_year);
+    }
+  }
+
+  if tm.tm_year <= 0 {
+    buf.push_str(" BC");
+  }
+
+  buf
+}
+
+/// encode_time_only()
+/// Encode time fields only. The time zone (seconds west of UTC) is
+/// printed if given.
+pub fn encode_time_only(tm: &TimeMeta, fsec: FracSec, tz: Option<i32>, style: DateStyle)
+                        -> String {
+  let mut buf = String::with_capacity(24);
+
+  buf.push_str(&format!("{:02}:{:02}:", tm.tm_hour, tm.tm_min));
+  append_seconds(&mut buf, tm.tm_sec, fsec, true);
+
+  if let Some(tz) = tz {
+    encode_timezone(&mut buf, tz, style);
+  }
+
+  buf
+}
+
+/// Encode time fields only on a 12-hour clock, e.g. "07:30:00.5 PM".
+/// Midnight is 12 AM and noon is 12 PM. The time zone (seconds west of
+/// UTC) is printed if given.
+pub fn encode_time_meridian(tm: &TimeMeta, fsec: FracSec, tz: Option<i32>, style: DateStyle)
+                            -> String {
+  let mut buf = String::with_capacity(28);
+  let hour = tm.tm_hour % HOURS_PER_DAY;
+  let hour12 = if hour % 12 == 0 { 12 } else { hour % 12 };
+
+  buf.push_str(&format!("{:02}:{:02}:", hour12, tm.tm_min));
+  append_seconds(&mut buf, tm.tm_sec, fsec, true);
+  buf.push_str(if hour < 12 { " AM" } else { " PM" });
+
+  if let Some(tz) = tz {
+    encode_timezone(&mut buf, tz, style);
+  }
+
+  buf
+}
+
+/// encode_date_time()
+/// Encode date and time interpreted as local time.
+///
+/// tm and fsec are the value to encode. If tz is given, it is the time
+/// zone in seconds west of UTC, and tzn is its abbreviation for the styles
+/// that print one (SQL, German and Postgres). Without tzn those styles
+/// print the numeric offset.
+///
+/// Supported date styles:
+///  Postgres - day mon hh:mm:ss yyyy tz
+///  SQL - mm/dd/yyyy hh:mm:ss.ss tz
+///  ISO - yyyy-mm-dd hh:mm:ss+/-tz
+///  German - dd.mm.yyyy hh:mm:ss tz
+///  XSD - yyyy-mm-ddThh:mm:ss.ss+/-tz
+///  Ordinal - yyyy-ddd hh:mm:ss+/-tz
+///  IsoWeek - yyyy-Www-d hh:mm:ss+/-tz
+///  Julian - Jddddddd.ddddd (no time zone)
+pub fn encode_date_time(tm: &TimeMeta, fsec: FracSec, tz: Option<i32>, tzn: Option<&str>,
+                        style: DateStyle) -> String {
+  encode_date_time_cal(tm, fsec, tz, tzn, style, &Gregorian)
+}
+
+/// encode_date_time() for a date in the calendar cal. ISO week dates are
+/// always given in the Gregorian calendar.
+pub(crate) fn encode_date_time_cal<C: Calendar>(tm: &TimeMeta, fsec: FracSec, tz: Option<i32>,
+                                                tzn: Option<&str>, style: DateStyle, cal: &C)
+                                                -> String {
+  debug_assert!(tm.tm_mon >= 1 && tm.tm_mon <= cal.months_in_year(tm.tm_year));
+
+  let mut buf = String::with_capacity(40);
+
+  match style {
+    DateStyle::ISO | DateStyle::XSD => {
+      // Compatible with ISO-8601 date formats
+      append_year(&mut buf, tm.tm_year);
+      buf.push_str(&format!("-{:02}-{:02}{}{:02}:{:02}:",
+                            tm.tm_mon, tm.tm_mday,
+                            if style == DateStyle::ISO { ' ' } else { 'T' },
+                            tm.tm_hour, tm.tm_min));
+      append_seconds(&mut buf, tm.tm_sec, fsec, true);
+      if let Some(tz) = tz {
+        encode_timezone(&mut buf, tz, style);
+      }
+    }
+    DateStyle::Ordinal | DateStyle::IsoWeek => {
+      if style == DateStyle::Ordinal {
+        // ISO 8601 ordinal date
+        append_year(&mut buf, tm.tm_year);
+        buf.push_str(&format!("-{:03}", day_of_year(tm, cal)));
+      } else {
+        buf.push_str(&encode_week_date(&gregorian_tm(tm, cal), false));
+      }
+      buf.push_str(&format!(" {:02}:{:02}:", tm.tm_hour, tm.tm_min));
+      append_seconds(&mut buf, tm.tm_sec, fsec, true);
+      if let Some(tz) = tz {
+        encode_timezone(&mut buf, tz, style);
+      }
+    }
+    DateStyle::Julian => {
+      buf.push_str(&encode_julian(&gregorian_tm(tm, cal), fsec));
+    }
+    DateStyle::SQL | DateStyle::German => {
+      if style == DateStyle::German {
+        // German variant on European style
+        buf.push_str(&format!("{:02}.{:02}.", tm.tm_mday, tm.tm_mon));
+      } else if DATE_ORDER == DateOrder::DMY {
+        // Compatible with Oracle/Ingres date formats
+        buf.push_str(&format!("{:02}/{:02}/", tm.tm_mday, tm.tm_mon));
+      } else {
+        buf.push_str(&format!("{:02}/{:02}/", tm.tm_mon, tm.tm_mday));
+      }
+      append_year(&mut buf, tm.tm_year);
+      buf.push_str(&format!(" {:02}:{:02}:", tm.tm_hour, tm.tm_min));
+      append_seconds(&mut buf, tm.tm_sec, fsec, true);
+      if let Some(tz) = tz {
+        match tzn {
+          Some(tzn) => {
+            buf.push(' ');
+            buf.push_str(tzn);
+          }
+          None => encode_timezone(&mut buf, tz, style)
+        }
+      }
+    }
+    DateStyle::Postgres => {
+      // Backward-compatible with traditional Postgres abstime dates
+      let wday = j2day(cal.date2j(tm.tm_year, tm.tm_mon, tm.tm_mday));
+      buf.push_str(&DAYS[wday as usize][..3]);
+      buf.push(' ');
+      if DATE_ORDER == DateOrder::DMY {
+        buf.push_str(&format!("{:02} {}", tm.tm_mday, cal.month_name(tm.tm_year, tm.tm_mon)));
+      } else {
+        buf.push_str(&format!("{} {:02}", cal.month_name(tm.tm_year, tm.tm_mon), tm.tm_mday));
+      }
+      buf.push_str(&format!(" {:02}:{:02}:", tm.tm_hour, tm.tm_min));
+      append_seconds(&mut buf, tm.tm_sec, fsec, true);
+      buf.push(' ');
+      append_year(&mut buf, tm.tm_year);
+      if let Some(tz) = tz {
+        buf.push(' ');
+        match tzn {
+          Some(tzn) => buf.push_str(tzn),
+          // We have a time zone, but no string version. Use the numeric
+          // form, but be sure to include a leading space to avoid
+          // formatting something which would be rejected by the date/time
+          // parser later. - thomas 2001-10-19
+          None => encode_timezone(&mut buf, tz, style)
+        }
+      }
+    }
+  }
+
+  if tm.tm_year <= 0 {
+    buf.push_str(" BC");
+  }
+
+  buf
+}
+
+fn add_iso8601_int_part(buf: &mut String, value: i64, units: char) {
+  if value == 0 {
+    return;
+  }
+  buf.push_str(&format!("{}{}", value, units));
+}
+
+fn add_postgres_int_part(buf: &mut String, value: i32, units: &str, is_zero: &mut bool,
+                         is_before: &mut bool) {
+  if value == 0 {
+    return;
+  }
+  buf.push_str(&format!("{}{}{} {}{}",
+                        if !*is_zero { " " } else { "" },
+                        if *is_before && value > 0 { "+" } else { "" },
+                        value,
+                        units,
+                        if value != 1 { "s" } else { "" }));
+
+  // Each nonzero field sets is_before for (only) the next one.  This is a
+  // tad bizarre but it's how it worked before...
+  *is_before = value < 0;
+  *is_zero = false;
+}
+
+/// encode_interval()
+/// Interpret an interval as a delta time and convert to string.
+///
+/// Infinite intervals are printed as "infinity" and "-infinity".
+pub fn encode_interval(span: &Interval, style: IntervalStyle) -> String {
+  if interval_is_nobegin(span) {
+    return String::from_utf8_lossy(EARLY).into_owned();
+  }
+  if interval_is_noend(span) {
+    return String::from_utf8_lossy(LATE).into_owned();
+  }
+
+  let year = span.month / MONTHS_PER_YEAR;
+  let mon = span.month % MONTHS_PER_YEAR;
+  let mday = span.day;
+  let mut time = span.time;
+  let hour = time / USECS_PER_HOUR;
+  time -= hour * USECS_PER_HOUR;
+  let min = time / USECS_PER_MINUTE;
+  time -= min * USECS_PER_MINUTE;
+  let sec = (time / USECS_PER_SEC) as i32;
+  let fsec = (time - sec as i64 * USECS_PER_SEC) as FracSec;
+  let min = min as i32;
+
+  let mut buf = String::with_capacity(32);
+
+  match style {
+    // ISO 8601 "time-intervals by duration only"
+    IntervalStyle::ISO8601 => {
+      // special-case zero to avoid printing nothing
+      if year == 0 && mon == 0 && mday == 0 && hour == 0 && min == 0 && sec == 0 && fsec == 0 {
+        return "PT0S".to_owned();
+      }
+
+      buf.push('P');
+      add_iso8601_int_part(&mut buf, year as i64, 'Y');
+      add_iso8601_int_part(&mut buf, mon as i64, 'M');
+      add_iso8601_int_part(&mut buf, mday as i64, 'D');
+      if hour != 0 || min != 0 || sec != 0 || fsec != 0 {
+        buf.push('T');
+      }
+      add_iso8601_int_part(&mut buf, hour, 'H');
+      add_iso8601_int_part(&mut buf, min as i64, 'M');
+      if sec != 0 || fsec != 0 {
+        if sec < 0 || fsec < 0 {
+          buf.push('-');
+        }
+        append_seconds(&mut buf, sec, fsec, false);
+        buf.push('S');
+      }
+    }
+
+    // Compatible with postgresql < 8.4 when DateStyle = 'iso'
+    IntervalStyle::Postgres => {
+      let mut is_zero = true;
+      let mut is_before = false;
+
+      add_postgres_int_part(&mut buf, year, "year", &mut is_zero, &mut is_before);
+      // Ideally we should spell out "month" like we do for "year" and
+      // "day".  However, for backward compatibility, we can't easily
+      // fix this.  bjm 2011-05-24
+      add_postgres_int_part(&mut buf, mon, "mon", &mut is_zero, &mut is_before);
+      add_postgres_int_part(&mut buf, mday, "day", &mut is_zero, &mut is_before);
+      if is_zero || hour != 0 || min != 0 || sec != 0 || fsec != 0 {
+        let minus = hour < 0 || min < 0 || sec < 0 || fsec < 0;
+        buf.push_str(&format!("{}{}{:02}:{:02}:",
+                              if is_zero { "" } else { " " },
+                              if minus { "-" } else if is_before { "+" } else { "" },
+                              hour.abs(), min.abs()));
+        append_seconds(&mut buf, sec, fsec, true);
+      }
+    }
+  }
+
+  buf
+}
+
+// ---------------------------------------------------------------------------
+// Ported from timestamp.c
+// ---------------------------------------------------------------------------
+
+// Julian day range accepted on input, as in PostgreSQL. date2j() and
+// j2date() themselves cover any i32 Julian day.
+const JULIAN_MINYEAR  :i32 = -4713;
+const JULIAN_MINMONTH :i32 = 11;
+const JULIAN_MINDAY   :i32 = 24;
+const JULIAN_MAXYEAR  :i32 = 5874898;
+const JULIAN_MAXMONTH :i32 = 6;
+const JULIAN_MAXDAY   :i32 = 3;
+
+#[allow(non_snake_case)]
+fn IS_VALID_JULIAN(y: i32, m: i32, _d: i32) -> bool {
+  (y > JULIAN_MINYEAR ||
+    (y == JULIAN_MINYEAR && (m >= JULIAN_MINMONTH))) &&
+  (y < JULIAN_MAXYEAR ||
+    (y == JULIAN_MAXYEAR && (m < JULIAN_MAXMONTH)))
+}
+
+/// Convert hour, minute, second and fractional second to a time offset.
+pub fn time2t(hour: i32, min: i32, sec: i32, fsec: FracSec) -> TimeOffset {
+  ((((hour * MINS_PER_HOUR) + min) * SECS_PER_MINUTE) + sec) as i64 * USECS_PER_SEC
+    + fsec as i64
+}
+
+/// Split a time of day into (hour, minute, second, fractional second).
+pub fn dt2time(jd: TimeOffset) -> (i32, i32, i32, FracSec) {
+  let mut time = jd;
+
+  let hour = time / USECS_PER_HOUR;
+  time -= hour * USECS_PER_HOUR;
+  let min = time / USECS_PER_MINUTE;
+  time -= min * USECS_PER_MINUTE;
+  let sec = time / USECS_PER_SEC;
+  let fsec = time - (sec * USECS_PER_SEC);
+
+  (hour as i32, min as i32, sec as i32, fsec as FracSec)
+}
+
+/// timestamp2tm()
+/// Convert a timestamp to a broken-down time (without time zone).
+/// Return None if the Julian day does not fit the i32 of j2date().
+pub fn timestamp2tm(dt: Timestamp) -> Option<(TimeMeta, FracSec)> {
+  let mut time = dt;
+  let mut date = time / USECS_PER_DAY;
+
+  if date != 0 {
+    time -= date * USECS_PER_DAY;
+  }
+  if time < 0 {
+    time += USECS_PER_DAY;
+    date -= 1;
+  }
+
+  // add offset to go from J2000 back to standard Julian date
+  date += POSTGRES_EPOCH_JDATE as i64;
+
+  if date < ::std::i32::MIN as i64 || date > ::std::i32::MAX as i64 {
+    return None;
+  }
+
+  let mut tm = TimeMeta::empty();
+  let (year, month, day) = j2date(date as i32);
+  tm.tm_year = year;
+  tm.tm_mon = month as i32;
+  tm.tm_mday = day as i32;
+
+  let (hour, min, sec, fsec) = dt2time(time);
+  tm.tm_hour = hour;
+  tm.tm_min = min;
+  tm.tm_sec = sec;
+  tm.tm_isdst = -1;
+
+  Some((tm, fsec))
+}
+
+/// Move the date in tm from the calendar cal to the proleptic Gregorian
+/// one, by way of its Julian day. Return false if the date has no Julian
+/// day in an i32.
+fn to_gregorian<C: Calendar>(tm: &mut TimeMeta, cal: &C) -> bool {
+  let julian = match cal.checked_date2j(tm.tm_year, tm.tm_mon, tm.tm_mday) {
+    Some(julian) if cal.j2date(julian) == (tm.tm_year, tm.tm_mon as u32, tm.tm_mday as u32) => {
+      julian
+    }
+    _ => return false
+  };
+
+  let (year, month, day) = j2date(julian);
+  tm.tm_year = year;
+  tm.tm_mon = month as i32;
+  tm.tm_mday = day as i32;
+  true
+}
+
+/// Move the date in tm from the proleptic Gregorian calendar to cal.
+fn from_gregorian<C: Calendar>(tm: &mut TimeMeta, cal: &C) {
+  let (year, month, day) = cal.j2date(date2j(tm.tm_year, tm.tm_mon, tm.tm_mday));
+  tm.tm_year = year;
+  tm.tm_mon = month as i32;
+  tm.tm_mday = day as i32;
+}
+
+/// tm2timestamp()
+/// Convert a broken-down time to a timestamp. If tzp is given, the result
+/// is shifted from that zone (seconds west of UTC) to UTC.
+/// Return None on overflow.
+pub fn tm2timestamp(tm: &TimeMeta, fsec: FracSec, tzp: Option<i32>) -> Option<Timestamp> {
+  // Prevent overflow in Julian-day routines
+  if !IS_VALID_JULIAN(tm.tm_year, tm.tm_mon, tm.tm_mday) {
+    return None;
+  }
+
+  let date = (date2j(tm.tm_year, tm.tm_mon, tm.tm_mday) - POSTGRES_EPOCH_JDATE) as i64;
+  let time = time2t(tm.tm_hour, tm.tm_min, tm.tm_sec, fsec);
+
+  let mut result = match date.checked_mul(USECS_PER_DAY).and_then(|r| r.checked_add(time)) {
+    Some(r) => r,
+    None => return None
+  };
+
+  // check for just-barely overflow (okay except time-of-day wraps)
+  // caution: we want to allow 1999-12-31 24:00:00
+  if (result < 0 && date > 0) || (result > 0 && date < -1) {
+    return None;
+  }
+
+  if let Some(tz) = tzp {
+    result = match result.checked_add(tz as i64 * USECS_PER_SEC) {
+      Some(r) => r,
+      None => return None
+    };
+  }
+
+  // final range check catches just-out-of-range timestamps
+  if !IS_VALID_TIMESTAMP(result) {
+    return None;
+  }
+
+  Some(result)
+}
+
+/// timestamp_pl_interval()
+/// Add an interval to a timestamp. Months are added first, clamping the
+/// day of month to the end of the resulting month, then days and time.
+/// An infinite interval gives that infinity, and an infinite timestamp is
+/// returned unchanged. Return None on overflow, or for opposite infinities.
+pub fn timestamp_pl_interval(timestamp: Timestamp, span: &Interval) -> Option<Timestamp> {
+  if interval_is_nobegin(span) {
+    return if timestamp_is_noend(timestamp) { None } else { Some(DT_NOBEGIN) };
+  }
+  if interval_is_noend(span) {
+    return if timestamp_is_nobegin(timestamp) { None } else { Some(DT_NOEND) };
+  }
+  if timestamp_not_finite(timestamp) {
+    return Some(timestamp);
+  }
+
+  let mut timestamp = timestamp;
+
+  if span.month != 0 {
+    let (mut tm, fsec) = match timestamp2tm(timestamp) {
+      Some(r) => r,
+      None => return None
+    };
+
+    tm.tm_mon = match tm.tm_mon.checked_add(span.month) {
+      Some(m) => m,
+      None => return None
+    };
+
+    if tm.tm_mon > MONTHS_PER_YEAR {
+      tm.tm_year += (tm.tm_mon - 1) / MONTHS_PER_YEAR;
+      tm.tm_mon = ((tm.tm_mon - 1) % MONTHS_PER_YEAR) + 1;
+    } else if tm.tm_mon < 1 {
+      tm.tm_year += tm.tm_mon / MONTHS_PER_YEAR - 1;
+      tm.tm_mon = tm.tm_mon % MONTHS_PER_YEAR + MONTHS_PER_YEAR;
+    }
+
+    // adjust for end of month boundary problems...
+    let mdays = DAY_TAB[isleap(tm.tm_year) as usize][(tm.tm_mon - 1) as usize];
+    if tm.tm_mday > mdays {
+      tm.tm_mday = mdays;
+    }
+
+    timestamp = match tm2timestamp(&tm, fsec, None) {
+      Some(t) => t,
+      None => return None
+    };
+  }
+
+  let delta = match (span.day as i64).checked_mul(USECS_PER_DAY)
+      .and_then(|d| d.checked_add(span.time)) {
+    Some(d) => d,
+    None => return None
+  };
+
+  match timestamp.checked_add(delta) {
+    Some(t) if IS_VALID_TIMESTAMP(t) => Some(t),
+    _ => None
+  }
+}
+
+/// timestamp_mi_interval()
+/// Subtract an interval from a timestamp. Return None on overflow, or for
+/// equal infinities.
+pub fn timestamp_mi_interval(timestamp: Timestamp, span: &Interval) -> Option<Timestamp> {
+  if interval_is_nobegin(span) {
+    return timestamp_pl_interval(timestamp, &INTERVAL_NOEND);
+  }
+  if interval_is_noend(span) {
+    return timestamp_pl_interval(timestamp, &INTERVAL_NOBEGIN);
+  }
+
+  let neg = match (span.month.checked_neg(), span.day.checked_neg(), span.time.checked_neg()) {
+    (Some(month), Some(day), Some(time)) => Interval::new(month, day, time),
+    _ => return None
+  };
+
+  timestamp_pl_interval(timestamp, &neg)
+}
+
+/// isoweek2j()
+/// Return the Julian day which corresponds to the first day (Monday) of the
+/// given ISO 8601 year and week. Julian days are used to convert between ISO
+/// week dates and Gregorian dates.
+pub fn isoweek2j(year: i32, week: i32) -> i32 {
+  // fourth day of current year
+  let day4 = date2j(year, 1, 4);
+
+  // day0 == offset to first day of week (Monday)
+  let day0 = j2day(day4 - 1);
+
+  ((week - 1) * 7) + (day4 - day0)
+}
+
+/// isoweek2date()
+/// Convert ISO week of year number to date.
+/// The year must be the ISO year!
+pub fn isoweek2date(woy: i32, year: i32) -> (i32, u32, u32) {
+  j2date(isoweek2j(year, woy))
+}
+
+/// isoweekdate2date()
+/// Convert an ISO 8601 week date (ISO year, ISO week) into a Gregorian date.
+/// The day of week is Gregorian (Sunday = 1 through Saturday = 7) so that
+/// weekday strings can be supplied. The year must be the ISO year.
+pub fn isoweekdate2date(isoweek: i32, wday: i32, year: i32) -> (i32, u32, u32) {
+  let mut jday = isoweek2j(year, isoweek);
+
+  // convert Gregorian week start (Sunday=1) to ISO week start (Monday=1)
+  if wday > 1 {
+    jday += wday - 2;
+  } else {
+    jday += 6;
+  }
+
+  j2date(jday)
+}
+
+/// date2isoweek()
+/// Returns ISO week number of year.
+pub fn date2isoweek(year: i32, mon: i32, mday: i32) -> i32 {
+  // current day
+  let dayn = date2j(year, mon, mday);
+
+  // fourth day of current year
+  let mut day4 = date2j(year, 1, 4);
+
+  // day0 == offset to first day of week (Monday)
+  let mut day0 = j2day(day4 - 1);
+
+  // We need the first week containing a Thursday, otherwise this day falls
+  // into the previous year for purposes of counting weeks
+  if dayn < day4 - day0 {
+    day4 = date2j(year - 1, 1, 4);
+    day0 = j2day(day4 - 1);
+  }
+
+  let mut result = (dayn - (day4 - day0)) / 7 + 1;
+
+  // Sometimes the last few days in a year will fall into the first week of
+  // the next year, so check for this.
+  if result >= 52 {
+    day4 = date2j(year + 1, 1, 4);
+    day0 = j2day(day4 - 1);
+
+    if dayn >= day4 - day0 {
+      result = (dayn - (day4 - day0)) / 7 + 1;
+    }
+  }
+
+  result
+}
+
+/// date2isoyear()
+/// Returns ISO 8601 year number.
+/// Note: zero or negative results follow the year-zero-exists convention.
+pub fn date2isoyear(mut year: i32, mon: i32, mday: i32) -> i32 {
+  // current day
+  let dayn = date2j(year, mon, mday);
+
+  // fourth day of current year
+  let mut day4 = date2j(year, 1, 4);
+
+  // day0 == offset to first day of week (Monday)
+  let mut day0 = j2day(day4 - 1);
+
+  // We need the first week containing a Thursday, otherwise this day falls
+  // into the previous year for purposes of counting weeks
+  if dayn < day4 - day0 {
+    day4 = date2j(year - 1, 1, 4);
+    day0 = j2day(day4 - 1);
+    year -= 1;
+  }
+
+  let result = (dayn - (day4 - day0)) / 7 + 1;
+
+  // Sometimes the last few days in a year will fall into the first week of
+  // the next year, so check for this.
+  if result >= 52 {
+    day4 = date2j(year + 1, 1, 4);
+    day0 = j2day(day4 - 1);
+
+    if dayn >= day4 - day0 {
+      year += 1;
+    }
+  }
+
+  year
+}
+
+/// GetCurrentTimestamp()
+/// The current wall-clock time as a timestamp.
+pub fn get_current_timestamp() -> TimestampTz {
+  use std::time::{SystemTime, UNIX_EPOCH};
+
+  let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("system clock before 1970");
+  let usecs = now.as_secs() as i64 * USECS_PER_SEC + now.subsec_micros() as i64;
+
+  usecs - (POSTGRES_EPOCH_JDATE - UNIX_EPOCH_JDATE) as i64 * USECS_PER_DAY
+}
+
+/// SetEpochTimestamp()
+/// Timestamp of 1970-01-01 00:00:00.
+fn set_epoch_timestamp() -> Timestamp {
+  (UNIX_EPOCH_JDATE - POSTGRES_EPOCH_JDATE) as i64 * USECS_PER_DAY
+}
+
+/// a / b rounded towards negative infinity, for b > 0.
+fn div_floor(a: i64, b: i64) -> i64 {
+  let q = a / b;
+  if a % b < 0 { q - 1 } else { q }
+}
+
+/// The timestamp usecs microseconds after the Unix epoch. Return None if
+/// it is out of range, or if usecs is None.
+fn from_unix_usecs(usecs: Option<i64>) -> Option<Timestamp> {
+  match usecs.and_then(|u| u.checked_add(set_epoch_timestamp())) {
+    Some(t) if IS_VALID_TIMESTAMP(t) => Some(t),
+    _ => None
+  }
+}
+
+/// Microseconds from the Unix epoch to t. Return None for infinite t.
+fn to_unix_usecs(t: Timestamp) -> Option<i64> {
+  if timestamp_not_finite(t) {
+    return None;
+  }
+  t.checked_sub(set_epoch_timestamp())
+}
+
+// The Unix time conversions serve both Timestamp and TimestampTz: a
+// Timestamp is taken to be in UTC. Conversions to a coarser unit round
+// towards negative infinity, so that 1969-12-31 23:59:59.5 is second -1.
+
+/// Convert seconds since 1970-01-01 00:00:00 UTC to a timestamp.
+/// Return None on overflow.
+pub fn from_unix_seconds(secs: i64) -> Option<Timestamp> {
+  from_unix_usecs(secs.checked_mul(USECS_PER_SEC))
+}
+
+/// Convert milliseconds since the Unix epoch to a timestamp.
+/// Return None on overflow.
+pub fn from_unix_millis(millis: i64) -> Option<Timestamp> {
+  from_unix_usecs(millis.checked_mul(1000))
+}
+
+/// Convert microseconds since the Unix epoch to a timestamp.
+/// Return None on overflow.
+pub fn from_unix_micros(micros: i64) -> Option<Timestamp> {
+  from_unix_usecs(Some(micros))
+}
+
+/// Convert nanoseconds since the Unix epoch to a timestamp, dropping the
+/// nanoseconds that do not make up a whole microsecond.
+/// Return None on overflow.
+pub fn from_unix_nanos(nanos: i64) -> Option<Timestamp> {
+  from_unix_usecs(Some(div_floor(nanos, 1000)))
+}
+
+/// Seconds since the Unix epoch. Return None for infinite timestamps.
+pub fn to_unix_seconds(t: Timestamp) -> Option<i64> {
+  to_unix_usecs(t).map(|u| div_floor(u, USECS_PER_SEC))
+}
+
+/// Milliseconds since the Unix epoch. Return None for infinite timestamps.
+pub fn to_unix_millis(t: Timestamp) -> Option<i64> {
+  to_unix_usecs(t).map(|u| div_floor(u, 1000))
+}
+
+/// Microseconds since the Unix epoch. Return None for infinite timestamps
+/// and on overflow.
+pub fn to_unix_micros(t: Timestamp) -> Option<i64> {
+  to_unix_usecs(t)
+}
+
+/// Nanoseconds since the Unix epoch. Return None for infinite timestamps
+/// and timestamps too far from 1970 to count in nanoseconds.
+pub fn to_unix_nanos(t: Timestamp) -> Option<i64> {
+  to_unix_usecs(t).and_then(|u| u.checked_mul(1000))
+}
+
+/// float8_timestamptz()
+/// Convert seconds since the Unix epoch, with a fraction, to a timestamp
+/// with time zone, as to_timestamp(double precision) does. The fraction is
+/// rounded to microseconds. Infinities give the infinite timestamps.
+/// Return None for NaN and on overflow. This is the way in for epoch
+/// seconds: as in PostgreSQL, the text input "epoch" takes no other fields.
+pub fn float8_timestamptz(seconds: f64) -> Option<TimestampTz> {
+  if seconds.is_nan() {
+    return None;
+  }
+  if seconds.is_infinite() {
+    return Some(if seconds < 0.0 { DT_NOBEGIN } else { DT_NOEND });
+  }
+
+  let usecs = (seconds * USECS_PER_SEC as f64).round();
+  if usecs < ::std::i64::MIN as f64 || usecs >= ::std::i64::MAX as f64 {
+    return None;
+  }
+  from_unix_usecs(Some(usecs as i64))
+}
+
+/// extract(epoch from timestamp)
+/// Seconds since the Unix epoch, with a fraction. Infinite timestamps give
+/// the infinities.
+pub fn extract_epoch(t: Timestamp) -> f64 {
+  if timestamp_is_nobegin(t) {
+    return ::std::f64::NEG_INFINITY;
+  }
+  if timestamp_is_noend(t) {
+    return ::std::f64::INFINITY;
+  }
+
+  // split into seconds and microseconds first, so that the difference
+  // cannot overflow
+  let secs = div_floor(t, USECS_PER_SEC)
+    - (UNIX_EPOCH_JDATE - POSTGRES_EPOCH_JDATE) as i64 * SECS_PER_DAY as i64;
+  let usecs = t - div_floor(t, USECS_PER_SEC) * USECS_PER_SEC;
+  secs as f64 + usecs as f64 / USECS_PER_SEC as f64
+}
+
+/// tm2interval()
+/// Convert broken-down interval fields to an interval.
+/// Return None on overflow.
+pub fn tm2interval(tm: &TimeMeta, fsec: FracSec) -> Option<Interval> {
+  let months = tm.tm_year as i64 * MONTHS_PER_YEAR as i64 + tm.tm_mon as i64;
+  if months > ::std::i32::MAX as i64 || months < ::std::i32::MIN as i64 {
+    return None;
+  }
+
+  let time = ((((tm.tm_hour as i64 * MINS_PER_HOUR as i64) + tm.tm_min as i64)
+               * SECS_PER_MINUTE as i64) + tm.tm_sec as i64) * USECS_PER_SEC + fsec as i64;
+
+  Some(Interval {
+    time: time,
+    day: tm.tm_mday,
+    month: months as i32
+  })
+}
+
+/// The body of timestamp_in() and its kin. The date in s is in the
+/// calendar cal.
+fn decode_timestamp<C: Calendar>(s: &str, type_name: &'static str, with_tz: bool, strict: bool,
+                                 cal: &C) -> Result<Timestamp, DateTimeParseError> {
+  let (fields, ftypes, spans) = parse_datetime(s).map_err(|e| e.locate(s, type_name, &[]))?;
+  let mut tm = TimeMeta::empty();
+  let mut fsec: FracSec = 0;
+  let mut tz: Option<i32> = None;
+
+  let dtype = decode_datetime_cal(&fields, &ftypes, &mut tm, &mut fsec, &mut tz, strict, cal)
+    .map_err(|e| e.locate(s, type_name, &spans))?;
+  match dtype {
+    DTK_DATE => {
+      // without a zone in the input, the session zone (UTC) applies
+      let tzp = if with_tz { Some(tz.unwrap_or(0)) } else { None };
+      let t = if to_gregorian(&mut tm, cal) { tm2timestamp(&tm, fsec, tzp) } else { None };
+      match t {
+        Some(t) => Ok(t),
+        None => Err(DateTimeParseError::new(DateTimeErrorKind::OutOfRange, s)
+                    .locate(s, type_name, &[]))
+      }
+    }
+    DTK_EPOCH => Ok(set_epoch_timestamp()),
+    DTK_LATE => Ok(DT_NOEND),
+    DTK_EARLY => Ok(DT_NOBEGIN),
+    _ => Err(DateTimeParseError::new(DateTimeErrorKind::BadFormat, s).locate(s, type_name, &[]))
+  }
+}
+
+/// timestamp_in()
+/// Convert a string to internal form. A time zone in the input is ignored.
+pub fn timestamp_in(s: &str) -> Result<Timestamp, DateTimeParseError> {
+  decode_timestamp(s, "timestamp", false, false, &Gregorian)
+}
+
+/// Same as timestamp_in(), but a day of week in the input must match the
+/// date.
+pub fn timestamp_in_strict(s: &str) -> Result<Timestamp, DateTimeParseError> {
+  decode_timestamp(s, "timestamp", false, true, &Gregorian)
+}
+
+/// timestamptz_in()
+/// Convert a string to internal form. Input without a time zone is taken
+/// to be UTC.
+pub fn timestamptz_in(s: &str) -> Result<TimestampTz, DateTimeParseError> {
+  decode_timestamp(s, "timestamp with time zone", true, false, &Gregorian)
+}
+
+/// Same as timestamptz_in(), but a day of week in the input must match the
+/// date.
+pub fn timestamptz_in_strict(s: &str) -> Result<TimestampTz, DateTimeParseError> {
+  decode_timestamp(s, "timestamp with time zone", true, true, &Gregorian)
+}
+
+/// timestamp_out()
+/// Convert a timestamp to external form.
+/// Return None if the timestamp is out of range of timestamp2tm().
+pub fn timestamp_out(timestamp: Timestamp, style: DateStyle) -> Option<String> {
+  timestamp_out_cal(timestamp, style, &Gregorian)
+}
+
+/// timestamp_out() with the date given in the calendar cal.
+fn timestamp_out_cal<C: Calendar>(timestamp: Timestamp, style: DateStyle, cal: &C)
+                                  -> Option<String> {
+  if timestamp_is_nobegin(timestamp) {
+    return Some(String::from_utf8_lossy(EARLY).into_owned());
+  }
+  if timestamp_is_noend(timestamp) {
+    return Some(String::from_utf8_lossy(LATE).into_owned());
+  }
+
+  match timestamp2tm(timestamp) {
+    Some((mut tm, fsec)) => {
+      from_gregorian(&mut tm, cal);
+      Some(encode_date_time_cal(&tm, fsec, None, None, style, cal))
+    }
+    None => None
+  }
+}
+
+/// timestamptz_out()
+/// Convert a timestamp with time zone to external form, in UTC.
+/// Return None if the timestamp is out of range of timestamp2tm().
+pub fn timestamptz_out(timestamp: TimestampTz, style: DateStyle) -> Option<String> {
+  if timestamp_not_finite(timestamp) {
+    return timestamp_out(timestamp, style);
+  }
+
+  match timestamp2tm(timestamp) {
+    Some((tm, fsec)) => Some(encode_date_time(&tm, fsec, Some(0), None, style)),
+    None => None
+  }
+}
+
+/// interval_out()
+/// Convert an interval to external form.
+pub fn interval_out(span: &Interval, style: IntervalStyle) -> String {
+  encode_interval(span, style)
+}
+
+// Rounding of microseconds to a precision, by its number of digits
+const TIMESTAMP_SCALES  :[i64;7] = [1000000, 100000, 10000, 1000, 100, 10, 1];
+const TIMESTAMP_OFFSETS :[i64;7] = [500000, 50000, 5000, 500, 50, 5, 0];
+
+/// Round microseconds to precision fractional digits of a second, halves
+/// away from zero. Return None on overflow.
+fn round_to_precision(usecs: i64, precision: i32) -> Option<i64> {
+  let scale = TIMESTAMP_SCALES[precision as usize];
+  let offset = TIMESTAMP_OFFSETS[precision as usize];
+
+  if usecs >= 0 {
+    usecs.checked_add(offset).map(|u| u / scale * scale)
+  } else {
+    usecs.checked_neg().and_then(|u| u.checked_add(offset)).map(|u| -(u / scale * scale))
+  }
+}
+
+/// AdjustTimestampForTypmod()
+/// Round off a timestamp to suit the given typmod, its precision, or -1
+/// for none. Infinite timestamps are returned unchanged.
+/// Return None if the typmod is out of range, or if the timestamp is out
+/// of range before or after rounding.
+pub fn adjust_timestamp_for_typmod(time: Timestamp, typmod: i32) -> Option<Timestamp> {
+  if timestamp_not_finite(time) {
+    return Some(time);
+  }
+  if !IS_VALID_TIMESTAMP(time) {
+    return None;
+  }
+  if typmod == -1 || typmod == MAX_TIMESTAMP_PRECISION {
+    return Some(time);
+  }
+  if typmod < 0 || typmod > MAX_TIMESTAMP_PRECISION {
+    return None;
+  }
+
+  match round_to_precision(time, typmod) {
+    Some(t) if IS_VALID_TIMESTAMP(t) => Some(t),
+    _ => None
+  }
+}
+
+/// intervaltypmodin()
+/// The typmod of an interval type with the given fields and precision,
+/// e.g. `interval day to second(3)`, or -1 if it has neither. A precision
+/// above MAX_INTERVAL_PRECISION is reduced to it.
+/// Return None for a negative precision.
+pub fn interval_typmod(fields: Option<IntervalFields>, precision: Option<i32>) -> Option<i32> {
+  let range = match fields {
+    Some(f) => f.range(),
+    None => INTERVAL_FULL_RANGE
+  };
+
+  match precision {
+    Some(p) if p < 0 => None,
+    Some(p) if p > MAX_INTERVAL_PRECISION => Some(INTERVAL_TYPMOD(MAX_INTERVAL_PRECISION, range)),
+    Some(p) => Some(INTERVAL_TYPMOD(p, range)),
+    None if range == INTERVAL_FULL_RANGE => Some(-1),
+    None => Some(INTERVAL_TYPMOD(INTERVAL_FULL_PRECISION, range))
+  }
+}
+
+/// AdjustIntervalForTypmod()
+/// Adjust an interval to suit the given typmod: fields below the lowest one
+/// of its range are dropped, then the seconds are rounded to its precision.
+/// Infinite intervals and a typmod of -1 leave the interval unchanged.
+/// Return None if the typmod is not valid, or on overflow.
+pub fn adjust_interval_for_typmod(span: &Interval, typmod: i32) -> Option<Interval> {
+  if interval_not_finite(span) || typmod < 0 {
+    return Some(*span);
+  }
+
+  let range = INTERVAL_RANGE(typmod);
+  let precision = INTERVAL_PRECISION(typmod);
+  let mut result = *span;
+
+  // Our interpretation of intervals with a limited set of fields is that
+  // fields to the right of the last one specified are zeroed out, but
+  // those to the left of it remain valid.
+  if range == INTERVAL_FULL_RANGE {
+    // Do nothing...
+  } else if range == IntervalFields::Year.range() {
+    result.month = (result.month / MONTHS_PER_YEAR) * MONTHS_PER_YEAR;
+    result.day = 0;
+    result.time = 0;
+  } else if range == IntervalFields::Month.range() ||
+            range == IntervalFields::YearToMonth.range() {
+    result.day = 0;
+    result.time = 0;
+  } else if range == IntervalFields::Day.range() {
+    result.time = 0;
+  } else if range == IntervalFields::Hour.range() ||
+            range == IntervalFields::DayToHour.range() {
+    result.time = (result.time / USECS_PER_HOUR) * USECS_PER_HOUR;
+  } else if range == IntervalFields::Minute.range() ||
+            range == IntervalFields::DayToMinute.range() ||
+            range == IntervalFields::HourToMinute.range() {
+    result.time = (result.time / USECS_PER_MINUTE) * USECS_PER_MINUTE;
+  } else if range == IntervalFields::Second.range() ||
+            range == IntervalFields::DayToSecond.range() ||
+            range == IntervalFields::HourToSecond.range() ||
+            range == IntervalFields::MinuteToSecond.range() {
+    // fractional-second rounding will be dealt with below
+  } else {
+    // unrecognized interval typmod
+    return None;
+  }
+
+  // Need to adjust sub-second precision?
+  if precision != INTERVAL_FULL_PRECISION {
+    if precision < 0 || precision > MAX_INTERVAL_PRECISION {
+      return None;
+    }
+    result.time = match round_to_precision(result.time, precision) {
+      Some(t) => t,
+      None => return None
+    };
+  }
+
+  Some(result)
+}
+
+// ---------------------------------------------------------------------------
+// Ported from date.c
+// ---------------------------------------------------------------------------
+
+/// date_in()
+/// Given date text string, convert to internal date format.
+pub fn date_in(s: &str) -> Result<Date, DateTimeParseError> {
+  decode_date_text(s, false, &Gregorian)
+}
+
+/// Same as date_in(), but a day of week in the input must match the date.
+pub fn date_in_strict(s: &str) -> Result<Date, DateTimeParseError> {
+  decode_date_text(s, true, &Gregorian)
+}
+
+/// The body of date_in() and date_in_strict(). The date in s is in the
+/// calendar cal.
+fn decode_date_text<C: Calendar>(s: &str, strict: bool, cal: &C)
+                                 -> Result<Date, DateTimeParseError> {
+  let (fields, ftypes, spans) = parse_datetime(s).map_err(|e| e.locate(s, "date", &[]))?;
+  let mut tm = TimeMeta::empty();
+  let mut fsec: FracSec = 0;
+  let mut tz: Option<i32> = None;
+
+  let dtype = decode_datetime_cal(&fields, &ftypes, &mut tm, &mut fsec, &mut tz, strict, cal)
+    .map_err(|e| e.locate(s, "date", &spans))?;
+  match dtype {
+    DTK_DATE => {}
+    DTK_EPOCH => return Ok(UNIX_EPOCH_JDATE - POSTGRES_EPOCH_JDATE),
+    DTK_LATE => return Ok(DATEVAL_NOEND),
+    DTK_EARLY => return Ok(DATEVAL_NOBEGIN),
+    _ => return Err(DateTimeParseError::new(DateTimeErrorKind::BadFormat, s)
+                    .locate(s, "date", &[]))
+  }
+
+  // Prevent overflow in Julian-day routines
+  if !to_gregorian(&mut tm, cal) || !IS_VALID_JULIAN(tm.tm_year, tm.tm_mon, tm.tm_mday) {
+    return Err(DateTimeParseError::new(DateTimeErrorKind::OutOfRange, s)
+               .locate(s, "date", &[]));
+  }
+
+  // Now check for just-out-of-range dates
+  let date = date2j(tm.tm_year, tm.tm_mon, tm.tm_mday) - POSTGRES_EPOCH_JDATE;
+  if !IS_VALID_DATE(date) {
+    return Err(DateTimeParseError::new(DateTimeErrorKind::OutOfRange, s)
+               .locate(s, "date", &[]));
+  }
+
+  Ok(date)
+}
+
+/// date_out()
+/// Given internal format date, convert to text string.
+/// Return None if the date is past the last Julian day j2date() takes.
+pub fn date_out(date: Date, style: DateStyle) -> Option<String> {
+  date_out_cal(date, style, &Gregorian)
+}
+
+/// date_out() with the date given in the calendar cal.
+fn date_out_cal<C: Calendar>(date: Date, style: DateStyle, cal: &C) -> Option<String> {
+  if date_is_nobegin(date) {
+    return Some(String::from_utf8_lossy(EARLY).into_owned());
+  }
+  if date_is_noend(date) {
+    return Some(String::from_utf8_lossy(LATE).into_owned());
+  }
+
+  let julian = match date.checked_add(POSTGRES_EPOCH_JDATE) {
+    Some(j) => j,
+    None => return None
+  };
+
+  let mut tm = TimeMeta::empty();
+  let (year, month, day) = cal.j2date(julian);
+  tm.tm_year = year;
+  tm.tm_mon = month as i32;
+  tm.tm_mday = day as i32;
+
+  Some(encode_date_only_cal(&tm, style, cal))
+}
+
+/// time_in()
+/// Given time text string, convert to internal time format. A time zone in
+/// the input is ignored.
+pub fn time_in(s: &str) -> Result<Time, DateTimeParseError> {
+  let (fields, ftypes, spans) = parse_datetime(s).map_err(|e| e.locate(s, "time", &[]))?;
+  let mut tm = TimeMeta::empty();
+  let mut fsec: FracSec = 0;
+  let mut tz: Option<i32> = None;
+
+  decode_time_only(&fields, &ftypes, &mut tm, &mut fsec, &mut tz)
+    .map_err(|e| e.locate(s, "time", &spans))?;
+
+  Ok(time2t(tm.tm_hour, tm.tm_min, tm.tm_sec, fsec))
+}
+
+/// time_out()
+/// Given internal format time, convert to text string.
+pub fn time_out(time: Time, style: DateStyle) -> String {
+  let mut tm = TimeMeta::empty();
+  let (hour, min, sec, fsec) = dt2time(time);
+  tm.tm_hour = hour;
+  tm.tm_min = min;
+  tm.tm_sec = sec;
+
+  encode_time_only(&tm, fsec, None, style)
+}
+
+/// timetz_in()
+/// Given time text string with an optional numeric time zone, convert to
+/// internal form. Input without a time zone is taken to be UTC.
+pub fn timetz_in(s: &str) -> Result<TimeTz, DateTimeParseError> {
+  let type_name = "time with time zone";
+  let (fields, ftypes, spans) = parse_datetime(s).map_err(|e| e.locate(s, type_name, &[]))?;
+  let mut tm = TimeMeta::empty();
+  let mut fsec: FracSec = 0;
+  let mut tz: Option<i32> = None;
+
+  decode_time_only(&fields, &ftypes, &mut tm, &mut fsec, &mut tz)
+    .map_err(|e| e.locate(s, type_name, &spans))?;
+
+  Ok(TimeTz::new(time2t(tm.tm_hour, tm.tm_min, tm.tm_sec, fsec), tz.unwrap_or(0)))
+}
+
+/// timetz_out()
+/// Given internal format time with time zone, convert to text string.
+pub fn timetz_out(time: &TimeTz, style: DateStyle) -> String {
+  let mut tm = TimeMeta::empty();
+  let (hour, min, sec, fsec) = dt2time(time.time);
+  tm.tm_hour = hour;
+  tm.tm_min = min;
+  tm.tm_sec = sec;
+
+  encode_time_only(&tm, fsec, Some(time.zone), style)
+}
+
+/// AdjustTimeForTypmod()
+/// Round off a time to suit the given typmod, its precision. A typmod out
+/// of range, such as -1, leaves the time unchanged.
+pub fn adjust_time_for_typmod(time: Time, typmod: i32) -> Time {
+  if typmod >= 0 && typmod <= MAX_TIME_PRECISION {
+    let scale = TIMESTAMP_SCALES[typmod as usize];
+    (time + TIMESTAMP_OFFSETS[typmod as usize]) / scale * scale
+  } else {
+    time
+  }
+}
+
+/// timetz_pl_interval()
+/// Add interval to timetz. Only the time part of the interval counts; the
+/// result wraps around midnight and keeps the time zone.
+pub fn timetz_pl_interval(time: &TimeTz, span: &Interval) -> TimeTz {
+  let mut result = time.time + span.time % USECS_PER_DAY;
+  result -= result / USECS_PER_DAY * USECS_PER_DAY;
+  if result < 0 {
+    result += USECS_PER_DAY;
+  }
+
+  TimeTz::new(result, time.zone)
+}
+
+/// timetz_mi_interval()
+/// Subtract interval from timetz.
+pub fn timetz_mi_interval(time: &TimeTz, span: &Interval) -> TimeTz {
+  // negate the time part only, so that the minimum value cannot overflow
+  timetz_pl_interval(time, &Interval::new(0, 0, -(span.time % USECS_PER_DAY)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::DateTimeErrorKind::*;
+
+  fn assert_decode_date(s: &str, year: i32, month: i32, day: i32, is2digity: bool) {
+    let mut tmask: i32 = 0;
+    let mut fmask: i32 = 0;
+    let mut is2digits: bool = false;
+    let mut tm = TimeMeta::empty();
+    decode_date(s.as_bytes(), &mut tmask, &mut fmask, &mut is2digits, &mut tm).ok().unwrap();
+    println!("{:?}", tm);
+    assert_eq!(tm.tm_year, year);
+    assert_eq!(tm.tm_mon, month);
+    assert_eq!(tm.tm_mday, day);
+    assert_eq!(is2digits, is2digity);
+  }
+
+  #[test]
+  fn test_decode_date() {
+    assert_decode_date("Feb-7-1997", 1997, 2, 7, false);
+    assert_decode_date("2-7-1997", 1997, 2, 7, false);
+    assert_decode_date("1997-2-7", 1997, 2, 7, false);
+    //decode_date("Feb-7-1997".as_bytes(), &mut tmask, &mut fmask, &mut is2digits, &mut tm);
+    //decode_date("2-7-1997".as_bytes(), &mut tmask, &mut fmask, &mut is2digits, &mut tm);
+    //decode_date("1997-2-7".as_bytes(), &mut tmask, &mut fmask, &mut is2digits, &mut tm);
+    //decode_date("1997.038".as_bytes(), &mut tmask, &mut fmask, &mut is2digits, &mut tm);
+  }
+
+  #[test]
+  fn test_parse_fractional_second() {
+    assert_eq!(123450, parse_fractional_second(".12345").ok().unwrap());
+    assert_eq!(500000, parse_fractional_second(".5").ok().unwrap());
+    assert_eq!(1, parse_fractional_second(".0000005").ok().unwrap());
+    assert_eq!(1000000, parse_fractional_second(".9999995").ok().unwrap());
+    assert_eq!(123457, parse_fractional_second(".123456789").ok().unwrap());
+  }
+
+  #[test]
+  fn test_parse_fraction() {
+    assert_eq!(Some(123456789), parse_fraction(b"123456789", 9, Rounding::HalfUp));
+    assert_eq!(Some(120000000), parse_fraction(b"12", 9, Rounding::HalfUp));
+    assert_eq!(Some(3), parse_fraction(b"0000025", 6, Rounding::HalfUp));
+    assert_eq!(Some(2), parse_fraction(b"0000025", 6, Rounding::HalfEven));
+    assert_eq!(Some(4), parse_fraction(b"0000035", 6, Rounding::HalfEven));
+    assert_eq!(Some(3), parse_fraction(b"00000250000000000000000000001", 6, Rounding::HalfEven));
+    assert_eq!(Some(2), parse_fraction(b"0000029999999999999999999", 6, Rounding::Floor));
+    assert_eq!(Some(0), parse_fraction(b"4999999999", 0, Rounding::HalfUp));
+    assert_eq!(Some(1), parse_fraction(b"5", 0, Rounding::HalfUp));
+    assert_eq!(Some(0), parse_fraction(b"5", 0, Rounding::HalfEven));
+    assert_eq!(None, parse_fraction(b"", 6, Rounding::HalfUp));
+    assert_eq!(None, parse_fraction(b"12a", 6, Rounding::HalfUp));
+    assert_eq!(None, parse_fraction(b"-1", 6, Rounding::HalfUp));
+
+    assert_eq!(Some(30 * USECS_PER_MINUTE), parse_fraction_of(b"5", USECS_PER_HOUR, Rounding::HalfUp));
+    assert_eq!(Some(20 * USECS_PER_MINUTE),
+               parse_fraction_of(b"33333333333333333333333333334", USECS_PER_HOUR, Rounding::HalfUp));
+    assert_eq!(Some(1), parse_fraction_of(b"5", 3, Rounding::Floor));
+    assert_eq!(Some(2), parse_fraction_of(b"5", 3, Rounding::HalfUp));
+    assert_eq!(Some(2), parse_fraction_of(b"5", 3, Rounding::HalfEven));
+    assert_eq!(Some(2), parse_fraction_of(b"5", 5, Rounding::HalfEven));
+    assert_eq!(Some(3), parse_fraction_of(b"50001", 5, Rounding::HalfEven));
+    assert_eq!(parse_fraction(b"0000025", 6, Rounding::HalfEven),
+               parse_fraction_of(b"0000025", 1000000, Rounding::HalfEven));
+    assert_eq!(None, parse_fraction_of(b"", USECS_PER_DAY, Rounding::HalfUp));
+    assert_eq!(None, parse_fraction_of(b"5e1", USECS_PER_DAY, Rounding::HalfUp));
+
+    // the text decoders read the same fractions
+    let t = timestamp_in("2016-11-11 10:30:15").ok().unwrap();
+    assert_eq!(t + 123457, timestamp_in("2016-11-11 10:30:15.1234565").ok().unwrap());
+    assert_eq!(t + 123457, timestamp_in("20161111T103015.1234565").ok().unwrap());
+    assert_eq!(t + 1, timestamp_in("2016-11-11 10:30:15.00000050").ok().unwrap());
+  }
+
+  /// xorshift64*, so that the property tests need no dependencies
+  struct Rng(u64);
+
+  impl Rng {
+    fn next(&mut self) -> u64 {
+      self.0 ^= self.0 >> 12;
+      self.0 ^= self.0 << 25;
+      self.0 ^= self.0 >> 27;
+      self.0.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+      self.next() % n
+    }
+  }
+
+  /// parse_fraction() by exact rational arithmetic on up to 28 digits.
+  fn reference_fraction(digits: &[u8], precision: u32, rounding: Rounding) -> i64 {
+    let n = digits.iter().fold(0u128, |n, &c| n * 10 + (c - b'0') as u128);
+    let num = n * 10u128.pow(precision);
+    let den = 10u128.pow(digits.len() as u32);
+    let (q, r) = ((num / den) as i64, num % den);
+
+    match rounding {
+      Rounding::Floor => q,
+      Rounding::HalfUp => if 2 * r >= den { q + 1 } else { q },
+      Rounding::HalfEven => {
+        if 2 * r > den || (2 * r == den && q % 2 != 0) { q + 1 } else { q }
+      }
+    }
+  }
+
+  #[test]
+  fn test_parse_fraction_properties() {
+    let mut rng = Rng(0x9E3779B97F4A7C15);
+    let roundings = [Rounding::HalfUp, Rounding::HalfEven, Rounding::Floor];
+
+    for _ in 0..20000 {
+      // favour runs of 0, 4, 5 and 9, where rounding is decided
+      let len = 1 + rng.below(28) as usize;
+      let digits: Vec<u8> = (0..len).map(|_| {
+        match rng.below(4) {
+          0 => b"0459"[rng.below(4) as usize],
+          _ => b'0' + rng.below(10) as u8
+        }
+      }).collect();
+      let precision = [0, 3, 6, 9][rng.below(4) as usize];
+      let rounding = roundings[rng.below(3) as usize];
+
+      let frac = parse_fraction(&digits, precision, rounding).unwrap();
+      assert_eq!(reference_fraction(&digits, precision, rounding), frac,
+                 "{} {} {:?}", String::from_utf8_lossy(&digits), precision, rounding);
+      assert!(frac >= 0 && frac <= 10i64.pow(precision));
+
+      // trailing zeros change nothing
+      let mut padded = digits.clone();
+      padded.extend_from_slice(b"000");
+      assert_eq!(Some(frac), parse_fraction(&padded, precision, rounding));
+
+      // rounding only ever moves by one unit
+      let floor = parse_fraction(&digits, precision, Rounding::Floor).unwrap();
+      assert!(frac == floor || frac == floor + 1);
+
+      // digits that fit are read back unchanged
+      if len <= precision as usize {
+        assert_eq!(floor, frac);
+      }
+    }
+
+    // microseconds written with six digits parse back to themselves
+    for _ in 0..10000 {
+      let usecs = rng.below(1000000) as i64;
+      let s = format!(".{:06}", usecs);
+      assert_eq!(usecs, parse_fractional_second(&s).ok().unwrap());
+      assert_eq!(usecs as FracSec, decode_fraction(s.as_bytes()).ok().unwrap());
+    }
+  }
+
+  #[test]
+  fn test_parse_fractional_second_fail1() {
+    let err = parse_fractional_second(".inv").err().unwrap();
+    assert_eq!(BadFormat, err.kind());
+    assert_eq!("invalid input syntax: '.inv'", err.to_string());
+  }
+
+  #[test]
+  fn test_j2day() {
+    let jd = date2j(2016, 11, 11);
+    assert_eq!(5, j2day(jd));
+  }
+
+  #[test]
+  fn test_decode_timezone() {
+    assert_eq!(-3600, decode_timezone("+1").ok().unwrap());
+    assert_eq!(3600,  decode_timezone("-1").ok().unwrap());
+    assert_eq!(-5400, decode_timezone("+1:30").ok().unwrap());
+    assert_eq!(5400,  decode_timezone("-1:30").ok().unwrap());
+  }
+
+  #[test]
+  fn test_decode_timezone_failure() {
+    match decode_timezone("+17") {
+      Err(ref e) if e.kind() == TzDispOverflow => {},
+      _ => assert!(false, "Overflow must happen")
+    };
+
+    match decode_timezone("+1:60") {
+      Err(ref e) if e.kind() == TzDispOverflow => {},
+      _ => assert!(false, "Overflow must happen")
+    };
+
+    match decode_timezone("+1:0:60") {
+      Err(ref e) if e.kind() == TzDispOverflow => {},
+      _ => assert!(false, "Overflow must happen")
+    };
+  }
+
+  #[test]
+  fn test_datebsearch() {
+    for w in DATETK_TBL.windows(2) {
+      assert!(w[0].token < w[1].token);
+    }
+
+    assert_eq!(b"april", datebsearch(b"april", &DATETK_TBL).unwrap().token);
+    assert_eq!(b"monday", datebsearch(b"monday", &DATETK_TBL).unwrap().token);
+    assert_eq!(b"friday", datebsearch(b"friday", &DATETK_TBL).unwrap().token);
+
+    assert!(datebsearch(b"not_found", &DATETK_TBL).is_none());
+  }
+
+  fn date(y: i32, m: i32, d: i32) -> Date {
+    date2j(y, m, d) - POSTGRES_EPOCH_JDATE
+  }
+
+  #[test]
+  fn test_parse_datetime() {
+    let (fields, ftypes, spans) = parse_datetime("2016-11-11T10:00:00.5+09").ok().unwrap();
+    assert_eq!(vec![b"2016-11-11".to_vec(), b"t".to_vec(), b"10:00:00.5".to_vec(),
+                    b"+09".to_vec()], fields);
+    assert_eq!(vec![DTK_DATE, DTK_STRING, DTK_TIME, DTK_TZ], ftypes);
+    assert_eq!(vec![0..10, 10..11, 11..21, 21..24], spans);
+
+    let (fields, ftypes, _) = parse_datetime("Feb 7, 1997 -infinity").ok().unwrap();
+    assert_eq!(vec![b"feb".to_vec(), b"7".to_vec(), b"1997".to_vec(), b"-infinity".to_vec()],
+               fields);
+    assert_eq!(vec![DTK_STRING, DTK_NUMBER, DTK_NUMBER, DTK_SPECIAL], ftypes);
+  }
+
+  #[test]
+  fn test_date_in() {
+    let d = date(1997, 2, 7);
+    assert_eq!(d, date_in("1997-02-07").ok().unwrap());
+    assert_eq!(d, date_in("Feb-7-1997").ok().unwrap());
+    assert_eq!(d, date_in("7 Feb 1997").ok().unwrap());
+    assert_eq!(d, date_in("19970207").ok().unwrap());
+    assert_eq!(d, date_in("y1997m2d7").ok().unwrap());
+    assert_eq!(d, date_in("1997-02-07 23:59:59+09").ok().unwrap());
+    assert_eq!(DATEVAL_NOEND, date_in("infinity").ok().unwrap());
+    assert_eq!(DATEVAL_NOBEGIN, date_in("-infinity").ok().unwrap());
+    assert_eq!(date(1970, 1, 1), date_in("epoch").ok().unwrap());
+
+    assert!(date_in("1997-02").is_err());
+    assert!(date_in("1997-02-07 bogus").is_err());
+    assert!(date_in("current").is_err());
+  }
+
+  #[test]
+  fn test_timestamp_in() {
+    let midnight = date(2016, 11, 11) as i64 * USECS_PER_DAY;
+    let ts = midnight + 10 * USECS_PER_HOUR + 500000;
+
+    assert_eq!(ts, timestamp_in("2016-11-11 10:00:00.5").ok().unwrap());
+    assert_eq!(ts, timestamp_in("2016-11-11T10:00:00.5").ok().unwrap());
+    assert_eq!(ts, timestamp_in("20161111T100000.5").ok().unwrap());
+    assert_eq!(ts, timestamp_in("Nov 11 2016 10:00:00.5").ok().unwrap());
+    // the zone is ignored without time zone
+    assert_eq!(ts, timestamp_in("2016-11-11 10:00:00.5+09").ok().unwrap());
+    assert_eq!(midnight, timestamp_in("2016-11-11").ok().unwrap());
+
+    assert_eq!(ts, timestamptz_in("2016-11-11 10:00:00.5").ok().unwrap());
+    assert_eq!(ts - 9 * USECS_PER_HOUR,
+               timestamptz_in("2016-11-11 10:00:00.5+09").ok().unwrap());
+    assert_eq!(ts + 5 * USECS_PER_HOUR + 30 * USECS_PER_MINUTE,
+               timestamptz_in("2016-11-11 10:00:00.5-05:30").ok().unwrap());
+    assert_eq!(ts - 9 * USECS_PER_HOUR,
+               timestamptz_in("20161111T100000.5+09").ok().unwrap());
+    assert_eq!(DT_NOEND, timestamptz_in("infinity").ok().unwrap());
+
+    assert!(timestamp_in("10:00:00").is_err());
+    assert!(timestamp_in("2016-11-11 10:00:00 t").is_err());
+
+    // a reserved word takes no other fields
+    assert!(timestamp_in("epoch 5").is_err());
+    assert!(timestamp_in("epoch 2016-11-11").is_err());
+    assert!(timestamp_in("now epoch").is_err());
+    assert!(timestamp_in("infinity 2016-11-11").is_err());
+    assert!(timestamp_in("2016-11-11 -infinity").is_err());
+    assert!(timestamptz_in("epoch +09").is_err());
+    assert!(date_in("epoch 2016-11-11").is_err());
+    assert_eq!(midnight, timestamp_in("2016-11-11 allballs").ok().unwrap());
+  }
+
+  fn assert_error(r: Result<Timestamp, DateTimeParseError>, kind: DateTimeErrorKind,
+                  bad: &str, ftype: Option<i32>) {
+    let err = r.err().unwrap();
+    assert_eq!(kind, err.kind());
+    assert_eq!(bad, &err.input()[err.span().unwrap()]);
+    assert_eq!(ftype, err.field_type());
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    assert_error(timestamp_in("2016-11-11 bogus"), UnknownToken, "bogus", Some(DTK_STRING));
+    assert_error(timestamp_in("2016-11-11 10:00 11:00"), AmbiguousField, "11:00",
+                 Some(DTK_TIME));
+    assert_error(timestamptz_in("2016-11-11 10:00+17"), TzDispOverflow, "+17", Some(DTK_TZ));
+    assert_error(timestamp_in("2016-11-11 10:00 é"), BadFormat, "é", None);
+    assert_error(date_in("Feb-7-1997 10:00 Foo").map(|d| d as Timestamp), UnknownToken, "Foo",
+                 Some(DTK_STRING));
+    // spans inside a field point at the offending part
+    assert_error(date_in("  Feb-7-19x7").map(|d| d as Timestamp), UnknownToken, "x",
+                 Some(DTK_DATE));
+    assert_error(date_in("1997-02-07-05").map(|d| d as Timestamp), BadFormat, "05",
+                 Some(DTK_DATE));
+    assert_error(date_in("Fox-7-1997").map(|d| d as Timestamp), UnknownToken, "Fox",
+                 Some(DTK_DATE));
+
+    let err = date_in("1997-02").err().unwrap();
+    assert_eq!((BadFormat, Some(0..7)), (err.kind(), err.span()));
+    assert_eq!("22007", err.code());
+    assert_eq!("invalid input syntax for type date: '1997-02'", err.to_string());
+    // missing fields are not the fault of any one field
+    assert_eq!(None, date_in("10:00").err().unwrap().span());
+
+    let err = timestamptz_in("2016-11-11 10:00+17").err().unwrap();
+    assert_eq!("22009", err.code());
+    assert_eq!("time zone displacement out of range for type timestamp with time zone: \
+                '2016-11-11 10:00+17'", err.to_string());
+
+    let err = decode_iso8601_interval("P1Y2X").err().unwrap();
+    assert_eq!((BadFormat, Some(3..5)), (err.kind(), err.span()));
+    let err = decode_iso8601_interval("P99999999999Y").err().unwrap();
+    assert_eq!((IntervalOverflow, Some(1..12)), (err.kind(), err.span()));
+    assert_eq!("22015", err.code());
+  }
+
+  fn error_kind<T>(r: Result<T, DateTimeParseError>) -> DateTimeErrorKind {
+    r.err().unwrap().kind()
+  }
+
+  #[test]
+  fn test_validate_date() {
+    let err = date_in("1997-13-45").err().unwrap();
+    assert_eq!(MdFieldOverflow, err.kind());
+    assert!(err.hint().is_some());
+    // the day is 97 under YMD order
+    assert_eq!(MdFieldOverflow, error_kind(date_in("02/07/97")));
+
+    assert_eq!(FieldOverflow, error_kind(date_in("1997-02-30")));
+    assert_eq!(FieldOverflow, error_kind(date_in("1900-02-29")));
+    assert_eq!(date(2000, 2, 29), date_in("2000-02-29").ok().unwrap());
+
+    // there is no year zero
+    assert_eq!(FieldOverflow, error_kind(date_in("0000-01-01")));
+    assert_eq!(FieldOverflow, error_kind(date_in("0000-01-01 BC")));
+    assert_eq!(date(-43, 3, 15), date_in("0044-03-15 BC").ok().unwrap());
+    assert_eq!(date(1997, 2, 7), date_in("Feb 7 1997 AD").ok().unwrap());
+    assert_eq!(AmbiguousField, error_kind(date_in("0044-03-15 BC AD")));
+
+    // two-digit years are 1970-2069
+    assert_eq!(date(1997, 2, 7), date_in("97-02-07").ok().unwrap());
+    assert_eq!(date(2007, 2, 7), date_in("07-02-07").ok().unwrap());
+    assert_eq!(date(2069, 1, 1), date_in("69-01-01").ok().unwrap());
+    assert_eq!(date(1970, 1, 1), date_in("70-01-01").ok().unwrap());
+    assert_eq!(date(1997, 2, 7), date_in("970207").ok().unwrap());
+
+    assert_eq!(OutOfRange, error_kind(date_in("5874898-12-31")));
+  }
+
+  #[test]
+  fn test_day_of_year() {
+    let d = date(1997, 2, 7);
+    assert_eq!(d, date_in("1997.038").ok().unwrap());
+    assert_eq!(d, date_in("1997-038").ok().unwrap());
+    assert_eq!(d, date_in("1997038").ok().unwrap());
+    assert_eq!(d, date_in("1997 038").ok().unwrap());
+    assert_eq!(date(1997, 1, 1), date_in("1997-001").ok().unwrap());
+    assert_eq!(date(2016, 12, 31), date_in("2016-366").ok().unwrap());
+    assert_eq!(date(2015, 12, 31), date_in("2015-365").ok().unwrap());
+    assert_eq!(date(-43, 3, 15), date_in("0044-074 BC").ok().unwrap());
+    assert_eq!(d as i64 * USECS_PER_DAY + time2t(10, 30, 0, 0),
+               timestamp_in("1997038T103000").ok().unwrap());
+
+    assert_eq!(FieldOverflow, error_kind(date_in("2015-366")));
+    assert_eq!(FieldOverflow, error_kind(date_in("2015367")));
+    assert_eq!(FieldOverflow, error_kind(date_in("2016-367")));
+    assert_eq!(FieldOverflow, error_kind(date_in("2016-999")));
+    assert!(date_in("1997-000").is_err());
+
+    assert_eq!("1997-038", date_out(d, DateStyle::Ordinal).unwrap());
+    assert_eq!("2016-366", date_out(date(2016, 12, 31), DateStyle::Ordinal).unwrap());
+    let ts = d as i64 * USECS_PER_DAY + time2t(7, 37, 16, 0);
+    assert_eq!("1997-038 07:37:16", timestamp_out(ts, DateStyle::Ordinal).unwrap());
+    assert_eq!("1997-038 07:37:16+00", timestamptz_out(ts, DateStyle::Ordinal).unwrap());
+
+    for s in ["1997-038", "2016-366", "2000-001"].iter() {
+      assert_eq!(*s, date_out(date_in(s).ok().unwrap(), DateStyle::Ordinal).unwrap());
+    }
+  }
+
+  #[test]
+  fn test_iso_week() {
+    assert_eq!(45, date2isoweek(2016, 11, 11));
+    assert_eq!(2016, date2isoyear(2016, 11, 11));
+    // January 1, 2016 is a Friday in the last week of 2015
+    assert_eq!(53, date2isoweek(2016, 1, 1));
+    assert_eq!(2015, date2isoyear(2016, 1, 1));
+    // December 29, 2008 is a Monday in the first week of 2009
+    assert_eq!(1, date2isoweek(2008, 12, 29));
+    assert_eq!(2009, date2isoyear(2008, 12, 29));
+
+    assert_eq!(date2j(2008, 12, 29), isoweek2j(2009, 1));
+    assert_eq!((2008, 12, 29), isoweek2date(1, 2009));
+    // Friday is 6 counting from Sunday = 1
+    assert_eq!((2016, 11, 11), isoweekdate2date(45, 6, 2016));
+    assert_eq!((2016, 11, 13), isoweekdate2date(45, 1, 2016));
+  }
+
+  #[test]
+  fn test_week_date() {
+    let d = date(2016, 11, 11);
+    assert_eq!(d, date_in("2016-W45-5").ok().unwrap());
+    assert_eq!(d, date_in("2016W455").ok().unwrap());
+    assert_eq!(date(2016, 11, 7), date_in("2016-w45").ok().unwrap());
+    assert_eq!(date(2016, 11, 7), date_in("2016W45").ok().unwrap());
+    assert_eq!(date(2016, 1, 3), date_in("2015-W53-7").ok().unwrap());
+    assert_eq!(d as i64 * USECS_PER_DAY + time2t(10, 0, 0, 0),
+               timestamp_in("2016-W45-5 10:00").ok().unwrap());
+
+    // 2016 has only 52 weeks
+    assert_eq!(FieldOverflow, error_kind(date_in("2016-W53-1")));
+    assert_eq!(FieldOverflow, error_kind(date_in("2016-W45-8")));
+    assert_eq!(FieldOverflow, error_kind(date_in("2016-W00-1")));
+    assert!(date_in("2016-W455").is_err());
+
+    let tm = timestamp2tm(d as i64 * USECS_PER_DAY).unwrap().0;
+    assert_eq!("2016-W45-5", encode_week_date(&tm, false));
+    assert_eq!("2016W455", encode_week_date(&tm, true));
+    assert_eq!("2015-W53-5", date_out(date(2016, 1, 1), DateStyle::IsoWeek).unwrap());
+    assert_eq!("2016-W45-5 10:00:00",
+               timestamp_out(d as i64 * USECS_PER_DAY + time2t(10, 0, 0, 0),
+                             DateStyle::IsoWeek).unwrap());
+
+    for s in ["2016-W45-5", "2015-W53-7", "2009-W01-1", "2020-W53-4"].iter() {
+      assert_eq!(*s, date_out(date_in(s).ok().unwrap(), DateStyle::IsoWeek).unwrap());
+    }
+  }
+
+  /// The calendar day after (y, m, d).
+  fn next_day((y, m, d): (i32, u32, u32)) -> (i32, u32, u32) {
+    if d < DAY_TAB[isleap(y) as usize][m as usize - 1] as u32 {
+      (y, m, d + 1)
+    } else if m < 12 {
+      (y, m + 1, 1)
+    } else {
+      (y + 1, 1, 1)
+    }
+  }
+
+  /// Check that date2j() inverts j2date() over the days from..=to, and
+  /// that each day follows the one before.
+  fn assert_julian_days(from: i32, to: i32) {
+    let mut prev = j2date(from);
+    assert_eq!(from, date2j(prev.0, prev.1 as i32, prev.2 as i32));
+    for j in from + 1..=to {
+      let ymd = j2date(j);
+      assert_eq!(next_day(prev), ymd, "j2date({})", j);
+      assert_eq!(j, date2j(ymd.0, ymd.1 as i32, ymd.2 as i32), "{:?}", ymd);
+      prev = ymd;
+    }
+  }
+
+  #[test]
+  fn test_j2date() {
+    assert_eq!((-4713, 11, 24), j2date(0));
+    assert_eq!((-4713, 11, 23), j2date(-1));
+    assert_eq!((-4800, 3, 1), j2date(-32044));
+    assert_eq!((-4800, 2, 29), j2date(-32045));
+    assert_eq!((-4801, 12, 31), j2date(-32105));
+    assert_eq!((2000, 1, 1), j2date(POSTGRES_EPOCH_JDATE));
+    assert_eq!((5874898, 6, 3), j2date(::std::i32::MAX));
+    assert_eq!((-5884323, 5, 15), j2date(::std::i32::MIN));
+    assert_eq!(::std::i32::MIN, date2j(-5884323, 5, 15));
+    assert_eq!(::std::i32::MAX, date2j(5874898, 6, 3));
+    assert_eq!(Some(::std::i32::MAX), checked_date2j(5874898, 6, 3));
+    assert_eq!(None, checked_date2j(5874898, 6, 4));
+    assert_eq!(None, checked_date2j(-5884323, 5, 14));
+    assert_eq!(None, checked_date2j(::std::i32::MAX, 1, 1));
+
+    // around the Julian epoch, the year -4800 of the old arithmetic, the
+    // Postgres epoch and both ends of the range
+    assert_julian_days(-1 << 20, 1 << 20);
+    assert_julian_days(POSTGRES_EPOCH_JDATE - 400000, POSTGRES_EPOCH_JDATE + 400000);
+    assert_julian_days(::std::i32::MIN, ::std::i32::MIN + 400000);
+    assert_julian_days(::std::i32::MAX - 400000, ::std::i32::MAX);
+
+    // the calendar repeats every 400 years
+    for j in (-1 << 20..1 << 20).step_by(997) {
+      let (y, m, d) = j2date(j);
+      assert_eq!((y - 400, m, d), j2date(j - 146097));
+      assert_eq!((y - 400 * 10000, m, d), j2date(j - 146097 * 10000));
+    }
+  }
+
+  // Every i32 Julian day. Takes a while, so run it with
+  // cargo test --release -- --ignored
+  #[test]
+  #[ignore]
+  fn test_j2date_exhaustive() {
+    assert_julian_days(::std::i32::MIN, ::std::i32::MAX);
+  }
+
+  #[test]
+  fn test_julian_day() {
+    let d = date(1999, 1, 8);
+    assert_eq!(2451187, date2j(1999, 1, 8));
+    assert_eq!(d, date_in("J2451187").ok().unwrap());
+    assert_eq!(d, date_in("jd 2451187").ok().unwrap());
+    assert_eq!(d, date_in("julian 2451187").ok().unwrap());
+
+    let noon = d as i64 * USECS_PER_DAY + time2t(12, 0, 0, 0);
+    assert_eq!(noon, timestamp_in("julian 2451187.5").ok().unwrap());
+    assert_eq!(noon, timestamp_in("J2451187.5").ok().unwrap());
+    assert_eq!(d as i64 * USECS_PER_DAY + time2t(6, 0, 0, 0),
+               timestamp_in("J2451187.25").ok().unwrap());
+    // read exactly, to the nearest microsecond
+    assert_eq!(d as i64 * USECS_PER_DAY + 1,
+               timestamp_in("J2451187.0000000000057871").ok().unwrap());
+    assert_eq!(d as i64 * USECS_PER_DAY,
+               timestamp_in("J2451187.0000000000057870").ok().unwrap());
+    assert!(timestamp_in("J2451187.5x").is_err());
+    assert_eq!(BadFormat, error_kind(timestamp_in("J2451187.5e-1")));
+    assert_eq!(BadFormat, error_kind(timestamp_in("J2451187.")));
+
+    assert_eq!(MIN_DATE, date_in("J0").ok().unwrap());
+    assert_eq!("4714-11-24 BC", date_out(date_in("J0").ok().unwrap(), DateStyle::ISO).unwrap());
+    assert_eq!(AmbiguousField, error_kind(date_in("J2451187 J2451188")));
+    assert_eq!(AmbiguousField, error_kind(timestamp_in("J2451187.5 10:00")));
+
+    assert_eq!("J2451187", date_out(d, DateStyle::Julian).unwrap());
+    assert_eq!("J2451187", timestamp_out(d as i64 * USECS_PER_DAY, DateStyle::Julian).unwrap());
+    assert_eq!("J2451187.5", timestamp_out(noon, DateStyle::Julian).unwrap());
+    assert_eq!("J2451187.5", timestamptz_out(noon, DateStyle::Julian).unwrap());
+
+    // fractional days round trip to the microsecond
+    for t in [1, noon + 1, noon + 123456789, noon + 43199999999].iter() {
+      let s = timestamp_out(*t, DateStyle::Julian).unwrap();
+      assert_eq!(*t, timestamp_in(&s).ok().unwrap());
+    }
+  }
+
+  #[test]
+  fn test_timetz() {
+    let t = timetz_in("12:34:56+05:30").ok().unwrap();
+    assert_eq!(time2t(12, 34, 56, 0), t.time());
+    assert_eq!(-19800, t.zone());
+    assert_eq!("12:34:56+05:30", timetz_out(&t, DateStyle::ISO));
+    assert_eq!("12:34:56.5-08", timetz_out(&timetz_in("12:34:56.5 -8").ok().unwrap(),
+                                           DateStyle::ISO));
+
+    // no zone means UTC
+    assert_eq!(0, timetz_in("12:34:56").ok().unwrap().zone());
+    assert_eq!(TzDispOverflow, error_kind(timetz_in("12:34:56+17")));
+    assert_eq!("unrecognized date/time token for type time with time zone: '12:3x'",
+               timetz_in("12:3x").err().unwrap().to_string());
+
+    // comparison is by UTC time, then by zone
+    let noon_cet = timetz_in("12:00+01").ok().unwrap();
+    let eleven_utc = timetz_in("11:00+00").ok().unwrap();
+    assert!(noon_cet != eleven_utc);
+    assert!(noon_cet < eleven_utc);
+    assert!(timetz_in("10:00+00").ok().unwrap() < noon_cet);
+    assert!(timetz_in("12:00-01").ok().unwrap() > noon_cet);
+    assert_eq!(noon_cet, timetz_in("12:00:00.000+01:00").ok().unwrap());
+
+    // arithmetic wraps around midnight and keeps the zone
+    let late = timetz_in("23:30+05:30").ok().unwrap();
+    let hour = Interval::new(0, 0, USECS_PER_HOUR);
+    assert_eq!("00:30:00+05:30", timetz_out(&timetz_pl_interval(&late, &hour), DateStyle::ISO));
+    assert_eq!("22:30:00+05:30", timetz_out(&timetz_mi_interval(&late, &hour), DateStyle::ISO));
+    assert_eq!(late, timetz_pl_interval(&late, &Interval::new(1, 1, USECS_PER_DAY)));
+    assert_eq!("00:30:00+05:30",
+               timetz_out(&timetz_mi_interval(&late, &Interval::new(0, 0, -25 * USECS_PER_HOUR)),
+                          DateStyle::ISO));
+    assert_eq!(late, timetz_mi_interval(&timetz_pl_interval(&late, &INTERVAL_NOEND),
+                                        &INTERVAL_NOEND));
+  }
+
+  #[test]
+  fn test_meridian() {
+    assert_eq!(time2t(0, 5, 0, 0), time_in("12:05 am").ok().unwrap());
+    assert_eq!(time2t(12, 5, 0, 0), time_in("12:05 pm").ok().unwrap());
+    assert_eq!(time2t(19, 0, 0, 0), time_in("7pm").ok().unwrap());
+    assert_eq!(time2t(7, 0, 0, 0), time_in("7 AM").ok().unwrap());
+    assert_eq!(time2t(0, 0, 0, 0), time_in("12am").ok().unwrap());
+    assert_eq!(time2t(19, 30, 0, 500000), time_in("07:30:00.5 PM").ok().unwrap());
+    assert_eq!(time2t(13, 0, 0, 0), time_in("13:00").ok().unwrap());
+    assert_eq!(date(1997, 2, 7) as i64 * USECS_PER_DAY + time2t(19, 30, 0, 0),
+               timestamp_in("Feb 7 1997 7:30 pm").ok().unwrap());
+    assert_eq!(date(2016, 11, 11) as i64 * USECS_PER_DAY + time2t(19, 0, 0, 0),
+               timestamp_in("2016-11-11 7pm").ok().unwrap());
+
+    let t = timetz_in("11:00 pm +01").ok().unwrap();
+    assert_eq!(time2t(23, 0, 0, 0), t.time());
+    assert_eq!(-3600, t.zone());
+
+    assert_eq!(FieldOverflow, error_kind(time_in("13:00 pm")));
+    assert_eq!(FieldOverflow, error_kind(timestamp_in("2016-11-11 13:00 am")));
+    assert_eq!(AmbiguousField, error_kind(time_in("7 am pm")));
+    assert!(time_in("pm").is_err());
+
+    let encode = |t: Time| {
+      let mut tm = TimeMeta::empty();
+      let (hour, min, sec, fsec) = dt2time(t);
+      tm.tm_hour = hour;
+      tm.tm_min = min;
+      tm.tm_sec = sec;
+      encode_time_meridian(&tm, fsec, None, DateStyle::ISO)
+    };
+    assert_eq!("12:05:00 AM", encode(time2t(0, 5, 0, 0)));
+    assert_eq!("12:00:00 PM", encode(time2t(12, 0, 0, 0)));
+    assert_eq!("07:30:00.5 PM", encode(time2t(19, 30, 0, 500000)));
+    assert_eq!("11:59:59 PM", encode(time2t(23, 59, 59, 0)));
+    for t in [0, time2t(0, 5, 0, 0), time2t(12, 0, 0, 0), time2t(19, 30, 0, 500000)].iter() {
+      assert_eq!(*t, time_in(&encode(*t)).ok().unwrap());
+    }
+
+    let mut tm = TimeMeta::empty();
+    tm.tm_hour = 9;
+    assert_eq!("09:00:00 AM+05:30", encode_time_meridian(&tm, 0, Some(-19800), DateStyle::ISO));
+    assert_eq!(TimeTz::new(time2t(9, 0, 0, 0), -19800),
+               timetz_in("09:00:00 AM+05:30").ok().unwrap());
+  }
+
+  #[test]
+  fn test_day_of_week() {
+    let ts = date(2016, 11, 11) as i64 * USECS_PER_DAY + time2t(10, 0, 0, 0);
+    assert_eq!(ts, timestamptz_in("Fri, 11 Nov 2016 10:00:00 GMT").ok().unwrap());
+    assert_eq!(ts, timestamptz_in("Fri, 11 Nov 2016 05:00:00 EST").ok().unwrap());
+    assert_eq!(ts, timestamptz_in("Fri, 11 Nov 2016 03:00:00 PDT").ok().unwrap());
+    assert_eq!(ts, timestamptz_in_strict("Fri, 11 Nov 2016 10:00:00 GMT").ok().unwrap());
+    assert_eq!(ts, timestamp_in_strict("friday 2016-11-11 10:00").ok().unwrap());
+    assert_eq!(date(2016, 11, 11), date_in_strict("Nov 11 2016 Fri").ok().unwrap());
+    assert_eq!(date(2016, 11, 13), date_in_strict("Sun 2016-11-13").ok().unwrap());
+
+    // the lenient decoders ignore a wrong day of week
+    assert_eq!(ts, timestamptz_in("Thu, 11 Nov 2016 10:00:00 GMT").ok().unwrap());
+    assert_eq!(date(2016, 11, 11), date_in("thurs 2016-11-11").ok().unwrap());
+
+    let (fields, ftypes, _) = parse_datetime("Thu, 11 Nov 2016").ok().unwrap();
+    let mut tm = TimeMeta::empty();
+    decode_datetime(&fields, &ftypes, &mut tm, &mut 0, &mut None).ok().unwrap();
+    assert_eq!(4, tm.tm_wday);
+
+    assert_error(timestamptz_in_strict("Thu, 11 Nov 2016 10:00:00 GMT"), WeekdayMismatch, "Thu",
+                 Some(DTK_STRING));
+    assert_error(timestamp_in_strict("2016-11-11 weds"), WeekdayMismatch, "weds", Some(DTK_STRING));
+    assert_eq!("22007", date_in_strict("2016-11-11 weds").err().unwrap().code());
+    assert_eq!(OutOfRange, error_kind(date_in_strict("2147483647-11-11 weds")));
+    assert_eq!(AmbiguousField, error_kind(date_in("Fri Fri 2016-11-11")));
+  }
+
+  #[test]
+  fn test_validate_time() {
+    assert_eq!(USECS_PER_DAY, time_in("24:00:00").ok().unwrap());
+    assert_eq!(time2t(23, 59, 60, 0), time_in("23:59:60").ok().unwrap());
+    assert_eq!(FieldOverflow, error_kind(time_in("24:00:00.5")));
+    assert_eq!(FieldOverflow, error_kind(time_in("10:60")));
+    assert_eq!(FieldOverflow, error_kind(time_in("23:59:61")));
+    assert_eq!(FieldOverflow, error_kind(time_in("256000")));
+    assert_eq!(FieldOverflow, error_kind(time_in("t250000")));
+
+    // a leap second rolls over into the next day
+    assert_eq!(timestamp_in("2017-01-01").ok().unwrap(),
+               timestamp_in("2016-12-31 23:59:60").ok().unwrap());
+    let err = timestamp_in("2016-11-11 25:00").err().unwrap();
+    assert_eq!((FieldOverflow, Some(11..16)), (err.kind(), err.span()));
+    assert_eq!(FieldOverflow, error_kind(timestamp_in("20161111T250000")));
+  }
+
+  #[test]
+  fn test_time_in() {
+    let t = time2t(10, 30, 5, 250000);
+    assert_eq!(t, time_in("10:30:05.25").ok().unwrap());
+    assert_eq!(t, time_in("103005.25").ok().unwrap());
+    assert_eq!(t, time_in("10:30:05.25+09").ok().unwrap());
+    assert_eq!(t, time_in("2016-11-11 10:30:05.25").ok().unwrap());
+    assert_eq!(time2t(10, 30, 0, 0), time_in("1030").ok().unwrap());
+    assert_eq!(0, time_in("allballs").ok().unwrap());
+
+    assert!(time_in("10").is_err());
+    assert!(time_in("2016-11-11").is_err());
+  }
+
+  #[test]
+  fn test_date_out() {
+    let d = date(1997, 12, 17);
+    assert_eq!("1997-12-17", date_out(d, DateStyle::ISO).unwrap());
+    assert_eq!("1997-12-17", date_out(d, DateStyle::XSD).unwrap());
+    assert_eq!("12/17/1997", date_out(d, DateStyle::SQL).unwrap());
+    assert_eq!("17.12.1997", date_out(d, DateStyle::German).unwrap());
+    assert_eq!("12-17-1997", date_out(d, DateStyle::Postgres).unwrap());
+    let mut tm = TimeMeta::empty();
+    tm.tm_year = -43;
+    tm.tm_mon = 3;
+    tm.tm_mday = 15;
+    assert_eq!("0044-03-15 BC", encode_date_only(&tm, DateStyle::ISO));
+    assert_eq!("infinity", date_out(DATEVAL_NOEND, DateStyle::ISO).unwrap());
+    assert_eq!("4714-11-23 BC", date_out(-POSTGRES_EPOCH_JDATE - 1, DateStyle::ISO).unwrap());
+    assert_eq!("5877612-06-23 BC", date_out(DATEVAL_NOBEGIN + 1, DateStyle::ISO).unwrap());
+    assert!(date_out(DATEVAL_NOEND - 1, DateStyle::ISO).is_none());
+  }
+
+  #[test]
+  fn test_timestamp_out() {
+    let ts = date(1997, 12, 17) as i64 * USECS_PER_DAY + time2t(7, 37, 16, 0);
+    assert_eq!("1997-12-17 07:37:16", timestamp_out(ts, DateStyle::ISO).unwrap());
+    assert_eq!("1997-12-17T07:37:16.5", timestamp_out(ts + 500000, DateStyle::XSD).unwrap());
+    assert_eq!("12/17/1997 07:37:16", timestamp_out(ts, DateStyle::SQL).unwrap());
+    assert_eq!("17.12.1997 07:37:16", timestamp_out(ts, DateStyle::German).unwrap());
+    assert_eq!("Wed Dec 17 07:37:16 1997", timestamp_out(ts, DateStyle::Postgres).unwrap());
+    assert_eq!("-infinity", timestamp_out(DT_NOBEGIN, DateStyle::ISO).unwrap());
+
+    assert_eq!("1997-12-17 07:37:16.000001+00",
+               timestamptz_out(ts + 1, DateStyle::ISO).unwrap());
+    assert_eq!("1997-12-17T07:37:16+00:00", timestamptz_out(ts, DateStyle::XSD).unwrap());
+    assert_eq!("Wed Dec 17 07:37:16 1997 +00",
+               timestamptz_out(ts, DateStyle::Postgres).unwrap());
+
+    let tm = timestamp2tm(ts).unwrap().0;
+    assert_eq!("1997-12-17 07:37:16-08",
+               encode_date_time(&tm, 0, Some(8 * SECS_PER_HOUR), None, DateStyle::ISO));
+    assert_eq!("12/17/1997 07:37:16 PST",
+               encode_date_time(&tm, 0, Some(8 * SECS_PER_HOUR), Some("PST"), DateStyle::SQL));
+    assert_eq!("07:37:16+05:30",
+               encode_time_only(&tm, 0, Some(-5 * SECS_PER_HOUR - 1800), DateStyle::ISO));
+
+    for s in ["2016-11-11 10:00:00", "0001-01-01 00:00:00.123456", "1999-12-31 23:59:59.99"]
+        .iter() {
+      assert_eq!(*s, timestamp_out(timestamp_in(s).ok().unwrap(), DateStyle::ISO).unwrap());
+    }
+  }
+
+  #[test]
+  fn test_time_out() {
+    assert_eq!("00:00:00", time_out(0, DateStyle::ISO));
+    assert_eq!("23:59:59.999999", time_out(USECS_PER_DAY - 1, DateStyle::ISO));
+    assert_eq!("10:30:05.25", time_out(time2t(10, 30, 5, 250000), DateStyle::ISO));
+  }
+
+  #[test]
+  fn test_encode_interval() {
+    let span = Interval::new(14, 3, time2t(4, 5, 6, 500000));
+    assert_eq!("1 year 2 mons 3 days 04:05:06.5", interval_out(&span, IntervalStyle::Postgres));
+    assert_eq!("P1Y2M3DT4H5M6.5S", interval_out(&span, IntervalStyle::ISO8601));
+
+    let span = Interval::new(-1, 1, -USECS_PER_SEC);
+    assert_eq!("-1 mons +1 day -00:00:01", interval_out(&span, IntervalStyle::Postgres));
+    assert_eq!("P-1M1DT-1S", interval_out(&span, IntervalStyle::ISO8601));
+
+    let zero = Interval::new(0, 0, 0);
+    assert_eq!("00:00:00", interval_out(&zero, IntervalStyle::Postgres));
+    assert_eq!("PT0S", interval_out(&zero, IntervalStyle::ISO8601));
+    assert_eq!("infinity", interval_out(&INTERVAL_NOEND, IntervalStyle::ISO8601));
+  }
+
+  fn assert_iso8601_interval(s: &str, month: i32, day: i32, time: TimeOffset) {
+    let span = decode_iso8601_interval(s).ok().unwrap();
+    assert_eq!((month, day, time), (span.month(), span.day(), span.time()));
+  }
+
+  #[test]
+  fn test_decode_iso8601_interval() {
+    let time = time2t(4, 5, 6, 500000);
+    assert_iso8601_interval("P1Y2M3DT4H5M6.5S", 14, 3, time);
+    assert_iso8601_interval("P0001-02-03T04:05:06.5", 14, 3, time);
+    assert_iso8601_interval("P00010203T040506.5", 14, 3, time);
+    assert_iso8601_interval("P2W", 0, 14, 0);
+    assert_iso8601_interval("PT0S", 0, 0, 0);
+    assert_iso8601_interval("P-1M1DT-1S", -1, 1, -USECS_PER_SEC);
+    assert_iso8601_interval("P0.5M", 0, 15, 0);
+    assert_iso8601_interval("P1.5D", 0, 1, 12 * USECS_PER_HOUR);
+
+    assert!(decode_iso8601_interval("P").is_err());
+    assert!(decode_iso8601_interval("1Y").is_err());
+    assert!(decode_iso8601_interval("P1X").is_err());
+    assert!(decode_iso8601_interval("PT1D").is_err());
+  }
+
+  #[test]
+  fn test_range_limits() {
+    assert_eq!(DATE_END_JULIAN, date2j(JULIAN_MAXYEAR, 1, 1));
+    assert_eq!(TIMESTAMP_END_JULIAN, date2j(294277, 1, 1));
+    assert_eq!(MIN_DATE, DATETIME_MIN_JULIAN - POSTGRES_EPOCH_JDATE);
+    assert_eq!(END_DATE, DATE_END_JULIAN - POSTGRES_EPOCH_JDATE);
+    assert_eq!(MIN_TIMESTAMP, MIN_DATE as i64 * USECS_PER_DAY);
+    assert_eq!(END_TIMESTAMP, (TIMESTAMP_END_JULIAN - POSTGRES_EPOCH_JDATE) as i64 * USECS_PER_DAY);
+
+    assert!(IS_VALID_DATE(MIN_DATE) && IS_VALID_DATE(MAX_DATE));
+    assert!(!IS_VALID_DATE(MIN_DATE - 1) && !IS_VALID_DATE(END_DATE));
+    assert!(IS_VALID_TIMESTAMP(MIN_TIMESTAMP) && IS_VALID_TIMESTAMP(MAX_TIMESTAMP));
+    assert!(!IS_VALID_TIMESTAMP(MIN_TIMESTAMP - 1) && !IS_VALID_TIMESTAMP(END_TIMESTAMP));
+    assert!(!IS_VALID_TIMESTAMP(DT_NOBEGIN) && !IS_VALID_TIMESTAMP(DT_NOEND));
+
+    assert_eq!("5874897-12-31", date_out(MAX_DATE, DateStyle::ISO).unwrap());
+    assert_eq!("4714-11-24 BC", date_out(MIN_DATE, DateStyle::ISO).unwrap());
+    assert_eq!("4714-11-24 00:00:00 BC", timestamp_out(MIN_TIMESTAMP, DateStyle::ISO).unwrap());
+    assert_eq!("294276-12-31 23:59:59.999999",
+               timestamp_out(MAX_TIMESTAMP, DateStyle::ISO).unwrap());
+
+    // the text input functions take the same ranges
+    assert_eq!(MAX_DATE, date_in("5874897-12-31").ok().unwrap());
+    assert_eq!(MIN_DATE, date_in("4714-11-24 BC").ok().unwrap());
+    assert_eq!(OutOfRange, error_kind(date_in("5874898-03-01")));
+    assert_eq!(OutOfRange, error_kind(date_in("4714-11-23 BC")));
+    assert_eq!(OutOfRange, error_kind(date_in("4714-11-01 BC")));
+    assert_eq!(MAX_TIMESTAMP, timestamp_in("294276-12-31 23:59:59.999999").ok().unwrap());
+    assert_eq!(MIN_TIMESTAMP, timestamp_in("4714-11-24 00:00:00 BC").ok().unwrap());
+    assert_eq!(OutOfRange, error_kind(timestamp_in("294277-01-01")));
+    assert_eq!(OutOfRange, error_kind(timestamp_in("4714-11-23 23:59:59.999999 BC")));
+    assert_eq!(OutOfRange, error_kind(timestamptz_in("294276-12-31 23:00:00-01")));
+  }
+
+  #[test]
+  fn test_unix_epoch() {
+    let t = timestamp_in("2016-11-11 01:00:00.123456").ok().unwrap();
+    let epoch = timestamp_in("epoch").ok().unwrap();
+
+    assert_eq!(Some(epoch), from_unix_seconds(0));
+    assert_eq!(Some(t - 123456), from_unix_seconds(1478826000));
+    assert_eq!(Some(t - 456), from_unix_millis(1478826000123));
+    assert_eq!(Some(t), from_unix_micros(1478826000123456));
+    assert_eq!(Some(t), from_unix_nanos(1478826000123456789));
+    assert_eq!(Some(epoch - 1), from_unix_nanos(-1));
+    assert_eq!(Some(epoch - 1), from_unix_nanos(-1000));
+    assert_eq!(None, from_unix_seconds(::std::i64::MAX / 1000));
+    assert_eq!(None, from_unix_micros(::std::i64::MIN));
+    assert_eq!(None, from_unix_micros(DT_NOBEGIN - set_epoch_timestamp()));
+    assert_eq!(None, from_unix_micros(DT_NOBEGIN + 1 - set_epoch_timestamp()));
+    assert_eq!(Some(MIN_TIMESTAMP), from_unix_micros(MIN_TIMESTAMP - set_epoch_timestamp()));
+    assert_eq!(None, from_unix_micros(MIN_TIMESTAMP - 1 - set_epoch_timestamp()));
+
+    assert_eq!(Some(1478826000), to_unix_seconds(t));
+    assert_eq!(Some(1478826000123), to_unix_millis(t));
+    assert_eq!(Some(1478826000123456), to_unix_micros(t));
+    assert_eq!(Some(1478826000123456000), to_unix_nanos(t));
+    assert_eq!(Some(-1), to_unix_seconds(epoch - 500000));
+    assert_eq!(Some(-1), to_unix_millis(epoch - 1));
+    assert_eq!(None, to_unix_nanos(timestamp_in("2300-01-01").ok().unwrap()));
+    assert_eq!(None, to_unix_seconds(DT_NOEND));
+    assert_eq!(None, to_unix_micros(DT_NOBEGIN));
+
+    // like to_timestamp(double precision)
+    assert_eq!(Some(t), float8_timestamptz(1478826000.123456));
+    assert_eq!(Some(epoch - 500000), float8_timestamptz(-0.5));
+    assert_eq!(Some(DT_NOEND), float8_timestamptz(::std::f64::INFINITY));
+    assert_eq!(Some(DT_NOBEGIN), float8_timestamptz(::std::f64::NEG_INFINITY));
+    assert_eq!(None, float8_timestamptz(::std::f64::NAN));
+    assert_eq!(None, float8_timestamptz(1e300));
+    assert_eq!(None, float8_timestamptz(-210866803200.5));
+    assert_eq!(Some(MIN_TIMESTAMP), float8_timestamptz(-210866803200.0));
+    assert!(timestamptz_in("epoch 1478826000.123456").is_err());
+    assert!(timestamptz_in("epoch 5").is_err());
+    assert!(timestamp_in("5 epoch").is_err());
+
+    assert_eq!(1478826000.123456, extract_epoch(t));
+    assert_eq!(-0.5, extract_epoch(epoch - 500000));
+    assert_eq!(0.0, extract_epoch(epoch));
+    assert_eq!(::std::f64::INFINITY, extract_epoch(DT_NOEND));
+    assert_eq!(::std::f64::NEG_INFINITY, extract_epoch(DT_NOBEGIN));
+    assert!(extract_epoch(DT_NOEND - 1) > 9.2e12);
+  }
+
+  #[test]
+  fn test_typmod() {
+    let t = timestamp_in("2016-11-11 10:30:15.123456").ok().unwrap();
+    assert_eq!(Some(t), adjust_timestamp_for_typmod(t, -1));
+    assert_eq!(Some(t), adjust_timestamp_for_typmod(t, 6));
+    assert_eq!(Some(t + 4), adjust_timestamp_for_typmod(t, 5));
+    assert_eq!(Some(t - 123456), adjust_timestamp_for_typmod(t, 0));
+    assert_eq!(timestamp_in("2016-11-11 10:30:15.12").ok(), adjust_timestamp_for_typmod(t, 2));
+    // halves round away from zero, on both sides of 2000-01-01
+    assert_eq!(Some(1000000), adjust_timestamp_for_typmod(500000, 0));
+    assert_eq!(Some(-1000000), adjust_timestamp_for_typmod(-500000, 0));
+    assert_eq!(Some(-1000000), adjust_timestamp_for_typmod(-999999, 3));
+    assert_eq!(Some(DT_NOEND), adjust_timestamp_for_typmod(DT_NOEND, 0));
+    assert_eq!(None, adjust_timestamp_for_typmod(t, 7));
+    assert_eq!(None, adjust_timestamp_for_typmod(t, -2));
+    assert_eq!(None, adjust_timestamp_for_typmod(DT_NOEND - 1, 0));
+    assert_eq!(None, adjust_timestamp_for_typmod(DT_NOEND - 1, -1));
+    assert_eq!(None, adjust_timestamp_for_typmod(MIN_TIMESTAMP - 1, 6));
+    assert_eq!(Some(MIN_TIMESTAMP), adjust_timestamp_for_typmod(MIN_TIMESTAMP + 1, 3));
+    assert_eq!(None, adjust_timestamp_for_typmod(MAX_TIMESTAMP, 0));
+
+    let time = time2t(23, 59, 59, 999999);
+    assert_eq!(USECS_PER_DAY, adjust_time_for_typmod(time, 0));
+    assert_eq!(time2t(23, 59, 59, 999990), adjust_time_for_typmod(time - 5, 5));
+    assert_eq!(time, adjust_time_for_typmod(time, -1));
+    assert_eq!(time, adjust_time_for_typmod(time, 7));
+  }
+
+  #[test]
+  fn test_interval_typmod() {
+    let span = Interval::new(14, 3, time2t(4, 5, 6, 789012));
+    let adjust = |fields, precision| {
+      let typmod = interval_typmod(fields, precision).unwrap();
+      let r = adjust_interval_for_typmod(&span, typmod).unwrap();
+      (r.month(), r.day(), r.time())
+    };
+
+    assert_eq!(Some(-1), interval_typmod(None, None));
+    assert_eq!(None, interval_typmod(Some(IntervalFields::Second), Some(-1)));
+    assert_eq!(interval_typmod(None, Some(6)), interval_typmod(None, Some(10)));
+
+    assert_eq!((14, 3, span.time()), adjust(None, None));
+    assert_eq!((12, 0, 0), adjust(Some(IntervalFields::Year), None));
+    assert_eq!((14, 0, 0), adjust(Some(IntervalFields::Month), None));
+    assert_eq!((14, 0, 0), adjust(Some(IntervalFields::YearToMonth), None));
+    assert_eq!((14, 3, 0), adjust(Some(IntervalFields::Day), None));
+    assert_eq!((14, 3, time2t(4, 0, 0, 0)), adjust(Some(IntervalFields::Hour), None));
+    assert_eq!((14, 3, time2t(4, 0, 0, 0)), adjust(Some(IntervalFields::DayToHour), None));
+    assert_eq!((14, 3, time2t(4, 5, 0, 0)), adjust(Some(IntervalFields::Minute), None));
+    assert_eq!((14, 3, time2t(4, 5, 0, 0)), adjust(Some(IntervalFields::DayToMinute), None));
+    assert_eq!((14, 3, time2t(4, 5, 0, 0)), adjust(Some(IntervalFields::HourToMinute), None));
+    assert_eq!((14, 3, span.time()), adjust(Some(IntervalFields::Second), None));
+    assert_eq!((14, 3, span.time()), adjust(Some(IntervalFields::DayToSecond), None));
+    assert_eq!((14, 3, time2t(4, 5, 7, 0)), adjust(Some(IntervalFields::DayToSecond), Some(0)));
+    assert_eq!((14, 3, time2t(4, 5, 6, 790000)),
+               adjust(Some(IntervalFields::HourToSecond), Some(2)));
+    assert_eq!((14, 3, time2t(4, 5, 6, 789000)), adjust(Some(IntervalFields::MinuteToSecond),
+                                                       Some(3)));
+    assert_eq!((14, 3, time2t(4, 5, 6, 789000)), adjust(None, Some(3)));
+
+    // the time part truncates towards zero, and rounds away from it
+    let neg = Interval::new(-14, -3, -time2t(4, 5, 6, 500000));
+    let typmod = interval_typmod(Some(IntervalFields::Year), None).unwrap();
+    assert_eq!(-12, adjust_interval_for_typmod(&neg, typmod).unwrap().month());
+    let typmod = interval_typmod(Some(IntervalFields::Hour), None).unwrap();
+    assert_eq!(-time2t(4, 0, 0, 0), adjust_interval_for_typmod(&neg, typmod).unwrap().time());
+    let typmod = interval_typmod(None, Some(0)).unwrap();
+    assert_eq!(-time2t(4, 5, 7, 0), adjust_interval_for_typmod(&neg, typmod).unwrap().time());
+
+    assert!(interval_not_finite(&adjust_interval_for_typmod(&INTERVAL_NOEND, 0).unwrap()));
+    assert!(adjust_interval_for_typmod(&span, INTERVAL_TYPMOD(0, INTERVAL_MASK(DOW))).is_none());
+    assert!(adjust_interval_for_typmod(&span, INTERVAL_TYPMOD(7, INTERVAL_FULL_RANGE)).is_none());
+  }
+}
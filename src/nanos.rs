@@ -0,0 +1,260 @@
+//! Timestamps with nanosecond precision.
+//!
+//! `Timestamp` counts microseconds, as PostgreSQL does. `TimestampNanos`
+//! counts nanoseconds from the same epoch, 2000-01-01 00:00:00 UTC, in an
+//! i64. It is a distinct type so that the two units can't be mixed up. That
+//! covers 1707-09-22 00:12:43.145224193 to 2292-04-10 23:47:16.854775806.
+//! The extreme values `NANOS_NOBEGIN` and `NANOS_NOEND` stand for -infinity
+//! and infinity.
+//!
+//! Every conversion that drops digits takes a `Rounding`.
+
+pub use super::Rounding;
+
+use super::{decode_datetime, div_floor, encode_date_time, encode_timezone, parse_datetime,
+            parse_fraction, set_epoch_timestamp, timestamp2tm, timestamp_is_nobegin,
+            timestamp_is_noend, timestamp_not_finite, tm2timestamp, DateStyle,
+            DateTimeErrorKind, DateTimeParseError, FracSec, TimeMeta, Timestamp, DTK_DATE,
+            DTK_EARLY, DTK_EPOCH, DTK_LATE, DTK_NUMBER, DTK_TIME, DT_NOBEGIN, DT_NOEND, EARLY,
+            LATE};
+
+/// Nanoseconds since 2000-01-01 00:00:00 UTC
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimestampNanos(i64);
+
+impl TimestampNanos {
+  pub fn new(nanos: i64) -> TimestampNanos {
+    TimestampNanos(nanos)
+  }
+
+  pub fn nanos(&self) -> i64 {
+    self.0
+  }
+}
+
+pub const NANOS_NOBEGIN :TimestampNanos = TimestampNanos(DT_NOBEGIN);
+pub const NANOS_NOEND   :TimestampNanos = TimestampNanos(DT_NOEND);
+
+/// n / d, for d > 0, rounded as given.
+fn round_div(n: i64, d: i64, rounding: Rounding) -> i64 {
+  let q = div_floor(n, d);
+  let r = n - q * d;
+  match rounding {
+    Rounding::Floor => q,
+    Rounding::HalfUp if r >= d - r => q + 1,
+    Rounding::HalfEven if r > d - r || (r == d - r && q % 2 != 0) => q + 1,
+    _ => q
+  }
+}
+
+/// Convert a timestamp to nanoseconds. Return None if it is out of range.
+pub fn from_timestamp(t: Timestamp) -> Option<TimestampNanos> {
+  if timestamp_not_finite(t) {
+    return Some(TimestampNanos(t));
+  }
+
+  match t.checked_mul(1000) {
+    Some(n) if !timestamp_not_finite(n) => Some(TimestampNanos(n)),
+    _ => None
+  }
+}
+
+/// Convert nanoseconds to a timestamp, rounding to microseconds.
+pub fn to_timestamp(t: TimestampNanos, rounding: Rounding) -> Timestamp {
+  let t = t.0;
+  if timestamp_not_finite(t) {
+    return t;
+  }
+  round_div(t, 1000, rounding)
+}
+
+/// Parse a timestamp as timestamptz_in() does, but keep up to nine
+/// fractional digits of the seconds. Further digits are rounded away.
+/// Input without a time zone is taken to be UTC.
+pub fn decode(s: &str, rounding: Rounding) -> Result<TimestampNanos, DateTimeParseError> {
+  let type_name = "timestamp with time zone";
+  let (mut fields, ftypes, spans) = parse_datetime(s).map_err(|e| e.locate(s, type_name, &[]))?;
+
+  // The decoder reads the seconds fraction to microseconds; take the
+  // digits past those out of the time field and add them back after. The
+  // time is either hh:mm:ss or run-together hhmmss or hhmm, with or
+  // without an ISO 8601 "t" before it.
+  let mut nanos = 0;
+  for i in 0..fields.len() {
+    let is_time = ftypes[i] == DTK_TIME ||
+      (ftypes[i] == DTK_NUMBER && i > 0 &&
+       (fields[i - 1] == b"t" || (is_run_together_time(&fields[i]) &&
+                                  !fields[i - 1][0].is_ascii_alphabetic())));
+    if !is_time {
+      continue;
+    }
+    let field = &mut fields[i];
+    if let Some(dot) = field.iter().position(|&c| c == b'.') {
+      let end = field[dot + 1..].iter().position(|c| !c.is_ascii_digit())
+        .map_or(field.len(), |i| dot + 1 + i);
+      if end > dot + 7 {
+        // nanoseconds past the microsecond, or 1000 if they round up to one
+        nanos = parse_fraction(&field[dot + 7..end], 3, rounding).unwrap();
+        field.drain(dot + 7..end);
+      }
+    }
+  }
+
+  let mut tm = TimeMeta::empty();
+  let mut fsec: FracSec = 0;
+  let mut tz: Option<i32> = None;
+  let dtype = decode_datetime(&fields, &ftypes, &mut tm, &mut fsec, &mut tz)
+    .map_err(|e| e.locate(s, type_name, &spans))?;
+
+  let t = match dtype {
+    DTK_DATE => tm2timestamp(&tm, fsec, Some(tz.unwrap_or(0))),
+    DTK_EPOCH => Some(set_epoch_timestamp()),
+    DTK_LATE => return Ok(NANOS_NOEND),
+    DTK_EARLY => return Ok(NANOS_NOBEGIN),
+    _ => return Err(DateTimeParseError::new(DateTimeErrorKind::BadFormat, s)
+                    .locate(s, type_name, &[]))
+  };
+
+  match t.and_then(|t| t.checked_mul(1000)).and_then(|n| n.checked_add(nanos)) {
+    Some(n) if !timestamp_not_finite(n) => Ok(TimestampNanos(n)),
+    _ => Err(DateTimeParseError::new(DateTimeErrorKind::OutOfRange, s).locate(s, type_name, &[]))
+  }
+}
+
+/// Whether a number field is a time such as "103015.123456789": four or six
+/// digits and a fraction. After a date, decode_datetime() reads it as
+/// hhmm[ss]. A Julian day follows a label, which the caller rules out.
+fn is_run_together_time(field: &[u8]) -> bool {
+  match field.iter().position(|&c| c == b'.') {
+    Some(dot) if dot == 4 || dot == 6 => field[..dot].iter().all(|c| c.is_ascii_digit()),
+    _ => false
+  }
+}
+
+/// Format a timestamp in the ISO style of timestamptz_out(), in UTC, with
+/// the seconds rounded to at most digits (0 to 9) fractional digits.
+/// Trailing zeros are dropped. Return None if rounding overflows.
+pub fn encode(t: TimestampNanos, digits: u32, rounding: Rounding) -> Option<String> {
+  debug_assert!(digits <= 9);
+
+  let t = t.0;
+  if timestamp_is_nobegin(t) {
+    return Some(String::from_utf8_lossy(EARLY).into_owned());
+  }
+  if timestamp_is_noend(t) {
+    return Some(String::from_utf8_lossy(LATE).into_owned());
+  }
+
+  let unit = 10i64.pow(9 - digits);
+  let t = match round_div(t, unit, rounding).checked_mul(unit) {
+    Some(t) if !timestamp_not_finite(t) => t,
+    _ => return None
+  };
+
+  let usecs = div_floor(t, 1000);
+  let (tm, fsec) = match timestamp2tm(usecs) {
+    Some(r) => r,
+    None => return None
+  };
+  let frac = fsec as i64 * 1000 + (t - usecs * 1000);
+
+  let mut buf = encode_date_time(&tm, 0, None, None, DateStyle::ISO);
+  if frac != 0 {
+    buf.push('.');
+    buf.push_str(format!("{:09}", frac).trim_end_matches('0'));
+  }
+  encode_timezone(&mut buf, 0, DateStyle::ISO);
+
+  Some(buf)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::timestamptz_in;
+  use super::super::DateTimeErrorKind::OutOfRange;
+
+  #[test]
+  fn test_round_div() {
+    assert_eq!(2, round_div(1500, 1000, Rounding::HalfUp));
+    assert_eq!(2, round_div(1500, 1000, Rounding::HalfEven));
+    assert_eq!(2, round_div(2500, 1000, Rounding::HalfEven));
+    assert_eq!(1, round_div(1999, 1000, Rounding::Floor));
+    assert_eq!(-2, round_div(-1500, 1000, Rounding::HalfEven));
+    assert_eq!(-1, round_div(-1500, 1000, Rounding::HalfUp));
+    assert_eq!(-2, round_div(-1001, 1000, Rounding::Floor));
+    assert_eq!(-1, round_div(-1499, 1000, Rounding::HalfUp));
+  }
+
+  #[test]
+  fn test_conversions() {
+    let t = timestamptz_in("2016-11-11 10:30:15.123456").ok().unwrap();
+    let n = |n: i64| TimestampNanos::new(n);
+    assert_eq!(Some(n(t * 1000)), from_timestamp(t));
+    assert_eq!(Some(NANOS_NOEND), from_timestamp(DT_NOEND));
+    assert_eq!(None, from_timestamp(timestamptz_in("2300-01-01").ok().unwrap()));
+    assert_eq!(None, from_timestamp(timestamptz_in("1700-01-01").ok().unwrap()));
+
+    assert_eq!(t, to_timestamp(n(t * 1000 + 499), Rounding::HalfUp));
+    assert_eq!(t + 1, to_timestamp(n(t * 1000 + 500), Rounding::HalfUp));
+    assert_eq!(t, to_timestamp(n(t * 1000 + 500), Rounding::HalfEven));
+    assert_eq!(t, to_timestamp(n(t * 1000 + 999), Rounding::Floor));
+    assert_eq!(-1, to_timestamp(n(-1), Rounding::Floor));
+    assert_eq!(0, to_timestamp(n(-1), Rounding::HalfUp));
+    assert_eq!(DT_NOBEGIN, to_timestamp(NANOS_NOBEGIN, Rounding::HalfUp));
+  }
+
+  #[test]
+  fn test_decode() {
+    let nanos = |s: &str, rounding| decode(s, rounding).ok().unwrap().nanos();
+    let t = timestamptz_in("2016-11-11 10:30:15.123456").ok().unwrap() * 1000;
+    assert_eq!(t + 789, nanos("2016-11-11 10:30:15.123456789", Rounding::HalfUp));
+    assert_eq!(t + 700, nanos("2016-11-11 10:30:15.1234567", Rounding::HalfUp));
+    assert_eq!(t, nanos("2016-11-11 10:30:15.123456", Rounding::HalfUp));
+    assert_eq!(t + 789, nanos("2016-11-11 19:30:15.123456789+09", Rounding::HalfUp));
+    assert_eq!(t + 789, nanos("Nov 11 10:30:15.123456789 2016", Rounding::HalfUp));
+    assert_eq!(t + 789, nanos("2016-11-11T10:30:15.123456789+00", Rounding::HalfUp));
+    assert_eq!(t + 789, nanos("20161111T103015.123456789", Rounding::HalfUp));
+    assert_eq!(t + 789, nanos("20161111 103015.123456789", Rounding::HalfUp));
+    assert_eq!(t + 789, nanos("Nov 11 2016 103015.123456789", Rounding::HalfUp));
+    let hhmm = timestamptz_in("2016-11-11 10:30:00").ok().unwrap() * 1000;
+    assert_eq!(hhmm + 123456789, nanos("20161111 1030.123456789", Rounding::HalfUp));
+
+    // digits past the ninth
+    let s = "2016-11-11 10:30:15.1234567885";
+    assert_eq!(t + 789, nanos(s, Rounding::HalfUp));
+    assert_eq!(t + 788, nanos(s, Rounding::HalfEven));
+    assert_eq!(t + 788, nanos(s, Rounding::Floor));
+    let s = "2016-11-11 10:30:15.123456999999";
+    assert_eq!(t + 1000, nanos(s, Rounding::HalfUp));
+    assert_eq!(t + 999, nanos(s, Rounding::Floor));
+
+    assert_eq!(NANOS_NOEND, decode("infinity", Rounding::HalfUp).ok().unwrap());
+    assert_eq!(set_epoch_timestamp() * 1000, nanos("epoch", Rounding::HalfUp));
+    assert_eq!(OutOfRange, decode("2300-01-01", Rounding::HalfUp).err().unwrap().kind());
+    assert!(decode("2016-11-11 10:30:1x.123456789", Rounding::HalfUp).is_err());
+  }
+
+  #[test]
+  fn test_encode() {
+    let n = |n: i64| TimestampNanos::new(n);
+    let t = decode("2016-11-11 10:30:15.123456789", Rounding::HalfUp).ok().unwrap();
+    assert_eq!("2016-11-11 10:30:15.123456789+00", encode(t, 9, Rounding::HalfUp).unwrap());
+    assert_eq!("2016-11-11 10:30:15.1234568+00", encode(t, 7, Rounding::HalfUp).unwrap());
+    assert_eq!("2016-11-11 10:30:15.1234567+00", encode(t, 7, Rounding::Floor).unwrap());
+    assert_eq!("2016-11-11 10:30:15.123+00", encode(t, 3, Rounding::HalfEven).unwrap());
+    assert_eq!("2016-11-11 10:30:15+00", encode(t, 0, Rounding::HalfUp).unwrap());
+    assert_eq!("2016-11-11 10:30:16.1+00", encode(n(t.nanos() + 976543211), 9, Rounding::HalfUp).unwrap());
+    assert_eq!("2016-11-11 10:30:16+00", encode(n(t.nanos() + 876543211), 9, Rounding::HalfUp).unwrap());
+    assert_eq!("2016-11-11 10:30:16+00", encode(n(t.nanos() + 376543211), 0, Rounding::HalfUp).unwrap());
+    assert_eq!("1999-12-31 23:59:59.999999999+00", encode(n(-1), 9, Rounding::HalfUp).unwrap());
+    assert_eq!("2000-01-01 00:00:00+00", encode(n(-1), 6, Rounding::HalfUp).unwrap());
+    assert_eq!("infinity", encode(NANOS_NOEND, 9, Rounding::HalfUp).unwrap());
+    assert_eq!(None, encode(n(DT_NOEND - 1), 0, Rounding::HalfUp));
+
+    for t in [t, n(-1), n(1), n(500000000123456789)].iter() {
+      let s = encode(*t, 9, Rounding::HalfUp).unwrap();
+      assert_eq!(*t, decode(&s, Rounding::HalfUp).ok().unwrap());
+    }
+  }
+}
@@ -0,0 +1,519 @@
+//! PostgreSQL binary wire format for the date/time types.
+//!
+//! The `*_send`/`*_recv` pairs match the binary output and input functions
+//! used by the extended query protocol. Dates are sent as i32 days since
+//! 2000-01-01. Times and timestamps are sent as i64 microseconds, and
+//! intervals as (i64 time, i32 day, i32 month). Those are already Julian's
+//! in-memory representations, so the codecs are plain big-endian copies.
+//! Infinities use the same extreme-value sentinels on the wire.
+//!
+//! `CopyWriter` and `CopyReader` stream rows in the COPY BINARY file format.
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use super::{Date, Interval, Time, TimeTz, Timestamp, TimestampTz, TZDISP_LIMIT, USECS_PER_DAY};
+use super::{date_not_finite, timestamp_not_finite, IS_VALID_DATE, IS_VALID_TIMESTAMP};
+
+#[derive(Debug)]
+pub enum WireError {
+  /// the value does not have the number of bytes its type requires
+  BadLength { expected: usize, actual: usize },
+  /// the value is outside the range of its type
+  OutOfRange(&'static str),
+  /// the stream is not a valid COPY BINARY stream
+  BadFormat(String),
+  Io(io::Error)
+}
+
+impl fmt::Display for WireError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      WireError::BadLength { expected, actual } => {
+        write!(f, "insufficient data left in message: expected {} bytes, got {}", expected, actual)
+      }
+      WireError::OutOfRange(what) => write!(f, "{} out of range", what),
+      WireError::BadFormat(ref s) => write!(f, "{}", s),
+      WireError::Io(ref e) => write!(f, "{}", e)
+    }
+  }
+}
+
+impl Error for WireError {}
+
+impl From<io::Error> for WireError {
+  fn from(e: io::Error) -> Self {
+    WireError::Io(e)
+  }
+}
+
+fn check_len(buf: &[u8], expected: usize) -> Result<(), WireError> {
+  if buf.len() != expected {
+    return Err(WireError::BadLength { expected: expected, actual: buf.len() });
+  }
+  Ok(())
+}
+
+fn get_i32(buf: &[u8]) -> i32 {
+  i32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]])
+}
+
+fn get_i64(buf: &[u8]) -> i64 {
+  let mut b = [0u8; 8];
+  b.copy_from_slice(&buf[..8]);
+  i64::from_be_bytes(b)
+}
+
+// ---------------------------------------------------------------------------
+// Value codecs
+// ---------------------------------------------------------------------------
+
+pub fn date_send(d: Date, buf: &mut Vec<u8>) {
+  buf.extend_from_slice(&d.to_be_bytes());
+}
+
+pub fn date_recv(buf: &[u8]) -> Result<Date, WireError> {
+  check_len(buf, 4)?;
+  let d = get_i32(buf);
+  // limit to the same range that PostgreSQL's date_in() accepts
+  if !date_not_finite(d) && !IS_VALID_DATE(d) {
+    return Err(WireError::OutOfRange("date"));
+  }
+  Ok(d)
+}
+
+pub fn time_send(t: Time, buf: &mut Vec<u8>) {
+  buf.extend_from_slice(&t.to_be_bytes());
+}
+
+pub fn time_recv(buf: &[u8]) -> Result<Time, WireError> {
+  check_len(buf, 8)?;
+  let t = get_i64(buf);
+  if t < 0 || t > USECS_PER_DAY {
+    return Err(WireError::OutOfRange("time"));
+  }
+  Ok(t)
+}
+
+pub fn timetz_send(t: &TimeTz, buf: &mut Vec<u8>) {
+  buf.extend_from_slice(&t.time.to_be_bytes());
+  buf.extend_from_slice(&t.zone.to_be_bytes());
+}
+
+pub fn timetz_recv(buf: &[u8]) -> Result<TimeTz, WireError> {
+  check_len(buf, 12)?;
+  let time = get_i64(buf);
+  if time < 0 || time > USECS_PER_DAY {
+    return Err(WireError::OutOfRange("time"));
+  }
+
+  let zone = get_i32(&buf[8..]);
+  if zone <= -TZDISP_LIMIT || zone >= TZDISP_LIMIT {
+    return Err(WireError::OutOfRange("time zone displacement"));
+  }
+  Ok(TimeTz::new(time, zone))
+}
+
+pub fn timestamp_send(ts: Timestamp, buf: &mut Vec<u8>) {
+  buf.extend_from_slice(&ts.to_be_bytes());
+}
+
+pub fn timestamp_recv(buf: &[u8]) -> Result<Timestamp, WireError> {
+  check_len(buf, 8)?;
+  let ts = get_i64(buf);
+  if !timestamp_not_finite(ts) && !IS_VALID_TIMESTAMP(ts) {
+    return Err(WireError::OutOfRange("timestamp"));
+  }
+  Ok(ts)
+}
+
+pub fn timestamptz_send(ts: TimestampTz, buf: &mut Vec<u8>) {
+  timestamp_send(ts, buf)
+}
+
+pub fn timestamptz_recv(buf: &[u8]) -> Result<TimestampTz, WireError> {
+  timestamp_recv(buf)
+}
+
+pub fn interval_send(span: &Interval, buf: &mut Vec<u8>) {
+  buf.extend_from_slice(&span.time.to_be_bytes());
+  buf.extend_from_slice(&span.day.to_be_bytes());
+  buf.extend_from_slice(&span.month.to_be_bytes());
+}
+
+pub fn interval_recv(buf: &[u8]) -> Result<Interval, WireError> {
+  check_len(buf, 16)?;
+  Ok(Interval::new(get_i32(&buf[12..]), get_i32(&buf[8..]), get_i64(buf)))
+}
+
+// ---------------------------------------------------------------------------
+// COPY BINARY streams
+// ---------------------------------------------------------------------------
+
+/// "PGCOPY\n\377\r\n\0"
+const COPY_SIGNATURE: [u8; 11] = [b'P', b'G', b'C', b'O', b'P', b'Y', b'\n', 0xff, b'\r', b'\n', 0];
+/// header flag bit 16: OIDs are included in the data
+const COPY_FLAG_OIDS: i32 = 1 << 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnType {
+  Date,
+  Time,
+  TimeTz,
+  Timestamp,
+  TimestampTz,
+  Interval
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Value {
+  Date(Date),
+  Time(Time),
+  TimeTz(TimeTz),
+  Timestamp(Timestamp),
+  TimestampTz(TimestampTz),
+  Interval(Interval)
+}
+
+impl ColumnType {
+  /// The number of bytes a value of this type has on the wire.
+  pub fn size(&self) -> usize {
+    match *self {
+      ColumnType::Date => 4,
+      ColumnType::Time | ColumnType::Timestamp | ColumnType::TimestampTz => 8,
+      ColumnType::TimeTz => 12,
+      ColumnType::Interval => 16
+    }
+  }
+}
+
+impl Value {
+  pub fn column_type(&self) -> ColumnType {
+    match *self {
+      Value::Date(_) => ColumnType::Date,
+      Value::Time(_) => ColumnType::Time,
+      Value::TimeTz(_) => ColumnType::TimeTz,
+      Value::Timestamp(_) => ColumnType::Timestamp,
+      Value::TimestampTz(_) => ColumnType::TimestampTz,
+      Value::Interval(_) => ColumnType::Interval
+    }
+  }
+
+  pub fn send(&self, buf: &mut Vec<u8>) {
+    match *self {
+      Value::Date(d) => date_send(d, buf),
+      Value::Time(t) => time_send(t, buf),
+      Value::TimeTz(ref t) => timetz_send(t, buf),
+      Value::Timestamp(ts) => timestamp_send(ts, buf),
+      Value::TimestampTz(ts) => timestamptz_send(ts, buf),
+      Value::Interval(ref span) => interval_send(span, buf)
+    }
+  }
+
+  pub fn recv(ty: ColumnType, buf: &[u8]) -> Result<Value, WireError> {
+    Ok(match ty {
+      ColumnType::Date => Value::Date(date_recv(buf)?),
+      ColumnType::Time => Value::Time(time_recv(buf)?),
+      ColumnType::TimeTz => Value::TimeTz(timetz_recv(buf)?),
+      ColumnType::Timestamp => Value::Timestamp(timestamp_recv(buf)?),
+      ColumnType::TimestampTz => Value::TimestampTz(timestamptz_recv(buf)?),
+      ColumnType::Interval => Value::Interval(interval_recv(buf)?)
+    })
+  }
+}
+
+/// Writes rows of the given column types as a COPY BINARY stream.
+pub struct CopyWriter<W: Write> {
+  inner: W,
+  columns: Vec<ColumnType>,
+  buf: Vec<u8>
+}
+
+impl<W: Write> CopyWriter<W> {
+  /// Write the file header and return a writer for the rows.
+  pub fn new(mut inner: W, columns: Vec<ColumnType>) -> Result<CopyWriter<W>, WireError> {
+    if columns.len() > ::std::i16::MAX as usize {
+      return Err(WireError::BadFormat(format!("too many columns: {}", columns.len())));
+    }
+
+    inner.write_all(&COPY_SIGNATURE)?;
+    inner.write_all(&0i32.to_be_bytes())?;  // flags
+    inner.write_all(&0i32.to_be_bytes())?;  // header extension length
+
+    Ok(CopyWriter {
+      inner: inner,
+      columns: columns,
+      buf: Vec::new()
+    })
+  }
+
+  /// Write one row; None is written as NULL.
+  pub fn write_row(&mut self, row: &[Option<Value>]) -> Result<(), WireError> {
+    if row.len() != self.columns.len() {
+      return Err(WireError::BadFormat(
+        format!("row has {} fields, expected {}", row.len(), self.columns.len())));
+    }
+
+    self.buf.clear();
+    self.buf.extend_from_slice(&(row.len() as i16).to_be_bytes());
+
+    for (value, &ty) in row.iter().zip(&self.columns) {
+      match *value {
+        None => self.buf.extend_from_slice(&(-1i32).to_be_bytes()),
+        Some(ref v) => {
+          if v.column_type() != ty {
+            return Err(WireError::BadFormat(
+              format!("value of type {:?} in a {:?} column", v.column_type(), ty)));
+          }
+          let len_at = self.buf.len();
+          self.buf.extend_from_slice(&[0; 4]);
+          v.send(&mut self.buf);
+          let len = (self.buf.len() - len_at - 4) as i32;
+          self.buf[len_at..len_at + 4].copy_from_slice(&len.to_be_bytes());
+        }
+      }
+    }
+
+    self.inner.write_all(&self.buf)?;
+    Ok(())
+  }
+
+  /// Write the file trailer and return the underlying writer.
+  pub fn finish(mut self) -> Result<W, WireError> {
+    self.inner.write_all(&(-1i16).to_be_bytes())?;
+    self.inner.flush()?;
+    Ok(self.inner)
+  }
+}
+
+/// Reads rows of the given column types from a COPY BINARY stream.
+pub struct CopyReader<R: Read> {
+  inner: R,
+  columns: Vec<ColumnType>,
+  buf: Vec<u8>,
+  done: bool
+}
+
+impl<R: Read> CopyReader<R> {
+  /// Read and check the file header.
+  pub fn new(mut inner: R, columns: Vec<ColumnType>) -> Result<CopyReader<R>, WireError> {
+    let mut signature = [0u8; 11];
+    inner.read_exact(&mut signature)?;
+    if signature != COPY_SIGNATURE {
+      return Err(WireError::BadFormat("COPY file signature not recognized".to_owned()));
+    }
+
+    let flags = read_i32(&mut inner)?;
+    if (flags & COPY_FLAG_OIDS) != 0 {
+      return Err(WireError::BadFormat("OIDs in COPY data are not supported".to_owned()));
+    }
+    if (flags & !COPY_FLAG_OIDS) >> 16 != 0 {
+      return Err(WireError::BadFormat("unrecognized critical flags in COPY file header".to_owned()));
+    }
+
+    // skip the header extension area
+    let ext_len = read_i32(&mut inner)?;
+    if ext_len < 0 {
+      return Err(WireError::BadFormat("invalid COPY file header (missing length)".to_owned()));
+    }
+    let skipped = io::copy(&mut (&mut inner).take(ext_len as u64), &mut io::sink())?;
+    if skipped != ext_len as u64 {
+      return Err(WireError::BadFormat("invalid COPY file header (wrong length)".to_owned()));
+    }
+
+    Ok(CopyReader {
+      inner: inner,
+      columns: columns,
+      buf: Vec::new(),
+      done: false
+    })
+  }
+
+  /// Read the next row, or None after the file trailer.
+  pub fn read_row(&mut self) -> Result<Option<Vec<Option<Value>>>, WireError> {
+    if self.done {
+      return Ok(None);
+    }
+
+    let mut count = [0u8; 2];
+    self.inner.read_exact(&mut count)?;
+    let count = i16::from_be_bytes(count);
+    if count == -1 {
+      self.done = true;
+      return Ok(None);
+    }
+    if count as usize != self.columns.len() {
+      return Err(WireError::BadFormat(
+        format!("row field count is {}, expected {}", count, self.columns.len())));
+    }
+
+    let mut row = Vec::with_capacity(self.columns.len());
+    for &ty in &self.columns {
+      let len = read_i32(&mut self.inner)?;
+      if len == -1 {
+        row.push(None);
+        continue;
+      }
+      if len < 0 {
+        return Err(WireError::BadFormat(format!("invalid field size {}", len)));
+      }
+      // every type is fixed width, so don't trust the length any further
+      if len as usize != ty.size() {
+        return Err(WireError::BadLength { expected: ty.size(), actual: len as usize });
+      }
+
+      self.buf.resize(len as usize, 0);
+      self.inner.read_exact(&mut self.buf)?;
+      row.push(Some(Value::recv(ty, &self.buf)?));
+    }
+
+    Ok(Some(row))
+  }
+
+  pub fn into_inner(self) -> R {
+    self.inner
+  }
+}
+
+fn read_i32<R: Read>(r: &mut R) -> Result<i32, WireError> {
+  let mut b = [0u8; 4];
+  r.read_exact(&mut b)?;
+  Ok(i32::from_be_bytes(b))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::{DATEVAL_NOEND, DT_NOBEGIN, INTERVAL_NOEND, USECS_PER_HOUR};
+  use super::super::interval_is_noend;
+
+  #[test]
+  fn test_send_recv() {
+    let mut buf = Vec::new();
+    date_send(-1, &mut buf);
+    assert_eq!(vec![0xff, 0xff, 0xff, 0xff], buf);
+    assert_eq!(-1, date_recv(&buf).unwrap());
+
+    buf.clear();
+    date_send(DATEVAL_NOEND, &mut buf);
+    assert_eq!(vec![0x7f, 0xff, 0xff, 0xff], buf);
+
+    buf.clear();
+    timestamp_send(DT_NOBEGIN, &mut buf);
+    assert_eq!(vec![0x80, 0, 0, 0, 0, 0, 0, 0], buf);
+    assert_eq!(DT_NOBEGIN, timestamptz_recv(&buf).unwrap());
+
+    buf.clear();
+    interval_send(&Interval::new(14, 3, 4 * USECS_PER_HOUR), &mut buf);
+    assert_eq!(vec![0, 0, 0, 3, 0x5a, 0x4e, 0x90, 0x00, 0, 0, 0, 3, 0, 0, 0, 14], buf);
+    let span = interval_recv(&buf).unwrap();
+    assert_eq!((14, 3, 4 * USECS_PER_HOUR), (span.month(), span.day(), span.time()));
+
+    buf.clear();
+    interval_send(&INTERVAL_NOEND, &mut buf);
+    assert!(interval_is_noend(&interval_recv(&buf).unwrap()));
+
+    buf.clear();
+    timetz_send(&TimeTz::new(USECS_PER_HOUR, -19800), &mut buf);
+    let t = timetz_recv(&buf).unwrap();
+    assert_eq!((USECS_PER_HOUR, -19800), (t.time(), t.zone()));
+  }
+
+  #[test]
+  fn test_recv_failure() {
+    match date_recv(&[0, 0, 0]) {
+      Err(WireError::BadLength { expected: 4, actual: 3 }) => {},
+      _ => assert!(false, "short input must fail")
+    };
+
+    let mut buf = Vec::new();
+    time_send(USECS_PER_DAY + 1, &mut buf);
+    match time_recv(&buf) {
+      Err(WireError::OutOfRange("time")) => {},
+      _ => assert!(false, "time must be within a day")
+    };
+
+    buf.clear();
+    date_send(DATEVAL_NOEND - 1, &mut buf);
+    match date_recv(&buf) {
+      Err(WireError::OutOfRange("date")) => {},
+      _ => assert!(false, "date must be within the date_in() range")
+    };
+
+    buf.clear();
+    timestamp_send(DT_NOBEGIN + 1, &mut buf);
+    match timestamp_recv(&buf) {
+      Err(WireError::OutOfRange("timestamp")) => {},
+      _ => assert!(false, "timestamp must be within the timestamp_in() range")
+    };
+
+    buf.clear();
+    timetz_send(&TimeTz::new(0, TZDISP_LIMIT), &mut buf);
+    match timetz_recv(&buf) {
+      Err(WireError::OutOfRange("time zone displacement")) => {},
+      _ => assert!(false, "zone must be within the displacement limit")
+    };
+  }
+
+  #[test]
+  fn test_copy_binary() {
+    let columns = vec![ColumnType::Date, ColumnType::Timestamp, ColumnType::Interval];
+    let mut w = CopyWriter::new(Vec::new(), columns.clone()).unwrap();
+    w.write_row(&[Some(Value::Date(6159)), None,
+                  Some(Value::Interval(Interval::new(1, 2, 3)))]).unwrap();
+    w.write_row(&[None, Some(Value::Timestamp(DT_NOBEGIN)), None]).unwrap();
+    assert!(w.write_row(&[Some(Value::Time(0)), None, None]).is_err());
+    let data = w.finish().unwrap();
+
+    assert_eq!(&COPY_SIGNATURE, &data[..11]);
+    assert_eq!(&[0xff, 0xff], &data[data.len() - 2..]);
+
+    let mut r = CopyReader::new(&data[..], columns).unwrap();
+    let row = r.read_row().unwrap().unwrap();
+    match (row[0], row[1], row[2]) {
+      (Some(Value::Date(6159)), None, Some(Value::Interval(span))) => {
+        assert_eq!((1, 2, 3), (span.month(), span.day(), span.time()));
+      }
+      _ => assert!(false, "unexpected row {:?}", row)
+    }
+
+    let row = r.read_row().unwrap().unwrap();
+    match (row[0], row[1], row[2]) {
+      (None, Some(Value::Timestamp(DT_NOBEGIN)), None) => {}
+      _ => assert!(false, "unexpected row {:?}", row)
+    }
+
+    assert!(r.read_row().unwrap().is_none());
+    assert!(r.read_row().unwrap().is_none());
+  }
+
+  #[test]
+  fn test_copy_binary_bad_header() {
+    match CopyReader::new(&b"PGCOPY\n\xff\r\n\x01"[..], vec![]) {
+      Err(WireError::BadFormat(_)) => {},
+      _ => assert!(false, "signature must be checked")
+    };
+
+    // header extension shorter than its length
+    let mut data = COPY_SIGNATURE.to_vec();
+    data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 8, 1, 2, 3]);
+    match CopyReader::new(&data[..], vec![]) {
+      Err(WireError::BadFormat(_)) => {},
+      _ => assert!(false, "truncated header extension must fail")
+    };
+  }
+
+  #[test]
+  fn test_copy_binary_bad_field_size() {
+    let mut data = COPY_SIGNATURE.to_vec();
+    data.extend_from_slice(&[0; 8]);
+    data.extend_from_slice(&[0, 1, 0x7f, 0xff, 0xff, 0xff]);
+
+    let mut r = CopyReader::new(&data[..], vec![ColumnType::Date]).unwrap();
+    match r.read_row() {
+      Err(WireError::BadLength { expected: 4, actual: 0x7fffffff }) => {},
+      _ => assert!(false, "field size must match the column type")
+    };
+  }
+}
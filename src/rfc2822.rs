@@ -0,0 +1,465 @@
+//! RFC 5322 (formerly RFC 2822) date-time, as used in email headers.
+//!
+//! `decode` reads "Fri, 11 Nov 2016 10:00:00 +0900" into a `TimestampTz`.
+//! Day and month names are looked up in `DATETK_TBL` and numeric zones are
+//! read by `decode_timezone`. Comments between tokens are part of the
+//! current syntax and always skipped. The obsolete syntax of section 4.3
+//! (two-digit years, and zone names such as "GMT" and "EST" or military
+//! zones) is only accepted when enabled in `Lenience`.
+//!
+//! `encode` writes the section 3.3 form with a numeric zone.
+
+use std::ops::Range;
+
+use super::{datebsearch, date2j, decode_timezone, isdigit, isalpha, isleap, j2day,
+            timestamp2tm, timestamp_not_finite, tm2timestamp, DateTimeErrorKind,
+            DateTimeParseError, TimeMeta, TimestampTz, DATETK_TBL, DAYS, DAY_TAB, DOW, DTZ,
+            MONTH, MONTHS, TZ, USECS_PER_SEC};
+
+const TYPE_NAME: &'static str = "timestamp with time zone";
+
+/// Which obsolete forms of RFC 5322 section 4.3 `decode` accepts.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Lenience {
+  /// two- and three-digit years, read as 1950-2049 and 1900 + year (obs-year)
+  pub years: bool,
+  /// "UT", "GMT", the US zone names and the military letters (obs-zone)
+  pub zones: bool
+}
+
+/// Only the current syntax of section 3.3.
+pub const STRICT: Lenience = Lenience { years: false, zones: false };
+
+/// The current syntax and all of the obsolete forms.
+pub const OBSOLETE: Lenience = Lenience { years: true, zones: true };
+
+/// The fields of a date-time, with the spans of those that can be out of
+/// range.
+pub(crate) struct Fields {
+  pub(crate) wday: Option<(i32, Range<usize>)>,
+  pub(crate) mday: (i32, Range<usize>),
+  pub(crate) mon: i32,
+  pub(crate) year: i32,
+  /// hour, minute and second
+  pub(crate) time: [(i32, Range<usize>); 3],
+  /// seconds west of UTC
+  pub(crate) tz: i32
+}
+
+/// Reads the tokens of a date-time in one of the fixed Internet formats,
+/// reporting errors against the whole input.
+pub(crate) struct Scanner<'a> {
+  s: &'a str,
+  buf: &'a [u8],
+  pos: usize,
+  comments: bool,
+  type_name: &'static str
+}
+
+impl<'a> Scanner<'a> {
+  /// A scanner for s. If comments is set, skip_cfws() skips comments.
+  pub(crate) fn new(s: &'a str, comments: bool) -> Scanner<'a> {
+    Scanner {
+      comments: comments,
+      ..Scanner::for_type(s, TYPE_NAME)
+    }
+  }
+
+  /// A scanner for s that reports errors for the named type.
+  pub(crate) fn for_type(s: &'a str, type_name: &'static str) -> Scanner<'a> {
+    Scanner {
+      s: s,
+      buf: s.as_bytes(),
+      pos: 0,
+      comments: false,
+      type_name: type_name
+    }
+  }
+
+  pub(crate) fn error(&self, kind: DateTimeErrorKind, span: Range<usize>) -> DateTimeParseError {
+    DateTimeParseError::new(kind, self.s).locate(self.s, self.type_name, &[]).at(span)
+  }
+
+  /// The error for an unexpected character, or the end of input.
+  pub(crate) fn unexpected(&self) -> DateTimeParseError {
+    let end = if self.pos < self.buf.len() { self.pos + 1 } else { self.pos };
+    self.error(DateTimeErrorKind::BadFormat, self.pos..end)
+  }
+
+  /// The offset of the next character.
+  pub(crate) fn offset(&self) -> usize {
+    self.pos
+  }
+
+  pub(crate) fn peek(&self) -> Option<u8> {
+    self.buf.get(self.pos).cloned()
+  }
+
+  /// Skip folding white space, and comments if allowed. Fail if required
+  /// and there was none.
+  fn skip_cfws(&mut self, required: bool) -> Result<(), DateTimeParseError> {
+    let start = self.pos;
+    loop {
+      match self.peek() {
+        Some(b' ') | Some(b'\t') | Some(b'\r') | Some(b'\n') => self.pos += 1,
+        Some(b'(') if self.comments => self.skip_comment()?,
+        _ => break
+      }
+    }
+
+    if required && self.pos == start {
+      return Err(self.unexpected());
+    }
+    Ok(())
+  }
+
+  /// Skip a comment, which may nest and may contain quoted pairs.
+  fn skip_comment(&mut self) -> Result<(), DateTimeParseError> {
+    let start = self.pos;
+    let mut depth = 0;
+    while let Some(c) = self.peek() {
+      self.pos += 1;
+      match c {
+        b'(' => depth += 1,
+        b')' => {
+          depth -= 1;
+          if depth == 0 {
+            return Ok(());
+          }
+        }
+        b'\\' => self.pos += 1,
+        _ => {}
+      }
+    }
+
+    Err(self.error(DateTimeErrorKind::BadFormat, start..self.buf.len()))
+  }
+
+  pub(crate) fn expect(&mut self, c: u8) -> Result<(), DateTimeParseError> {
+    if self.peek() != Some(c) {
+      return Err(self.unexpected());
+    }
+    self.pos += 1;
+    Ok(())
+  }
+
+  /// Consume a run of characters matching f and return its span.
+  fn take(&mut self, f: fn(u8) -> bool) -> Range<usize> {
+    let start = self.pos;
+    while self.peek().map_or(false, f) {
+      self.pos += 1;
+    }
+    start..self.pos
+  }
+
+  /// Fail unless all input has been read.
+  pub(crate) fn end(&self) -> Result<(), DateTimeParseError> {
+    if self.pos < self.buf.len() {
+      return Err(self.unexpected());
+    }
+    Ok(())
+  }
+
+  /// Consume min to max digits and return their value and span.
+  pub(crate) fn number(&mut self, min: usize, max: usize) -> Result<(i32, Range<usize>), DateTimeParseError> {
+    let span = self.digits();
+    if span.len() < min || span.len() > max {
+      return Err(self.error(DateTimeErrorKind::BadFormat, span));
+    }
+    Ok((self.value(span.clone()), span))
+  }
+
+  /// Consume a run of digits, possibly empty, and return its span.
+  pub(crate) fn digits(&mut self) -> Range<usize> {
+    self.take(isdigit)
+  }
+
+  /// The input at span.
+  pub(crate) fn bytes(&self, span: Range<usize>) -> &'a [u8] {
+    &self.buf[span]
+  }
+
+  /// The value of the digits at span, which must fit in an i32.
+  pub(crate) fn value(&self, span: Range<usize>) -> i32 {
+    self.buf[span].iter().fold(0, |n, &c| n * 10 + (c - b'0') as i32)
+  }
+
+  /// Consume a word and return it in lower case, with its span.
+  pub(crate) fn word(&mut self) -> (Vec<u8>, Range<usize>) {
+    let span = self.take(isalpha);
+    (self.buf[span.clone()].to_ascii_lowercase(), span)
+  }
+
+  /// Consume a day or month name of token type ty and return its value.
+  pub(crate) fn name(&mut self, ty: i8) -> Result<(i32, Range<usize>), DateTimeParseError> {
+    let (word, span) = self.word();
+    match datebsearch(&word, &DATETK_TBL) {
+      // only the three-letter abbreviations are allowed
+      Some(tk) if tk.ty == ty && word.len() == 3 => Ok((tk.value, span)),
+      _ => Err(self.error(DateTimeErrorKind::BadFormat, span))
+    }
+  }
+
+  /// Consume hour ":" minute [ ":" second ], each of two digits. The
+  /// seconds are required unless optional_sec is set.
+  pub(crate) fn time_of_day(&mut self, optional_sec: bool)
+                            -> Result<[(i32, Range<usize>); 3], DateTimeParseError> {
+    let hour = self.number(2, 2)?;
+    self.expect(b':')?;
+    let min = self.number(2, 2)?;
+    let sec = if optional_sec && self.peek() != Some(b':') {
+      (0, min.1.clone())
+    } else {
+      self.expect(b':')?;
+      self.number(2, 2)?
+    };
+    Ok([hour, min, sec])
+  }
+
+  /// Check the ranges of the fields and convert them to a timestamp. The
+  /// weekday, if given, must fall on the date.
+  pub(crate) fn timestamp(&self, f: Fields) -> Result<TimestampTz, DateTimeParseError> {
+    let [(hour, ref hour_span), (min, ref min_span), (sec, ref sec_span)] = f.time;
+
+    if f.mday.0 < 1 || f.mday.0 > DAY_TAB[isleap(f.year) as usize][(f.mon - 1) as usize] {
+      return Err(self.error(DateTimeErrorKind::FieldOverflow, f.mday.1));
+    }
+    if hour > 23 {
+      return Err(self.error(DateTimeErrorKind::FieldOverflow, hour_span.clone()));
+    }
+    if min > 59 {
+      return Err(self.error(DateTimeErrorKind::FieldOverflow, min_span.clone()));
+    }
+    // allow a leap second
+    if sec > 60 {
+      return Err(self.error(DateTimeErrorKind::FieldOverflow, sec_span.clone()));
+    }
+
+    let mut tm = TimeMeta::empty();
+    tm.tm_year = f.year;
+    tm.tm_mon = f.mon;
+    tm.tm_mday = f.mday.0;
+    tm.tm_hour = hour;
+    tm.tm_min = min;
+    tm.tm_sec = sec;
+
+    // the year must be in range before the weekday can be checked
+    let t = match tm2timestamp(&tm, 0, Some(f.tz)) {
+      Some(t) => t,
+      None => return Err(self.error(DateTimeErrorKind::OutOfRange, 0..self.s.len()))
+    };
+
+    if let Some((val, span)) = f.wday {
+      if j2day(date2j(f.year, f.mon, f.mday.0)) != val {
+        return Err(self.error(DateTimeErrorKind::WeekdayMismatch, span));
+      }
+    }
+
+    Ok(t)
+  }
+}
+
+/// Decode an RFC 5322 date-time. The weekday, if given, must fall on the
+/// date.
+pub fn decode(s: &str, lenience: Lenience) -> Result<TimestampTz, DateTimeParseError> {
+  let mut sc = Scanner::new(s, true);
+
+  // [ day-of-week "," ]
+  sc.skip_cfws(false)?;
+  let mut wday = None;
+  if sc.peek().map_or(false, isalpha) {
+    wday = Some(sc.name(DOW)?);
+    sc.skip_cfws(false)?;
+    sc.expect(b',')?;
+    sc.skip_cfws(false)?;
+  }
+
+  // date = day month year
+  let mday = sc.number(1, 2)?;
+  sc.skip_cfws(true)?;
+  let (mon, _) = sc.name(MONTH)?;
+  sc.skip_cfws(true)?;
+  let (mut year, year_span) = sc.number(if lenience.years { 2 } else { 4 }, 9)?;
+  match year_span.len() {
+    // obs-year: add 2000 to years below 50, otherwise 1900
+    2 if year < 50 => year += 2000,
+    2 | 3 => year += 1900,
+    _ => {}
+  }
+  sc.skip_cfws(true)?;
+
+  let time = sc.time_of_day(true)?;
+  sc.skip_cfws(true)?;
+
+  // zone = ( "+" / "-" ) 4DIGIT, or obs-zone
+  let zone_start = sc.pos;
+  let tz = match sc.peek() {
+    Some(b'+') | Some(b'-') => {
+      sc.pos += 1;
+      let span = zone_start..sc.number(4, 4)?.1.end;
+      decode_timezone(&s[span.clone()]).map_err(|e| sc.error(e.kind(), span))?
+    }
+    Some(c) if isalpha(c) => {
+      let span = sc.take(isalpha);
+      if !lenience.zones {
+        return Err(sc.error(DateTimeErrorKind::BadFormat, span));
+      }
+      obsolete_zone(&sc.buf[span.clone()])
+        .ok_or_else(|| sc.error(DateTimeErrorKind::UnknownToken, span))?
+    }
+    _ => return Err(sc.unexpected())
+  };
+
+  sc.skip_cfws(false)?;
+  sc.end()?;
+
+  sc.timestamp(Fields {
+    wday: wday,
+    mday: mday,
+    mon: mon,
+    year: year,
+    time: time,
+    tz: tz
+  })
+}
+
+/// Seconds west of UTC of an obsolete zone name. The military letters were
+/// defined with the wrong sign, so RFC 5322 says to read them as "-0000",
+/// i.e. UTC.
+fn obsolete_zone(name: &[u8]) -> Option<i32> {
+  let name = name.to_ascii_lowercase();
+  if name.len() == 1 && name[0] != b'j' {
+    return Some(0);
+  }
+
+  match datebsearch(&name, &DATETK_TBL) {
+    Some(tk) if tk.ty == TZ || tk.ty == DTZ => Some(-tk.value),
+    _ => None
+  }
+}
+
+/// Encode t as an RFC 5322 date-time in the given zone (seconds west of
+/// UTC), e.g. "Fri, 11 Nov 2016 10:00:00 +0900". Fractional seconds and
+/// any seconds of the zone offset are dropped. Return None for infinite
+/// timestamps and years before 1 AD.
+pub fn encode(t: TimestampTz, zone: i32) -> Option<String> {
+  if timestamp_not_finite(t) {
+    return None;
+  }
+
+  let local = match t.checked_sub(zone as i64 * USECS_PER_SEC) {
+    Some(l) => l,
+    None => return None
+  };
+  let tm = match timestamp2tm(local) {
+    Some((tm, _)) if tm.tm_year > 0 => tm,
+    _ => return None
+  };
+
+  let wday = j2day(date2j(tm.tm_year, tm.tm_mon, tm.tm_mday));
+  let east = -zone;
+  Some(format!("{}, {:02} {} {:04} {:02}:{:02}:{:02} {}{:02}{:02}",
+               &DAYS[wday as usize][..3], tm.tm_mday, MONTHS[(tm.tm_mon - 1) as usize],
+               tm.tm_year, tm.tm_hour, tm.tm_min, tm.tm_sec,
+               if east < 0 { '-' } else { '+' }, east.abs() / 3600, east.abs() / 60 % 60))
+}
+
+/// Helpers shared by the tests of the formats read with `Scanner`.
+#[cfg(test)]
+pub(crate) mod test_helpers {
+  use super::super::{date2j, time2t, DateTimeErrorKind, DateTimeParseError, Timestamp,
+                     POSTGRES_EPOCH_JDATE, USECS_PER_DAY};
+
+  /// The timestamp of a Gregorian date and time of day.
+  pub(crate) fn ts(y: i32, m: i32, d: i32, hour: i32, min: i32, sec: i32) -> Timestamp {
+    (date2j(y, m, d) - POSTGRES_EPOCH_JDATE) as i64 * USECS_PER_DAY + time2t(hour, min, sec, 0)
+  }
+
+  /// The kind of a failure and the text it points at.
+  pub(crate) fn error_at<T>(r: Result<T, DateTimeParseError>) -> (DateTimeErrorKind, String) {
+    let e = r.err().unwrap();
+    (e.kind(), e.input()[e.span().unwrap()].to_owned())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::test_helpers::{error_at, ts};
+  use super::super::USECS_PER_HOUR;
+  use super::super::DateTimeErrorKind::*;
+
+  #[test]
+  fn test_decode() {
+    let t = ts(2016, 11, 11, 1, 0, 0);
+    assert_eq!(t, decode("Fri, 11 Nov 2016 10:00:00 +0900", STRICT).ok().unwrap());
+    assert_eq!(t, decode("11 Nov 2016 10:00 +0900", STRICT).ok().unwrap());
+    assert_eq!(t, decode("fri,11 nov 2016 01:00:00 -0000", STRICT).ok().unwrap());
+    assert_eq!(t, decode("  Fri, 11\r\n Nov 2016 00:00:00 -0100  ", STRICT).ok().unwrap());
+    assert_eq!(ts(2016, 12, 31, 23, 59, 59) + 1000000,
+               decode("Sat, 31 Dec 2016 23:59:60 +0000", STRICT).ok().unwrap());
+
+    // obsolete syntax
+    assert_eq!(t, decode("Fri, 11 Nov 2016 01:00:00 GMT", OBSOLETE).ok().unwrap());
+    assert_eq!(t, decode("Thu, 10 Nov 2016 20:00:00 EST", OBSOLETE).ok().unwrap());
+    assert_eq!(t, decode("Thu, 10 Nov 2016 18:00:00 pdt", OBSOLETE).ok().unwrap());
+    assert_eq!(t, decode("Fri, 11 Nov 2016 01:00:00 Z", OBSOLETE).ok().unwrap());
+    assert_eq!(t, decode("Fri, 11 Nov 16 01:00:00 UT", OBSOLETE).ok().unwrap());
+    assert_eq!(ts(1997, 2, 7, 1, 0, 0), decode("7 Feb 97 01:00 +0000", OBSOLETE).ok().unwrap());
+    assert_eq!(ts(2003, 2, 7, 1, 0, 0), decode("7 Feb 103 01:00 +0000", OBSOLETE).ok().unwrap());
+
+    // comments are current syntax
+    assert_eq!(t, decode("11 Nov 2016 10:00:00 +0900 (JST)", STRICT).ok().unwrap());
+    assert_eq!(t, decode("Fri, (day (of week)) 11 Nov 2016 10:00:00 +0900 (\\)\\))", STRICT)
+                  .ok().unwrap());
+    assert_eq!(t, decode("Fri,(x)11(y)Nov(z)2016 10:00:00 +0900", STRICT).ok().unwrap());
+
+    assert_eq!((BadFormat, "GMT".to_owned()),
+               error_at(decode("Fri, 11 Nov 2016 01:00:00 GMT", STRICT)));
+    assert_eq!((BadFormat, "16".to_owned()),
+               error_at(decode("Fri, 11 Nov 16 01:00:00 +0000", STRICT)));
+    assert_eq!((BadFormat, "(JST".to_owned()),
+               error_at(decode("11 Nov 2016 10:00:00 +0900 (JST", STRICT)));
+    assert_eq!((UnknownToken, "J".to_owned()),
+               error_at(decode("11 Nov 2016 10:00:00 J", OBSOLETE)));
+    assert_eq!((BadFormat, "Thurs".to_owned()),
+               error_at(decode("Thurs, 10 Nov 2016 10:00:00 +0900", STRICT)));
+    assert_eq!((BadFormat, "N".to_owned()),
+               error_at(decode("11Nov 2016 10:00:00 +0900", STRICT)));
+    assert_eq!((BadFormat, "0".to_owned()),
+               error_at(decode("11 Nov 2016 10:0 +0900", STRICT)));
+    assert_eq!((BadFormat, "x".to_owned()),
+               error_at(decode("11 Nov 2016 10:00:00 +0900x", STRICT)));
+    assert_eq!((FieldOverflow, "31".to_owned()),
+               error_at(decode("31 Nov 2016 10:00:00 +0900", STRICT)));
+    assert_eq!((FieldOverflow, "24".to_owned()),
+               error_at(decode("11 Nov 2016 24:00:00 +0900", STRICT)));
+    assert_eq!((TzDispOverflow, "+1600".to_owned()),
+               error_at(decode("11 Nov 2016 10:00:00 +1600", STRICT)));
+    assert_eq!((WeekdayMismatch, "Thu".to_owned()),
+               error_at(decode("Thu, 11 Nov 2016 10:00:00 +0900", STRICT)));
+    assert_eq!((OutOfRange, "Thu, 11 Nov 999999999 10:00:00 +0900".to_owned()),
+               error_at(decode("Thu, 11 Nov 999999999 10:00:00 +0900", STRICT)));
+    assert_eq!((OutOfRange, "Sat, 1 Jan 294277 00:00:00 +0000".to_owned()),
+               error_at(decode("Sat, 1 Jan 294277 00:00:00 +0000", STRICT)));
+    assert_eq!("invalid input syntax for type timestamp with time zone: '11 Nov 2016'",
+               decode("11 Nov 2016", STRICT).err().unwrap().to_string());
+  }
+
+  #[test]
+  fn test_encode() {
+    let t = ts(2016, 11, 11, 1, 0, 0);
+    assert_eq!("Fri, 11 Nov 2016 10:00:00 +0900", encode(t, -9 * 3600).unwrap());
+    assert_eq!("Thu, 10 Nov 2016 20:00:00 -0500", encode(t, 5 * 3600).unwrap());
+    assert_eq!("Fri, 11 Nov 2016 06:30:00 +0530", encode(t, -19800).unwrap());
+    assert_eq!("Fri, 11 Nov 2016 01:00:00 +0000", encode(t + 999999, 0).unwrap());
+    assert_eq!("Mon, 01 Jan 0001 00:00:00 +0000", encode(ts(1, 1, 1, 0, 0, 0), 0).unwrap());
+    assert_eq!(None, encode(ts(0, 12, 31, 0, 0, 0), 0));
+    assert_eq!(None, encode(super::super::DT_NOEND, 0));
+
+    for zone in [0, -9 * 3600, 8 * 3600, -19800].iter() {
+      let t = t + 7 * USECS_PER_HOUR;
+      assert_eq!(t, decode(&encode(t, *zone).unwrap(), STRICT).ok().unwrap());
+    }
+  }
+}
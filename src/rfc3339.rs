@@ -0,0 +1,192 @@
+//! RFC 3339 date-time, e.g. "2016-11-11T10:00:00.5+09:00".
+//!
+//! Unlike `timestamptz_in`, which takes whatever PostgreSQL would, `decode`
+//! accepts only the exact RFC 3339 profile: a full date, "T" or a space, a
+//! full time with an optional fraction of 1 to 9 digits, and an offset that
+//! is "Z" or "+hh:mm"/"-hh:mm". Fractions beyond microseconds are rounded.
+//!
+//! `encode` writes a chosen number of fractional digits, and either "Z" or
+//! "+00:00" for UTC.
+
+use super::{decode_timezone, parse_fraction, timestamp2tm, timestamp_not_finite,
+            DateTimeErrorKind, DateTimeParseError, Rounding, TimestampTz, USECS_PER_SEC};
+use super::rfc2822::{Fields, Scanner};
+
+/// Fractional digits written by `encode`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Precision {
+  /// 2016-11-11T10:00:00Z
+  Seconds,
+  /// 2016-11-11T10:00:00.500Z
+  Millis,
+  /// 2016-11-11T10:00:00.500000Z
+  Micros,
+  /// 2016-11-11T10:00:00.500000000Z
+  Nanos
+}
+
+/// Decode an RFC 3339 date-time, rejecting anything outside the profile.
+pub fn decode(s: &str) -> Result<TimestampTz, DateTimeParseError> {
+  let mut sc = Scanner::new(s, false);
+
+  // full-date = date-fullyear "-" date-month "-" date-mday
+  let (year, _) = sc.number(4, 4)?;
+  sc.expect(b'-')?;
+  let (mon, mon_span) = sc.number(2, 2)?;
+  sc.expect(b'-')?;
+  let mday = sc.number(2, 2)?;
+  if mon < 1 || mon > 12 {
+    return Err(sc.error(DateTimeErrorKind::FieldOverflow, mon_span));
+  }
+
+  match sc.peek() {
+    Some(b'T') | Some(b't') | Some(b' ') => sc.expect(sc.peek().unwrap())?,
+    _ => return Err(sc.unexpected())
+  }
+
+  // partial-time = time-hour ":" time-minute ":" time-second [time-secfrac]
+  let time = sc.time_of_day(false)?;
+  let mut fsec = 0;
+  if sc.peek() == Some(b'.') {
+    sc.expect(b'.')?;
+    let (_, span) = sc.number(1, 9)?;
+    fsec = parse_fraction(&s.as_bytes()[span], 6, Rounding::HalfUp).unwrap();
+  }
+
+  // time-offset = "Z" / time-numoffset
+  let tz = match sc.peek() {
+    Some(b'Z') | Some(b'z') => {
+      sc.expect(sc.peek().unwrap())?;
+      0
+    }
+    Some(b'+') | Some(b'-') => {
+      let sign = sc.peek().unwrap();
+      sc.expect(sign)?;
+      let (_, hour_span) = sc.number(2, 2)?;
+      sc.expect(b':')?;
+      let (_, min_span) = sc.number(2, 2)?;
+      let span = hour_span.start - 1..min_span.end;
+      decode_timezone(&s[span.clone()]).map_err(|e| sc.error(e.kind(), span))?
+    }
+    _ => return Err(sc.unexpected())
+  };
+  sc.end()?;
+
+  let t = sc.timestamp(Fields {
+    wday: None,
+    mday: mday,
+    mon: mon,
+    year: year,
+    time: time,
+    tz: tz
+  })?;
+
+  match t.checked_add(fsec) {
+    Some(t) if !timestamp_not_finite(t) => Ok(t),
+    _ => Err(sc.error(DateTimeErrorKind::OutOfRange, 0..s.len()))
+  }
+}
+
+/// Encode t as an RFC 3339 date-time in the given zone (seconds west of
+/// UTC). Digits beyond precision are truncated, as are any seconds of the
+/// zone offset. If zulu is set, a zero offset is written "Z" rather than
+/// "+00:00". Return None for infinite timestamps and years that do not
+/// have four digits.
+pub fn encode(t: TimestampTz, zone: i32, precision: Precision, zulu: bool) -> Option<String> {
+  if timestamp_not_finite(t) {
+    return None;
+  }
+
+  let local = match t.checked_sub(zone as i64 * USECS_PER_SEC) {
+    Some(l) => l,
+    None => return None
+  };
+  let (tm, fsec) = match timestamp2tm(local) {
+    Some((tm, fsec)) if tm.tm_year >= 0 && tm.tm_year <= 9999 => (tm, fsec),
+    _ => return None
+  };
+
+  let mut buf = format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+                        tm.tm_year, tm.tm_mon, tm.tm_mday, tm.tm_hour, tm.tm_min, tm.tm_sec);
+  match precision {
+    Precision::Seconds => {}
+    Precision::Millis => buf.push_str(&format!(".{:03}", fsec / 1000)),
+    Precision::Micros => buf.push_str(&format!(".{:06}", fsec)),
+    Precision::Nanos => buf.push_str(&format!(".{:06}000", fsec))
+  }
+
+  let east = -zone;
+  if east == 0 && zulu {
+    buf.push('Z');
+  } else {
+    buf.push_str(&format!("{}{:02}:{:02}", if east < 0 { '-' } else { '+' },
+                          east.abs() / 3600, east.abs() / 60 % 60));
+  }
+
+  Some(buf)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::DT_NOEND;
+  use super::super::rfc2822::test_helpers::{error_at, ts};
+  use super::super::DateTimeErrorKind::*;
+
+  #[test]
+  fn test_decode() {
+    let t = ts(2016, 11, 11, 1, 0, 0);
+    assert_eq!(t, decode("2016-11-11T01:00:00Z").ok().unwrap());
+    assert_eq!(t, decode("2016-11-11t01:00:00z").ok().unwrap());
+    assert_eq!(t, decode("2016-11-11 10:00:00+09:00").ok().unwrap());
+    assert_eq!(t, decode("2016-11-10T20:00:00-05:00").ok().unwrap());
+    assert_eq!(t, decode("2016-11-11T01:00:00-00:00").ok().unwrap());
+    assert_eq!(t + 500000, decode("2016-11-11T01:00:00.5Z").ok().unwrap());
+    assert_eq!(t + 123457, decode("2016-11-11T01:00:00.123456789Z").ok().unwrap());
+    assert_eq!(t + 1, decode("2016-11-11T01:00:00.0000005Z").ok().unwrap());
+    assert_eq!(t + USECS_PER_SEC, decode("2016-11-11T01:00:00.9999999Z").ok().unwrap());
+    assert_eq!(ts(2016, 12, 31, 23, 59, 59) + USECS_PER_SEC,
+               decode("2016-12-31T23:59:60Z").ok().unwrap());
+
+    assert_eq!((BadFormat, "".to_owned()), error_at(decode("2016-11-11T01:00:00")));
+    assert_eq!((BadFormat, " ".to_owned()), error_at(decode("2016-11-11T01:00:00 +09:00")));
+    assert_eq!((BadFormat, "Z".to_owned()), error_at(decode("2016-11-11T01:00Z")));
+    assert_eq!((BadFormat, "_".to_owned()), error_at(decode("2016-11-11_01:00:00Z")));
+    assert_eq!((BadFormat, "1".to_owned()), error_at(decode("2016-11-1T01:00:00Z")));
+    assert_eq!((BadFormat, "20161".to_owned()), error_at(decode("20161-11-11T01:00:00Z")));
+    assert_eq!((BadFormat, "".to_owned()), error_at(decode("2016-11-11T01:00:00.Z")));
+    assert_eq!((BadFormat, "1234567890".to_owned()),
+               error_at(decode("2016-11-11T01:00:00.1234567890Z")));
+    assert_eq!((BadFormat, "0900".to_owned()), error_at(decode("2016-11-11T01:00:00+0900")));
+    assert_eq!((BadFormat, "Z".to_owned()), error_at(decode("2016-11-11T01:00:00+09Z")));
+    assert_eq!((BadFormat, "x".to_owned()), error_at(decode("2016-11-11T01:00:00Zx")));
+    assert_eq!((FieldOverflow, "13".to_owned()), error_at(decode("2016-13-11T01:00:00Z")));
+    assert_eq!((FieldOverflow, "30".to_owned()), error_at(decode("2015-02-30T01:00:00Z")));
+    assert_eq!((FieldOverflow, "24".to_owned()), error_at(decode("2016-11-11T24:00:00Z")));
+    assert_eq!((TzDispOverflow, "+16:00".to_owned()),
+               error_at(decode("2016-11-11T01:00:00+16:00")));
+  }
+
+  #[test]
+  fn test_encode() {
+    let t = ts(2016, 11, 11, 1, 0, 0) + 123456;
+    assert_eq!("2016-11-11T01:00:00Z", encode(t, 0, Precision::Seconds, true).unwrap());
+    assert_eq!("2016-11-11T01:00:00+00:00", encode(t, 0, Precision::Seconds, false).unwrap());
+    assert_eq!("2016-11-11T01:00:00.123Z", encode(t, 0, Precision::Millis, true).unwrap());
+    assert_eq!("2016-11-11T01:00:00.123456Z", encode(t, 0, Precision::Micros, true).unwrap());
+    assert_eq!("2016-11-11T01:00:00.123456000Z", encode(t, 0, Precision::Nanos, true).unwrap());
+    assert_eq!("2016-11-11T10:00:00.123+09:00",
+               encode(t, -9 * 3600, Precision::Millis, true).unwrap());
+    assert_eq!("2016-11-10T20:00:00-05:00", encode(t, 5 * 3600, Precision::Seconds, true).unwrap());
+    assert_eq!("0000-01-01T00:00:00Z", encode(ts(0, 1, 1, 0, 0, 0), 0, Precision::Seconds, true)
+                                       .unwrap());
+    assert_eq!(None, encode(ts(10000, 1, 1, 0, 0, 0), 0, Precision::Seconds, true));
+    assert_eq!(None, encode(DT_NOEND, 0, Precision::Seconds, true));
+
+    for zone in [0, -9 * 3600, 5 * 3600, -19800].iter() {
+      for p in [Precision::Micros, Precision::Nanos].iter() {
+        assert_eq!(t, decode(&encode(t, *zone, *p, false).unwrap()).ok().unwrap());
+      }
+    }
+  }
+}
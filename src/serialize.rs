@@ -0,0 +1,336 @@
+//! Serde support for date/time values, behind the `serde` feature.
+//!
+//! `Date`, `Time`, `Timestamp` and `TimestampTz` are plain integers, so by
+//! default they serialize as their internal values: days or microseconds
+//! counted from 2000-01-01. `Interval` serializes as a struct of its `month`,
+//! `day` and `time` fields.
+//!
+//! The modules below select another representation with
+//! `#[serde(with = "...")]`:
+//!
+//! * `date::iso`, `time::iso`, `timestamp::iso`, `timestamptz::iso`: text in
+//!   the ISO DateStyle, e.g. "2016-11-11 10:00:00+00". Strings are read back
+//!   with `decode_datetime`, so any input the SQL layer accepts is accepted.
+//! * `date::julian_day`: the Julian day number, e.g. 2457704.
+//! * `timestamp::unix_micros`, `timestamptz::unix_micros`: microseconds since
+//!   1970-01-01.
+//! * `interval::iso8601`: an ISO 8601 duration, e.g. "P1Y2M3DT4H5M6S".
+//!
+//! Infinite values are written as "infinity"/"-infinity" in text forms and
+//! keep their sentinel value in integer forms.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Event {
+//!   #[serde(with = "julian::serialize::timestamptz::iso")]
+//!   at: TimestampTz,
+//!   #[serde(with = "julian::serialize::interval::iso8601")]
+//!   every: Interval,
+//! }
+//! ```
+
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{DateTimeParseError, Interval};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Interval")]
+struct IntervalFields {
+  month: i32,
+  day: i32,
+  time: i64
+}
+
+impl Serialize for Interval {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    IntervalFields {
+      month: self.month,
+      day: self.day,
+      time: self.time
+    }.serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for Interval {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Interval, D::Error> {
+    let f = IntervalFields::deserialize(deserializer)?;
+    Ok(Interval::new(f.month, f.day, f.time))
+  }
+}
+
+fn serialize_text<S: Serializer>(text: Option<String>, type_name: &str, serializer: S)
+                                 -> Result<S::Ok, S::Error> {
+  match text {
+    Some(s) => serializer.serialize_str(&s),
+    None => Err(ser::Error::custom(format!("{} out of range", type_name)))
+  }
+}
+
+fn deserialize_text<'de, D, T, F>(deserializer: D, parse: F) -> Result<T, D::Error>
+    where D: Deserializer<'de>, F: FnOnce(&str) -> Result<T, DateTimeParseError> {
+  let s = String::deserialize(deserializer)?;
+  match parse(&s) {
+    Ok(v) => Ok(v),
+    Err(e) => Err(de::Error::custom(e))
+  }
+}
+
+pub mod date {
+  /// "2016-11-11"
+  pub mod iso {
+    use serde::{Deserializer, Serializer};
+    use {date_in, date_out, Date, DateStyle};
+
+    pub fn serialize<S: Serializer>(date: &Date, serializer: S) -> Result<S::Ok, S::Error> {
+      super::super::serialize_text(date_out(*date, DateStyle::ISO), "date", serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Date, D::Error> {
+      super::super::deserialize_text(deserializer, date_in)
+    }
+  }
+
+  /// Julian day number, e.g. 2457704 for 2016-11-11
+  pub mod julian_day {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use {date_not_finite, Date, IS_VALID_DATE, POSTGRES_EPOCH_JDATE};
+
+    pub fn serialize<S: Serializer>(date: &Date, serializer: S) -> Result<S::Ok, S::Error> {
+      if date_not_finite(*date) {
+        return date.serialize(serializer);
+      }
+      match date.checked_add(POSTGRES_EPOCH_JDATE) {
+        Some(jd) => jd.serialize(serializer),
+        None => Err(::serde::ser::Error::custom("date out of range"))
+      }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Date, D::Error> {
+      let jd = i32::deserialize(deserializer)?;
+      if date_not_finite(jd) {
+        return Ok(jd);
+      }
+      match jd.checked_sub(POSTGRES_EPOCH_JDATE) {
+        Some(date) if IS_VALID_DATE(date) => Ok(date),
+        _ => Err(::serde::de::Error::custom("date out of range"))
+      }
+    }
+  }
+}
+
+pub mod time {
+  /// "10:00:00.5"
+  pub mod iso {
+    use serde::{Deserializer, Serializer};
+    use {time_in, time_out, DateStyle, Time};
+
+    pub fn serialize<S: Serializer>(time: &Time, serializer: S) -> Result<S::Ok, S::Error> {
+      serializer.serialize_str(&time_out(*time, DateStyle::ISO))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Time, D::Error> {
+      super::super::deserialize_text(deserializer, time_in)
+    }
+  }
+}
+
+pub mod timestamp {
+  /// "2016-11-11 10:00:00.5"
+  pub mod iso {
+    use serde::{Deserializer, Serializer};
+    use {timestamp_in, timestamp_out, DateStyle, Timestamp};
+
+    pub fn serialize<S: Serializer>(ts: &Timestamp, serializer: S)
+                                    -> Result<S::Ok, S::Error> {
+      super::super::serialize_text(timestamp_out(*ts, DateStyle::ISO), "timestamp", serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D)
+                                                  -> Result<Timestamp, D::Error> {
+      super::super::deserialize_text(deserializer, timestamp_in)
+    }
+  }
+
+  /// Microseconds since 1970-01-01 00:00:00
+  pub mod unix_micros {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use {timestamp_not_finite, Timestamp, IS_VALID_TIMESTAMP, POSTGRES_EPOCH_JDATE,
+         UNIX_EPOCH_JDATE, USECS_PER_DAY};
+
+    const EPOCH_DIFF_USECS: i64 = (POSTGRES_EPOCH_JDATE - UNIX_EPOCH_JDATE) as i64 * USECS_PER_DAY;
+
+    pub fn serialize<S: Serializer>(ts: &Timestamp, serializer: S)
+                                    -> Result<S::Ok, S::Error> {
+      if timestamp_not_finite(*ts) {
+        return ts.serialize(serializer);
+      }
+      match ts.checked_add(EPOCH_DIFF_USECS) {
+        Some(us) => us.serialize(serializer),
+        None => Err(::serde::ser::Error::custom("timestamp out of range"))
+      }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D)
+                                                  -> Result<Timestamp, D::Error> {
+      let us = i64::deserialize(deserializer)?;
+      if timestamp_not_finite(us) {
+        return Ok(us);
+      }
+      match us.checked_sub(EPOCH_DIFF_USECS) {
+        Some(ts) if IS_VALID_TIMESTAMP(ts) => Ok(ts),
+        _ => Err(::serde::de::Error::custom("timestamp out of range"))
+      }
+    }
+  }
+}
+
+pub mod timestamptz {
+  /// "2016-11-11 10:00:00.5+00", always written in UTC
+  pub mod iso {
+    use serde::{Deserializer, Serializer};
+    use {timestamptz_in, timestamptz_out, DateStyle, TimestampTz};
+
+    pub fn serialize<S: Serializer>(ts: &TimestampTz, serializer: S)
+                                    -> Result<S::Ok, S::Error> {
+      super::super::serialize_text(timestamptz_out(*ts, DateStyle::ISO),
+                                   "timestamp with time zone", serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D)
+                                                  -> Result<TimestampTz, D::Error> {
+      super::super::deserialize_text(deserializer, timestamptz_in)
+    }
+  }
+
+  pub use super::timestamp::unix_micros;
+}
+
+pub mod interval {
+  /// "P1Y2M3DT4H5M6S"
+  pub mod iso8601 {
+    use serde::{Deserializer, Serializer};
+    use {decode_iso8601_interval, encode_interval, Interval, IntervalStyle};
+
+    pub fn serialize<S: Serializer>(span: &Interval, serializer: S)
+                                    -> Result<S::Ok, S::Error> {
+      serializer.serialize_str(&encode_interval(span, IntervalStyle::ISO8601))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D)
+                                                  -> Result<Interval, D::Error> {
+      super::super::deserialize_text(deserializer, |s: &str| {
+        match s {
+          "infinity" => Ok(::INTERVAL_NOEND),
+          "-infinity" => Ok(::INTERVAL_NOBEGIN),
+          _ => decode_iso8601_interval(s)
+        }
+      })
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde::{Deserialize, Serialize};
+  use serde_json;
+  use super::super::{date2j, time2t, Date, Interval, Time, Timestamp, TimestampTz,
+                     DATEVAL_NOEND, DT_NOBEGIN, POSTGRES_EPOCH_JDATE, USECS_PER_DAY,
+                     USECS_PER_HOUR};
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq)]
+  struct Row {
+    #[serde(with = "::serialize::date::iso")]
+    date: Date,
+    #[serde(with = "::serialize::date::julian_day")]
+    jd: Date,
+    #[serde(with = "::serialize::time::iso")]
+    time: Time,
+    #[serde(with = "::serialize::timestamp::iso")]
+    ts: Timestamp,
+    #[serde(with = "::serialize::timestamptz::iso")]
+    tstz: TimestampTz,
+    #[serde(with = "::serialize::timestamptz::unix_micros")]
+    unix: TimestampTz,
+    raw: Timestamp
+  }
+
+  #[test]
+  fn test_representations() {
+    let date = date2j(2016, 11, 11) - POSTGRES_EPOCH_JDATE;
+    let ts = date as i64 * USECS_PER_DAY + 10 * USECS_PER_HOUR + 500000;
+    let row = Row {
+      date: date,
+      jd: date,
+      time: time2t(10, 0, 0, 500000),
+      ts: ts,
+      tstz: ts,
+      unix: ts,
+      raw: ts
+    };
+
+    let json = serde_json::to_string(&row).unwrap();
+    assert_eq!(concat!(r#"{"date":"2016-11-11","jd":2457704,"time":"10:00:00.5","#,
+                       r#""ts":"2016-11-11 10:00:00.5","tstz":"2016-11-11 10:00:00.5+00","#,
+                       r#""unix":1478858400500000,"raw":532173600500000}"#), json);
+    assert_eq!(row, serde_json::from_str(&json).unwrap());
+
+    // strings go through the SQL parser
+    let json = concat!(r#"{"date":"Nov 11 2016","jd":2457704,"time":"100000.5","#,
+                       r#""ts":"20161111T100000.5","tstz":"2016-11-11 19:00:00.5+09","#,
+                       r#""unix":1478858400500000,"raw":532173600500000}"#);
+    assert_eq!(row, serde_json::from_str(json).unwrap());
+
+    assert!(serde_json::from_str::<Row>(&json.replace("Nov 11", "Nov 11 bogus")).is_err());
+  }
+
+  #[test]
+  fn test_infinity() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Inf {
+      #[serde(with = "::serialize::date::iso")]
+      date: Date,
+      #[serde(with = "::serialize::date::julian_day")]
+      jd: Date,
+      #[serde(with = "::serialize::timestamp::unix_micros")]
+      unix: Timestamp
+    }
+
+    let inf = Inf { date: DATEVAL_NOEND, jd: DATEVAL_NOEND, unix: DT_NOBEGIN };
+    let json = serde_json::to_string(&inf).unwrap();
+    assert_eq!(r#"{"date":"infinity","jd":2147483647,"unix":-9223372036854775808}"#, json);
+    assert_eq!(inf, serde_json::from_str(&json).unwrap());
+
+    // finite values just inside the sentinels are out of range
+    assert!(serde_json::from_str::<Inf>(
+      r#"{"date":"infinity","jd":-2147483647,"unix":0}"#).is_err());
+    assert!(serde_json::from_str::<Inf>(
+      r#"{"date":"infinity","jd":2147483646,"unix":0}"#).is_err());
+    assert!(serde_json::from_str::<Inf>(
+      r#"{"date":"infinity","jd":0,"unix":-300000000000000000}"#).is_err());
+    assert!(serde_json::from_str::<Inf>(r#"{"date":"infinity","jd":0,"unix":0}"#).is_ok());
+  }
+
+  #[test]
+  fn test_interval() {
+    #[derive(Serialize, Deserialize)]
+    struct Spans {
+      fields: Interval,
+      #[serde(with = "::serialize::interval::iso8601")]
+      iso: Interval
+    }
+
+    let span = Interval::new(14, 3, time2t(4, 5, 6, 0));
+    let json = serde_json::to_string(&Spans { fields: span, iso: span }).unwrap();
+    assert_eq!(r#"{"fields":{"month":14,"day":3,"time":14706000000},"iso":"P1Y2M3DT4H5M6S"}"#,
+               json);
+
+    let back: Spans = serde_json::from_str(&json).unwrap();
+    for s in [back.fields, back.iso].iter() {
+      assert_eq!((14, 3, time2t(4, 5, 6, 0)), (s.month(), s.day(), s.time()));
+    }
+
+    assert!(serde_json::from_str::<Spans>(r#"{"fields":{"month":1},"iso":"PT0S"}"#).is_err());
+    assert!(serde_json::from_str::<Spans>(
+      r#"{"fields":{"month":1,"day":0,"time":0},"iso":"1 day"}"#).is_err());
+  }
+}